@@ -1,30 +1,98 @@
 use std::{
-    error::Error,
     fs,
     path::{Path, PathBuf},
 };
 
 use crate::{
-    operations,
-    util::{self, PathStringUtil},
+    ExportControl, ExportProgress, ExportStage, LoopPoints, MusicExportError, loop_points,
+    operations::{self, LoudnessMeasurement},
+    util::{self, PathStringUtil, TempFiles},
 };
 
 const FAMITRACKER_SILENCE_START: f64 = 0.084;
 const FAMITRACKER_SILENCE_END: f64 = 0.1;
+/// [TrimMode::Silence] threshold when none is given.
+pub const DEFAULT_SILENCE_THRESHOLD_DBFS: f64 = -60.;
+/// [TrimMode::Silence] margin when none is given.
+pub const DEFAULT_SILENCE_MARGIN_SECS: f64 = 0.02;
+/// [LoudnessTarget] loudness when none is given.
+pub const DEFAULT_TARGET_LUFS: f64 = -16.;
+/// [LoudnessTarget] ceiling when none is given.
+pub const DEFAULT_TRUE_PEAK_CEILING_DBTP: f64 = -1.;
+/// Written next to the OGGs by [export_as_game_music].
+pub const LOOP_HELPER_FILE_NAME: &str = "music_loop_points.gml";
+
+/// How much of each WAV to cut before export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrimMode {
+    /// Cut exactly this much. For seamless loops, whose length must not
+    /// depend on what the first and last samples happen to be.
+    Exact { start_secs: f64, end_secs: f64 },
+    /// Cut leading and trailing silence: every frame quieter than
+    /// `threshold_dbfs` on all channels, except `margin_secs` next to the sound.
+    Silence {
+        threshold_dbfs: f64,
+        margin_secs: f64,
+    },
+}
 
 pub struct GameMusicExportOptions {
-    pub trim_start_secs: f64,
-    pub trim_end_secs: f64,
+    pub trim: TrimMode,
+    /// Game music only: bring every song to the same loudness. `None` keeps
+    /// each song as loud as it was mixed.
+    pub loudness: Option<LoudnessTarget>,
 }
 impl GameMusicExportOptions {
     pub fn famitracker_defaults() -> Self {
         Self {
-            trim_start_secs: FAMITRACKER_SILENCE_START,
-            trim_end_secs: FAMITRACKER_SILENCE_END,
+            trim: TrimMode::Exact {
+                start_secs: FAMITRACKER_SILENCE_START,
+                end_secs: FAMITRACKER_SILENCE_END,
+            },
+            loudness: None,
+        }
+    }
+}
+
+/// EBU R128 loudness normalization. Each song gets a single gain, so loops
+/// and dynamics are untouched, and a song that would peak above
+/// `true_peak_ceiling_dbtp` is left quieter than the target instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessTarget {
+    pub integrated_lufs: f64,
+    pub true_peak_ceiling_dbtp: f64,
+}
+impl LoudnessTarget {
+    /// The gain that brings `measured` to the target without going over the
+    /// ceiling. Silence gets none.
+    fn adjust(&self, measured: LoudnessMeasurement) -> LoudnessAdjustment {
+        if !measured.integrated_lufs.is_finite() || !measured.true_peak_dbtp.is_finite() {
+            return LoudnessAdjustment {
+                measured,
+                gain_db: 0.,
+                peak_limited: false,
+            };
+        }
+        let to_target = self.integrated_lufs - measured.integrated_lufs;
+        let to_ceiling = self.true_peak_ceiling_dbtp - measured.true_peak_dbtp;
+        LoudnessAdjustment {
+            measured,
+            gain_db: to_target.min(to_ceiling),
+            peak_limited: to_ceiling < to_target,
         }
     }
 }
 
+/// How a song was normalized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessAdjustment {
+    /// The trimmed song before normalizing.
+    pub measured: LoudnessMeasurement,
+    pub gain_db: f64,
+    /// The true-peak ceiling kept the song below the target loudness.
+    pub peak_limited: bool,
+}
+
 pub enum Mp4LoopOption {
     SetValue(u32),
     /// Example: have shorter songs get looped longer (3x) other songs will just get looped 2x
@@ -53,6 +121,23 @@ impl Mp4ExportOptions {
 
 pub struct MusicExportResult {
     pub num_files_exported: usize,
+    /// One entry per exported file, in export order.
+    pub tracks: Vec<ExportedTrack>,
+}
+
+pub struct ExportedTrack {
+    /// The WAV file in the music folder.
+    pub source: PathBuf,
+    pub output: PathBuf,
+    pub trimmed_start_secs: f64,
+    pub trimmed_end_secs: f64,
+    /// Length after trimming.
+    pub duration_secs: f64,
+    pub channels: u16,
+    /// Game music only: the loop written into the OGG, if the song has one.
+    pub loop_points: Option<LoopPoints>,
+    /// Game music only: set when [GameMusicExportOptions::loudness] is.
+    pub loudness: Option<LoudnessAdjustment>,
 }
 
 /// game_title does not need to include 'OST' in it
@@ -60,19 +145,18 @@ pub fn export_as_mp4_files(
     project_folder_path: &Path,
     options: &GameMusicExportOptions,
     mp4_options: &Mp4ExportOptions,
-) -> Result<MusicExportResult, Box<dyn Error>> {
+    control: &mut ExportControl,
+) -> Result<MusicExportResult, MusicExportError> {
     let music_folder_path = get_music_folder_path(project_folder_path)?;
 
     let output_music_folder_path = music_folder_path.join("Mp4GameMusic");
-    fs::create_dir_all(&output_music_folder_path)?;
+    fs::create_dir_all(&output_music_folder_path)
+        .map_err(|e| MusicExportError::io(&output_music_folder_path, e))?;
 
-    let music_files: Vec<PathBuf> = music_folder_path
-        .read_dir()?
-        .flatten()
-        .map(|f| f.path())
-        .filter(|path| path.extension().unwrap_or_default() == "wav")
-        .collect();
+    let music_files = wav_files(&music_folder_path)?;
+    let total = music_files.len();
     let mut num_files_exported = 0;
+    let mut tracks = Vec::new();
 
     let Mp4ExportOptions {
         video_image_path,
@@ -83,8 +167,15 @@ pub fn export_as_mp4_files(
     } = mp4_options;
 
     for (i, music_file) in music_files.iter().enumerate() {
+        let progress = |stage| ExportProgress {
+            file: music_file,
+            index: i,
+            total,
+            stage,
+        };
+        let in_song = |e: MusicExportError| e.for_song(music_file);
         let input_path_filename = music_file.unwrap_filename();
-        let input_path = music_file.into_string(); // The full input path
+        let input_path = music_file.to_path_string(); // The full input path
 
         // Ex: 3 becomes 03 and 12 just becomes 12
         let output_filename_prefix = format!("{:02}. ", i);
@@ -100,25 +191,22 @@ pub fn export_as_mp4_files(
                 "{}.trimmed.wav",
                 input_path_filename.trim_end_matches(".wav")
             ))
-            .into_string();
-
-        let trim_start_secs = options.trim_start_secs;
-        let trim_end_secs = options.trim_end_secs;
-
-        let trim_result = operations::trim_wav(
-            &input_path,
-            &temp_trimmed_wav_path,
-            trim_start_secs,
-            trim_end_secs,
-        )?;
-        // Done trimming. Export production ver:
-
+            .to_path_string();
         let temp_prod_wav_path = output_music_folder_path
             .join(format!(
                 "{}.prod.wav",
                 input_path_filename.trim_end_matches(".wav")
             ))
-            .into_string();
+            .to_path_string();
+        let _temp_files = TempFiles(vec![
+            temp_trimmed_wav_path.clone(),
+            temp_prod_wav_path.clone(),
+        ]);
+
+        control.enter(progress(ExportStage::Trim), num_files_exported)?;
+        let trim_result = operations::trim_wav(&input_path, &temp_trimmed_wav_path, &options.trim)
+            .map_err(in_song)?;
+        // Done trimming. Export production ver:
 
         let loop_num = match loops {
             Mp4LoopOption::SetValue(val) => val,
@@ -131,83 +219,159 @@ pub fn export_as_mp4_files(
             }
         };
 
+        control.enter(progress(ExportStage::Master), num_files_exported)?;
         operations::export_production_wav_file(
             &temp_trimmed_wav_path,
             &temp_prod_wav_path,
             *loop_num,
             *fade_duration_secs,
             *lead_in_silence_secs,
-        )?;
-        // Delete the temp trimmed wav
-        fs::remove_file(&temp_trimmed_wav_path)?;
+        )
+        .map_err(in_song)?;
 
         let output_mp4_path = output_music_folder_path
             .join(output_mp4_filename)
-            .into_string();
-        operations::export_production_mp4(&temp_prod_wav_path, &output_mp4_path, video_image_path)?;
-        // Delete the temp prod wav:
-        fs::remove_file(&temp_prod_wav_path)?;
+            .to_path_string();
+        control.enter(progress(ExportStage::RenderVideo), num_files_exported)?;
+        operations::export_production_mp4(&temp_prod_wav_path, &output_mp4_path, video_image_path)
+            .map_err(in_song)?;
+        // The temp wavs are deleted when `_temp_files` drops.
 
         num_files_exported += 1;
+        tracks.push(ExportedTrack {
+            source: music_file.clone(),
+            output: output_mp4_path.into(),
+            trimmed_start_secs: trim_result.trimmed_start_secs,
+            trimmed_end_secs: trim_result.trimmed_end_secs,
+            duration_secs: trim_result.new_duration_secs,
+            channels: trim_result.channels,
+            loop_points: None,
+            loudness: None,
+        });
+        control.report(progress(ExportStage::Done));
     }
 
-    Ok(MusicExportResult { num_files_exported })
+    Ok(MusicExportResult {
+        num_files_exported,
+        tracks,
+    })
 }
 
 /// goes inside the 'music' folder at `project_folder_path` and takes all of the wav files in there and exports them to be usable in GameMaker:
 /// ex: song1.wav --> sndSong1.ogg
+/// Loop points from `song1.loop` or the WAV's markers go into the OGG and [LOOP_HELPER_FILE_NAME].
 pub fn export_as_game_music(
     project_folder_path: &Path,
     options: &GameMusicExportOptions,
-) -> Result<MusicExportResult, Box<dyn Error>> {
+    control: &mut ExportControl,
+) -> Result<MusicExportResult, MusicExportError> {
     let music_folder_path = get_music_folder_path(project_folder_path)?;
 
     let output_music_folder_path = music_folder_path.join("GameMusic");
-    fs::create_dir_all(&output_music_folder_path)?;
-
-    let music_files: Vec<PathBuf> = music_folder_path
-        .read_dir()?
-        .flatten()
-        .map(|f| f.path())
-        .filter(|path| path.extension().unwrap_or_default() == "wav")
-        .collect();
+    fs::create_dir_all(&output_music_folder_path)
+        .map_err(|e| MusicExportError::io(&output_music_folder_path, e))?;
 
+    let music_files = wav_files(&music_folder_path)?;
+    let total = music_files.len();
     let mut num_files_exported = 0;
-    for music_file in music_files {
+    let mut tracks = Vec::new();
+    for (i, music_file) in music_files.iter().enumerate() {
+        let progress = |stage| ExportProgress {
+            file: music_file,
+            index: i,
+            total,
+            stage,
+        };
+        let in_song = |e: MusicExportError| e.for_song(music_file);
         let input_path_filename = music_file.unwrap_filename();
-        let input_path = music_file.into_string(); // The full input path
+        let input_path = music_file.to_path_string(); // The full input path
 
         let output_filename = "snd".to_string()
             + &util::convert_to_pascal_case(&input_path_filename.replace(".wav", ""))
             + ".ogg";
 
-        let output_ogg_path = output_music_folder_path.join(output_filename).into_string();
+        let output_ogg_path = output_music_folder_path
+            .join(output_filename)
+            .to_path_string();
         let temp_trimmed_wav_path = output_music_folder_path
             .join(format!(
                 "{}.trimmed.wav",
                 input_path_filename.trim_end_matches(".wav")
             ))
-            .into_string();
-
-        let trim_start_secs = options.trim_start_secs;
-        let trim_end_secs = options.trim_end_secs;
-
-        operations::trim_wav(
-            &input_path,
+            .to_path_string();
+        let _temp_files = TempFiles(vec![temp_trimmed_wav_path.clone()]);
+
+        control.enter(progress(ExportStage::Trim), num_files_exported)?;
+        let trim_result = operations::trim_wav(&input_path, &temp_trimmed_wav_path, &options.trim)
+            .map_err(in_song)?;
+        let loop_points = loop_points::read(music_file, &trim_result)?;
+        let loudness = match &options.loudness {
+            Some(target) => {
+                control.enter(progress(ExportStage::MeasureLoudness), num_files_exported)?;
+                let measured =
+                    operations::measure_loudness(&temp_trimmed_wav_path).map_err(in_song)?;
+                Some(target.adjust(measured))
+            }
+            None => None,
+        };
+        control.enter(progress(ExportStage::EncodeOgg), num_files_exported)?;
+        operations::wav_to_ogg(
             &temp_trimmed_wav_path,
-            trim_start_secs,
-            trim_end_secs,
-        )?;
-        operations::wav_to_ogg(&temp_trimmed_wav_path, &output_ogg_path)?;
-        fs::remove_file(&temp_trimmed_wav_path)?;
+            &output_ogg_path,
+            loop_points.as_ref(),
+            loudness.map(|adjustment| adjustment.gain_db),
+        )
+        .map_err(in_song)?;
         num_files_exported += 1;
+        tracks.push(ExportedTrack {
+            source: music_file.clone(),
+            output: output_ogg_path.into(),
+            trimmed_start_secs: trim_result.trimmed_start_secs,
+            trimmed_end_secs: trim_result.trimmed_end_secs,
+            duration_secs: trim_result.new_duration_secs,
+            channels: trim_result.channels,
+            loop_points,
+            loudness,
+        });
+        control.report(progress(ExportStage::Done));
     }
 
-    Ok(MusicExportResult { num_files_exported })
+    // Rewritten on every export so songs that lost their loop drop out of it.
+    let loops: Vec<(String, LoopPoints)> = tracks
+        .iter()
+        .filter_map(|track| {
+            let sound_name = track.output.file_stem()?.to_string_lossy().into_owned();
+            Some((sound_name, track.loop_points?))
+        })
+        .collect();
+    let helper_path = output_music_folder_path.join(LOOP_HELPER_FILE_NAME);
+    fs::write(&helper_path, loop_points::gml_helper(&loops))
+        .map_err(|e| MusicExportError::io(&helper_path, e))?;
+
+    Ok(MusicExportResult {
+        num_files_exported,
+        tracks,
+    })
+}
+
+/// The `.wav` files directly inside `music_folder_path`, sorted by name so
+/// the export order (and the MP4 track numbers) are stable.
+fn wav_files(music_folder_path: &Path) -> Result<Vec<PathBuf>, MusicExportError> {
+    let mut music_files: Vec<PathBuf> = music_folder_path
+        .read_dir()
+        .map_err(|e| MusicExportError::io(music_folder_path, e))?
+        .flatten()
+        .map(|f| f.path())
+        .filter(|path| path.extension().unwrap_or_default() == "wav")
+        .collect();
+    music_files.sort();
+    Ok(music_files)
 }
 
-fn get_music_folder_path(project_folder_path: &Path) -> Result<PathBuf, Box<dyn Error>> {
-    let dirs = project_folder_path.read_dir()?;
+fn get_music_folder_path(project_folder_path: &Path) -> Result<PathBuf, MusicExportError> {
+    let dirs = project_folder_path
+        .read_dir()
+        .map_err(|e| MusicExportError::io(project_folder_path, e))?;
 
     for dir in dirs.into_iter().flatten() {
         let entry_path = dir.path();
@@ -220,5 +384,55 @@ fn get_music_folder_path(project_folder_path: &Path) -> Result<PathBuf, Box<dyn
         }
     }
 
-    Err("Music folder was not found".into())
+    Err(MusicExportError::MusicFolderNotFound {
+        project_folder: project_folder_path.to_path_buf(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: LoudnessTarget = LoudnessTarget {
+        integrated_lufs: -16.,
+        true_peak_ceiling_dbtp: -1.,
+    };
+
+    fn measured(integrated_lufs: f64, true_peak_dbtp: f64) -> LoudnessMeasurement {
+        LoudnessMeasurement {
+            integrated_lufs,
+            true_peak_dbtp,
+            loudness_range_lu: 5.,
+        }
+    }
+
+    #[test]
+    fn quiet_song_is_brought_up_to_the_target() {
+        let adjustment = TARGET.adjust(measured(-20., -8.));
+        assert_eq!(adjustment.gain_db, 4.);
+        assert!(!adjustment.peak_limited);
+    }
+
+    #[test]
+    fn loud_song_is_brought_down_to_the_target() {
+        let adjustment = TARGET.adjust(measured(-10., -0.5));
+        assert_eq!(adjustment.gain_db, -6.);
+        assert!(!adjustment.peak_limited);
+    }
+
+    #[test]
+    fn ceiling_keeps_a_peaky_song_below_the_target() {
+        let adjustment = TARGET.adjust(measured(-22., -3.));
+        assert_eq!(adjustment.gain_db, 2.);
+        assert!(adjustment.peak_limited);
+    }
+
+    #[test]
+    fn silence_gets_no_gain() {
+        let silence = measured(f64::NEG_INFINITY, f64::NEG_INFINITY);
+        let adjustment = TARGET.adjust(silence);
+        assert_eq!(adjustment.gain_db, 0.);
+        assert!(!adjustment.peak_limited);
+        assert_eq!(adjustment.measured, silence);
+    }
 }
@@ -0,0 +1,265 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Everything a music export can fail with. Errors about a single song carry
+/// the WAV file from the music folder it happened to.
+#[derive(Debug)]
+pub enum MusicExportError {
+    /// No `music` / `Music` folder in the project folder.
+    MusicFolderNotFound {
+        project_folder: PathBuf,
+    },
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// `ffmpeg` or `ffprobe` couldn't be started; FFmpeg needs to be on the PATH.
+    FfmpegMissing {
+        program: &'static str,
+        file: PathBuf,
+    },
+    /// `ffmpeg` or `ffprobe` ran but exited with an error.
+    FfmpegFailed {
+        program: &'static str,
+        file: PathBuf,
+        stderr: String,
+    },
+    /// A WAV that hound can't read, e.g. a compressed or malformed file.
+    UnsupportedWavFormat {
+        file: PathBuf,
+        reason: String,
+    },
+    TooShortToTrim {
+        file: PathBuf,
+        duration_secs: f64,
+        trim_start_secs: f64,
+        trim_end_secs: f64,
+    },
+    /// Silence trimming found no sample louder than the threshold.
+    EntirelySilent {
+        file: PathBuf,
+        threshold_dbfs: f64,
+    },
+    /// A sidecar `.loop` file that can't be parsed, or loop points that
+    /// don't fit the trimmed song.
+    InvalidLoopPoints {
+        file: PathBuf,
+        reason: String,
+    },
+    /// `ffprobe` printed something that isn't a duration.
+    FfprobeParse {
+        file: PathBuf,
+        output: String,
+    },
+    /// `ffmpeg`'s volumedetect output had no `max_volume`.
+    VolumeDetectParse {
+        file: PathBuf,
+    },
+    /// `ffmpeg`'s loudnorm analysis had no measurements.
+    LoudnessParse {
+        file: PathBuf,
+    },
+    FadeLongerThanLoop {
+        file: PathBuf,
+        fade_duration_secs: f64,
+        loop_duration_secs: f64,
+    },
+    /// The export was cancelled through its [crate::CancellationToken].
+    Cancelled {
+        files_exported: usize,
+    },
+}
+
+impl MusicExportError {
+    /// The song the error is about, if it's about one.
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            Self::FfmpegMissing { file, .. }
+            | Self::FfmpegFailed { file, .. }
+            | Self::UnsupportedWavFormat { file, .. }
+            | Self::TooShortToTrim { file, .. }
+            | Self::EntirelySilent { file, .. }
+            | Self::InvalidLoopPoints { file, .. }
+            | Self::FfprobeParse { file, .. }
+            | Self::VolumeDetectParse { file }
+            | Self::LoudnessParse { file }
+            | Self::FadeLongerThanLoop { file, .. } => Some(file),
+            Self::MusicFolderNotFound { .. } | Self::Io { .. } | Self::Cancelled { .. } => None,
+        }
+    }
+
+    /// Blame the song being exported rather than the temp file an operation
+    /// was working on.
+    pub(crate) fn for_song(mut self, song: &Path) -> Self {
+        match &mut self {
+            Self::FfmpegMissing { file, .. }
+            | Self::FfmpegFailed { file, .. }
+            | Self::UnsupportedWavFormat { file, .. }
+            | Self::TooShortToTrim { file, .. }
+            | Self::EntirelySilent { file, .. }
+            | Self::InvalidLoopPoints { file, .. }
+            | Self::FfprobeParse { file, .. }
+            | Self::VolumeDetectParse { file }
+            | Self::LoudnessParse { file }
+            | Self::FadeLongerThanLoop { file, .. } => *file = song.to_path_buf(),
+            Self::MusicFolderNotFound { .. } | Self::Io { .. } | Self::Cancelled { .. } => {}
+        }
+        self
+    }
+
+    pub(crate) fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn wav(file: &str, error: hound::Error) -> Self {
+        match error {
+            // hound reports a truncated file as an I/O error too.
+            hound::Error::IoError(source)
+                if matches!(
+                    source.kind(),
+                    io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied
+                ) =>
+            {
+                Self::io(file, source)
+            }
+            other => Self::UnsupportedWavFormat {
+                file: file.into(),
+                reason: other.to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for MusicExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MusicFolderNotFound { project_folder } => {
+                write!(f, "no music folder found in {}", project_folder.display())
+            }
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::FfmpegMissing { program, file } => write!(
+                f,
+                "{}: {program} was not found; install FFmpeg and make sure it is on the PATH",
+                file.display()
+            ),
+            Self::FfmpegFailed {
+                program,
+                file,
+                stderr,
+            } => {
+                // The last lines hold the actual error; the rest is banner and stream info.
+                let lines: Vec<&str> = stderr.trim_end().lines().collect();
+                let tail = lines[lines.len().saturating_sub(3)..].join("\n");
+                write!(f, "{}: {program} failed: {tail}", file.display())
+            }
+            Self::UnsupportedWavFormat { file, reason } => {
+                write!(f, "{}: unsupported WAV file: {reason}", file.display())
+            }
+            Self::TooShortToTrim {
+                file,
+                duration_secs,
+                trim_start_secs,
+                trim_end_secs,
+            } => write!(
+                f,
+                "{}: too short ({duration_secs:.3}s) to trim {trim_start_secs}s from the start and {trim_end_secs}s from the end",
+                file.display()
+            ),
+            Self::EntirelySilent {
+                file,
+                threshold_dbfs,
+            } => write!(
+                f,
+                "{}: nothing louder than {threshold_dbfs} dBFS to keep",
+                file.display()
+            ),
+            Self::InvalidLoopPoints { file, reason } => {
+                write!(f, "{}: invalid loop points: {reason}", file.display())
+            }
+            Self::FfprobeParse { file, output } => write!(
+                f,
+                "{}: could not read a duration from ffprobe output {:?}",
+                file.display(),
+                output.trim()
+            ),
+            Self::VolumeDetectParse { file } => write!(
+                f,
+                "{}: ffmpeg volumedetect reported no max_volume",
+                file.display()
+            ),
+            Self::LoudnessParse { file } => write!(
+                f,
+                "{}: ffmpeg loudnorm reported no loudness measurements",
+                file.display()
+            ),
+            Self::FadeLongerThanLoop {
+                file,
+                fade_duration_secs,
+                loop_duration_secs,
+            } => write!(
+                f,
+                "{}: fade duration ({fade_duration_secs}s) is longer than a single loop ({loop_duration_secs}s)",
+                file.display()
+            ),
+            Self::Cancelled { files_exported } => {
+                write!(f, "export cancelled after {files_exported} file(s)")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MusicExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn song_errors_blame_the_song_instead_of_the_temp_file() {
+        let song = Path::new("music/title.wav");
+        let error = MusicExportError::EntirelySilent {
+            file: "title.trimmed.wav".into(),
+            threshold_dbfs: -60.,
+        }
+        .for_song(song);
+        assert_eq!(error.file(), Some(song));
+
+        let error = MusicExportError::FfmpegFailed {
+            program: "ffmpeg",
+            file: "title.trimmed.wav".into(),
+            stderr: String::new(),
+        }
+        .for_song(song);
+        assert_eq!(error.file(), Some(song));
+    }
+
+    #[test]
+    fn errors_about_no_song_are_left_alone() {
+        let song = Path::new("music/title.wav");
+        let error = MusicExportError::io("title.trimmed.wav", io::ErrorKind::NotFound.into())
+            .for_song(song);
+        let MusicExportError::Io { path, .. } = &error else {
+            panic!("{error}");
+        };
+        assert_eq!(path, Path::new("title.trimmed.wav"));
+        assert_eq!(error.file(), None);
+
+        let error = MusicExportError::Cancelled { files_exported: 2 }.for_song(song);
+        assert!(matches!(
+            error,
+            MusicExportError::Cancelled { files_exported: 2 }
+        ));
+    }
+}
@@ -1,4 +1,10 @@
 mod api;
-mod operations;
+mod error;
+mod loop_points;
+pub mod operations;
+mod progress;
 pub use api::*;
-mod util;
\ No newline at end of file
+pub use error::*;
+pub use loop_points::{LoopPoints, gml_helper};
+pub use progress::*;
+mod util;
@@ -0,0 +1,520 @@
+//! Where a song loops: read from a sidecar `<song>.loop` file or the WAV's
+//! `smpl` / `cue ` chunks, written to the OGG as `LOOPSTART` / `LOOPLENGTH`
+//! Vorbis comments, and exposed to GML through a generated helper.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{MusicExportError, operations::TrimWavResult};
+
+/// A loop in an exported song, in sample frames of the trimmed audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopPoints {
+    pub start_frame: u64,
+    /// Exclusive: playback jumps back to `start_frame` on reaching it.
+    pub end_frame: u64,
+    pub sample_rate: u32,
+}
+
+impl LoopPoints {
+    pub fn length_frames(&self) -> u64 {
+        self.end_frame - self.start_frame
+    }
+
+    pub fn start_secs(&self) -> f64 {
+        self.start_frame as f64 / self.sample_rate as f64
+    }
+
+    pub fn end_secs(&self) -> f64 {
+        self.end_frame as f64 / self.sample_rate as f64
+    }
+}
+
+/// A loop in the source WAV, before trimming. No end loops to the end.
+#[derive(Debug, PartialEq, Eq)]
+struct SourceLoop {
+    start_frame: u64,
+    end_frame: Option<u64>,
+}
+
+/// The loop points of `wav_path` once `trim` has been applied, if it has any.
+/// A sidecar file wins over markers in the WAV itself.
+pub(crate) fn read(
+    wav_path: &Path,
+    trim: &TrimWavResult,
+) -> Result<Option<LoopPoints>, MusicExportError> {
+    let sidecar = wav_path.with_extension("loop");
+    let source = match fs::read_to_string(&sidecar) {
+        Ok(text) => Some(parse_sidecar(&text, trim.sample_rate).map_err(|reason| {
+            MusicExportError::InvalidLoopPoints {
+                file: sidecar.clone(),
+                reason,
+            }
+        })?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            read_wav_markers(wav_path).map_err(|e| MusicExportError::io(wav_path, e))?
+        }
+        Err(e) => return Err(MusicExportError::io(&sidecar, e)),
+    };
+    let Some(source) = source else {
+        return Ok(None);
+    };
+
+    let invalid = |reason: String| MusicExportError::InvalidLoopPoints {
+        file: wav_path.to_path_buf(),
+        reason,
+    };
+    let secs = |frame: u64| frame as f64 / trim.sample_rate as f64;
+    let start_frame = source
+        .start_frame
+        .checked_sub(trim.trimmed_start_frames)
+        .ok_or_else(|| {
+            invalid(format!(
+                "loop start ({:.3}s) is inside the {:.3}s trimmed from the start",
+                secs(source.start_frame),
+                trim.trimmed_start_secs
+            ))
+        })?;
+    // Whatever was trimmed off the end is gone, so a loop can't extend into it.
+    let end_frame = source
+        .end_frame
+        .map_or(trim.kept_frames, |end| {
+            end.saturating_sub(trim.trimmed_start_frames)
+        })
+        .min(trim.kept_frames);
+    if start_frame >= end_frame {
+        return Err(invalid(format!(
+            "loop start ({:.3}s) is not before the loop end ({:.3}s) after trimming",
+            secs(start_frame),
+            secs(end_frame)
+        )));
+    }
+    Ok(Some(LoopPoints {
+        start_frame,
+        end_frame,
+        sample_rate: trim.sample_rate,
+    }))
+}
+
+/// `start = <secs>` and an optional `end = <secs>`, one per line; `#` starts
+/// a comment.
+fn parse_sidecar(text: &str, sample_rate: u32) -> Result<SourceLoop, String> {
+    let mut start = None;
+    let mut end = None;
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("expected `key = seconds`, found `{line}`"));
+        };
+        let secs: f64 = value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|secs: &f64| secs.is_finite() && *secs >= 0.)
+            .ok_or_else(|| format!("`{}` is not a number of seconds", value.trim()))?;
+        let frame = (secs * sample_rate as f64).round() as u64;
+        match key.trim() {
+            "start" => start = Some(frame),
+            "end" => end = Some(frame),
+            other => return Err(format!("unknown key `{other}`; expected `start` or `end`")),
+        }
+    }
+    let start_frame = start.ok_or("missing `start = <secs>`")?;
+    Ok(SourceLoop {
+        start_frame,
+        end_frame: end,
+    })
+}
+
+/// The first loop of a `smpl` chunk, else the `cue ` points labelled
+/// "loop start" / "loop end", else a lone cue point (loop start) or a pair.
+fn read_wav_markers(wav_path: &Path) -> io::Result<Option<SourceLoop>> {
+    let chunks = riff_chunks(wav_path)?;
+    let chunk = |id: &[u8; 4]| chunks.iter().find(|(i, _)| i == id).map(|(_, d)| d);
+
+    if let Some(smpl) = chunk(b"smpl")
+        && u32_at(smpl, 28).is_some_and(|loops| loops > 0)
+        && let (Some(start), Some(end)) = (u32_at(smpl, 36 + 8), u32_at(smpl, 36 + 12))
+    {
+        // The end sample is the last one played, so one past it is exclusive.
+        return Ok(Some(SourceLoop {
+            start_frame: start.into(),
+            end_frame: Some(u64::from(end) + 1),
+        }));
+    }
+
+    let Some(cue) = chunk(b"cue ") else {
+        return Ok(None);
+    };
+    // (cue id, sample offset)
+    let mut points: Vec<(u32, u64)> = (0..u32_at(cue, 0).unwrap_or(0) as usize)
+        .map_while(|i| {
+            let at = 4 + i * 24;
+            Some((u32_at(cue, at)?, u32_at(cue, at + 20)?.into()))
+        })
+        .collect();
+    points.sort_by_key(|&(_, frame)| frame);
+
+    let labels = cue_labels(&chunks);
+    let labelled = |name: &str| {
+        points.iter().find_map(|(id, frame)| {
+            labels
+                .iter()
+                .any(|(label_id, label)| label_id == id && label == name)
+                .then_some(*frame)
+        })
+    };
+    let source = match (labelled("loopstart"), labelled("loopend"), &points[..]) {
+        (Some(start_frame), end_frame, _) => Some(SourceLoop {
+            start_frame,
+            end_frame,
+        }),
+        (None, _, [(_, start)]) => Some(SourceLoop {
+            start_frame: *start,
+            end_frame: None,
+        }),
+        (None, _, [(_, start), (_, end)]) => Some(SourceLoop {
+            start_frame: *start,
+            end_frame: Some(*end),
+        }),
+        _ => None,
+    };
+    Ok(source)
+}
+
+/// `labl` entries of `LIST`/`adtl` chunks as (cue id, label), the label
+/// lowercased with spaces, `_` and `-` removed.
+fn cue_labels(chunks: &[([u8; 4], Vec<u8>)]) -> Vec<(u32, String)> {
+    let mut labels = Vec::new();
+    for (_, list) in chunks
+        .iter()
+        .filter(|(id, data)| id == b"LIST" && data.starts_with(b"adtl"))
+    {
+        let mut at = 4;
+        while let (Some(id), Some(size)) = (list.get(at..at + 4), u32_at(list, at + 4)) {
+            let body = at + 8;
+            let end = (body + size as usize).min(list.len());
+            if id == b"labl"
+                && let Some(cue_id) = u32_at(list, body)
+            {
+                let text = &list[(body + 4).min(end)..end];
+                let text = text.split(|&b| b == 0).next().unwrap_or_default();
+                let label = String::from_utf8_lossy(text)
+                    .chars()
+                    .filter(|c| !matches!(c, ' ' | '_' | '-'))
+                    .collect::<String>()
+                    .to_lowercase();
+                labels.push((cue_id, label));
+            }
+            at = end + (size as usize & 1);
+        }
+    }
+    labels
+}
+
+/// Every top-level RIFF chunk except `data`, which is skipped over rather
+/// than read.
+fn riff_chunks(path: &Path) -> io::Result<Vec<([u8; 4], Vec<u8>)>> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(Vec::new());
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk_header = [0u8; 8];
+    while file.read_exact(&mut chunk_header).is_ok() {
+        let id: [u8; 4] = chunk_header[0..4].try_into().expect("4 bytes");
+        let size = u32_at(&chunk_header, 4).expect("8 bytes");
+        // Chunks are padded to an even size.
+        let padded = u64::from(size) + u64::from(size & 1);
+        if &id == b"data" {
+            file.seek(SeekFrom::Current(padded as i64))?;
+            continue;
+        }
+        let mut data = Vec::new();
+        (&mut file).take(size.into()).read_to_end(&mut data)?;
+        if size & 1 == 1 {
+            file.seek(SeekFrom::Current(1))?;
+        }
+        chunks.push((id, data));
+    }
+    Ok(chunks)
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Option<u32> {
+    let bytes = bytes.get(at..at + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// A GML script with `music_loop_points(sound)`, returning the loop of each
+/// exported `snd*` asset in seconds (what `audio_sound_loop_start` and
+/// `audio_sound_loop_end` take), or `undefined` for songs without one.
+pub fn gml_helper(loops: &[(String, LoopPoints)]) -> String {
+    let mut gml = String::from(
+        "// Generated by `gmhelper music`; re-export the music instead of editing this file.\n\
+         \n\
+         /// @description Loop points of a music asset in seconds, or undefined if it has none.\n\
+         /// @param {Asset.GMSound} sound\n\
+         /// @returns {Struct|Undefined}\n\
+         function music_loop_points(sound)\n\
+         {\n\
+         \tswitch (audio_get_name(sound))\n\
+         \t{\n",
+    );
+    for (sound_name, points) in loops {
+        gml.push_str(&format!(
+            "\t\tcase \"{sound_name}\": return {{ loop_start: {:.6}, loop_end: {:.6} }};\n",
+            points.start_secs(),
+            points.end_secs()
+        ));
+    }
+    gml.push_str("\t}\n\treturn undefined;\n}\n");
+    gml
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, path::PathBuf};
+
+    use super::*;
+
+    /// A path in the temp folder, unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ost_export {} {name}", std::process::id()))
+    }
+
+    /// A short silent WAV with `chunks` after its `data` chunk.
+    fn wav_with_chunks(name: &str, chunks: &[(&[u8; 4], Vec<u8>)]) -> PathBuf {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut wav = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut wav, spec).unwrap();
+        for _ in 0..10 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let mut bytes = wav.into_inner();
+        for (id, data) in chunks {
+            bytes.extend_from_slice(*id);
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend(data);
+            if data.len() % 2 == 1 {
+                bytes.push(0);
+            }
+        }
+        let riff_size = (bytes.len() - 8) as u32;
+        bytes[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        let path = temp_path(&format!("{name}.wav"));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn le(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|w| w.to_le_bytes()).collect()
+    }
+
+    /// A `smpl` chunk with one loop.
+    fn smpl(start: u32, end: u32) -> (&'static [u8; 4], Vec<u8>) {
+        // Header fields, one loop, then the loop's cue id, type, start, end,
+        // fraction and play count.
+        (
+            b"smpl",
+            le(&[0, 0, 0, 60, 0, 0, 0, 1, 0, 0, 0, start, end, 0, 0]),
+        )
+    }
+
+    /// A `cue ` chunk of (cue id, sample offset) points.
+    fn cue(points: &[(u32, u32)]) -> (&'static [u8; 4], Vec<u8>) {
+        let mut data = le(&[points.len() as u32]);
+        for &(id, offset) in points {
+            data.extend(le(&[id, offset]));
+            data.extend(b"data");
+            data.extend(le(&[0, 0, offset]));
+        }
+        (b"cue ", data)
+    }
+
+    /// A `LIST`/`adtl` chunk labelling cue points.
+    fn labels(labels: &[(u32, &str)]) -> (&'static [u8; 4], Vec<u8>) {
+        let mut data = b"adtl".to_vec();
+        for &(id, label) in labels {
+            let mut labl = le(&[id]);
+            labl.extend(label.as_bytes());
+            labl.push(0);
+            data.extend(b"labl");
+            data.extend(le(&[labl.len() as u32]));
+            if labl.len() % 2 == 1 {
+                labl.push(0);
+            }
+            data.extend(labl);
+        }
+        (b"LIST", data)
+    }
+
+    fn markers(name: &str, chunks: &[(&[u8; 4], Vec<u8>)]) -> Option<SourceLoop> {
+        let path = wav_with_chunks(name, chunks);
+        let source = read_wav_markers(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        source
+    }
+
+    fn source(start_frame: u64, end_frame: Option<u64>) -> Option<SourceLoop> {
+        Some(SourceLoop {
+            start_frame,
+            end_frame,
+        })
+    }
+
+    #[test]
+    fn sidecar_is_read_in_seconds() {
+        let text = "# loop after the intro\n\nstart = 1.5\nend=3 # bar 12\n";
+        assert_eq!(parse_sidecar(text, 1000).ok(), source(1500, Some(3000)));
+        assert_eq!(
+            parse_sidecar("start = 0.25", 48000).ok(),
+            source(12000, None)
+        );
+    }
+
+    #[test]
+    fn invalid_sidecars_are_refused() {
+        for text in [
+            "",
+            "end = 2",
+            "start 1",
+            "start = soon",
+            "start = -1",
+            "start = 1\nloop = 2",
+        ] {
+            assert!(parse_sidecar(text, 1000).is_err(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn smpl_loop_end_is_made_exclusive() {
+        assert_eq!(markers("smpl", &[smpl(100, 199)]), source(100, Some(200)));
+    }
+
+    #[test]
+    fn smpl_loop_wins_over_cue_points() {
+        let chunks = [cue(&[(1, 5)]), smpl(100, 199)];
+        assert_eq!(markers("smpl and cue", &chunks), source(100, Some(200)));
+    }
+
+    #[test]
+    fn labelled_cue_points_are_used() {
+        let chunks = [
+            cue(&[(1, 300), (2, 10), (3, 50)]),
+            labels(&[(1, "loop_end"), (2, "intro"), (3, "Loop Start")]),
+        ];
+        assert_eq!(markers("labelled", &chunks), source(50, Some(300)));
+    }
+
+    #[test]
+    fn unlabelled_cue_points_are_a_start_or_a_pair() {
+        assert_eq!(markers("one cue", &[cue(&[(1, 40)])]), source(40, None));
+        assert_eq!(
+            markers("two cues", &[cue(&[(1, 90), (2, 30)])]),
+            source(30, Some(90))
+        );
+        assert_eq!(
+            markers("three cues", &[cue(&[(1, 1), (2, 2), (3, 3)])]),
+            None
+        );
+        assert_eq!(markers("no markers", &[]), None);
+    }
+
+    fn trim_result(trimmed_start_frames: u64, kept_frames: u64) -> TrimWavResult {
+        TrimWavResult {
+            new_duration_secs: kept_frames as f64 / 1000.,
+            trimmed_start_secs: trimmed_start_frames as f64 / 1000.,
+            trimmed_end_secs: 0.1,
+            sample_rate: 1000,
+            channels: 1,
+            trimmed_start_frames,
+            kept_frames,
+        }
+    }
+
+    /// The loop points of a song with `sidecar` once 100 frames are cut from
+    /// its start and 2000 are kept.
+    fn read_trimmed(name: &str, sidecar: &str) -> Result<Option<LoopPoints>, MusicExportError> {
+        let wav = wav_with_chunks(name, &[]);
+        let sidecar_path = wav.with_extension("loop");
+        fs::write(&sidecar_path, sidecar).unwrap();
+        let points = read(&wav, &trim_result(100, 2000));
+        fs::remove_file(&wav).unwrap();
+        fs::remove_file(&sidecar_path).unwrap();
+        points
+    }
+
+    fn points(start_frame: u64, end_frame: u64) -> Option<LoopPoints> {
+        Some(LoopPoints {
+            start_frame,
+            end_frame,
+            sample_rate: 1000,
+        })
+    }
+
+    #[test]
+    fn loop_points_move_with_the_trimmed_start() {
+        let loop_points = read_trimmed("shifted", "start = 0.5\nend = 1.5").unwrap();
+        assert_eq!(loop_points, points(400, 1400));
+    }
+
+    #[test]
+    fn loop_end_is_the_trimmed_end_when_missing_or_past_it() {
+        assert_eq!(
+            read_trimmed("no end", "start = 0.5").unwrap(),
+            points(400, 2000)
+        );
+        assert_eq!(
+            read_trimmed("late end", "start = 0.5\nend = 9").unwrap(),
+            points(400, 2000)
+        );
+    }
+
+    #[test]
+    fn loop_points_outside_the_trimmed_song_are_refused() {
+        for (name, sidecar) in [
+            ("early start", "start = 0.05"),
+            ("late start", "start = 2.5"),
+        ] {
+            let error = read_trimmed(name, sidecar).unwrap_err();
+            assert!(
+                matches!(error, MusicExportError::InvalidLoopPoints { .. }),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn unreadable_sidecar_is_blamed() {
+        let error = read_trimmed("bad sidecar", "start = soon").unwrap_err();
+        let MusicExportError::InvalidLoopPoints { file, .. } = error else {
+            panic!("{error}");
+        };
+        assert_eq!(file.extension(), Some("loop".as_ref()));
+    }
+
+    #[test]
+    fn song_without_loop_points_has_none() {
+        let wav = wav_with_chunks("no loop", &[]);
+        let loop_points = read(&wav, &trim_result(100, 2000)).unwrap();
+        fs::remove_file(&wav).unwrap();
+        assert_eq!(loop_points, None);
+    }
+}
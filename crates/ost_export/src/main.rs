@@ -1,15 +1,21 @@
 use std::path::Path;
-mod operations;
-use operations::*;
-mod api;
-mod util;
+
+use ost_export::{TrimMode, operations::*};
 
 fn main() {
     let input = "C:\\Users\\grays\\Downloads\\test.wav";
     let output = Path::new(input).with_extension("trimmed.wav");
     let output = output.to_str().expect("invalid path");
 
-    trim_wav(input, output, 0.084, 0.1).expect("Failed to trim file");
+    trim_wav(
+        input,
+        output,
+        &TrimMode::Exact {
+            start_secs: 0.084,
+            end_secs: 0.1,
+        },
+    )
+    .expect("Failed to trim file");
 
     let output_production = Path::new(input).with_extension("production.wav");
     let output_production = output_production.to_str().expect("invalid path");
@@ -23,5 +29,5 @@ fn main() {
         .expect("Failed to export production MP4 file");
 
     let output_ogg = &output.replace(".wav", ".ogg");
-    wav_to_ogg(output, output_ogg).expect("Failed to convert to OGG");
+    wav_to_ogg(output, output_ogg, None, None).expect("Failed to convert to OGG");
 }
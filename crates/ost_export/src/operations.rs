@@ -1,71 +1,274 @@
+use std::process::{Command, Output};
+
+use crate::{LoopPoints, MusicExportError, TrimMode};
+
 pub struct TrimWavResult {
     pub new_duration_secs: f64,
+    /// How much was cut from the start.
+    pub trimmed_start_secs: f64,
+    /// How much was cut from the end.
+    pub trimmed_end_secs: f64,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub trimmed_start_frames: u64,
+    /// Length in sample frames after trimming.
+    pub kept_frames: u64,
 }
 
-/// Trims a WAV file from the start and end
+/// Trims a WAV file from the start and end, keeping its sample format, bit
+/// depth and channel count
 /// # Arguments
 /// * `input_path` - The path to the input WAV file
 /// * `output_path` - The path to the output WAV file
-/// * `trim_start_secs` - The number of seconds to trim from the start
-/// * `trim_end_secs` - The number of seconds to trim from the end
+/// * `mode` - Fixed offsets to cut, or the silence threshold to cut up to
 /// # Returns
-/// * `Ok(())` - If the file was trimmed successfully
+/// * `Ok(TrimWavResult)` - If the file was trimmed successfully
 /// * `Err(e)` - If the file was not trimmed successfully
 pub fn trim_wav(
     input_path: &str,
     output_path: &str,
-    trim_start_secs: f64,
-    trim_end_secs: f64,
-) -> Result<TrimWavResult, Box<dyn std::error::Error>> {
-    let reader = hound::WavReader::open(input_path)?;
-    let spec = reader.spec();
+    mode: &TrimMode,
+) -> Result<TrimWavResult, MusicExportError> {
+    let reader =
+        hound::WavReader::open(input_path).map_err(|e| MusicExportError::wav(input_path, e))?;
+    // Every integer depth (8/16/24/32-bit) reads and writes losslessly as i32.
+    match reader.spec().sample_format {
+        hound::SampleFormat::Float => trim_samples::<f32, _>(reader, input_path, output_path, mode),
+        hound::SampleFormat::Int => trim_samples::<i32, _>(reader, input_path, output_path, mode),
+    }
+}
 
-    let samples_per_second = spec.sample_rate as f64 * spec.channels as f64;
-    let all_samples: Vec<i32> = reader.into_samples::<i32>().collect::<Result<_, _>>()?;
+/// A sample's distance from zero, where 1.0 is full scale.
+trait Level {
+    fn level(self, bits_per_sample: u16) -> f64;
+}
 
-    let skip_start = (trim_start_secs * samples_per_second) as usize;
-    let skip_end = (trim_end_secs * samples_per_second) as usize;
+impl Level for f32 {
+    fn level(self, _bits_per_sample: u16) -> f64 {
+        self.abs() as f64
+    }
+}
 
-    let total = all_samples.len();
-    if total <= skip_start + skip_end {
-        return Err(format!(
-            "File is too short to trim {trim_start_secs}s from start and {trim_end_secs}s from end"
-        )
-        .into());
+impl Level for i32 {
+    fn level(self, bits_per_sample: u16) -> f64 {
+        self.unsigned_abs() as f64 / (1u64 << (bits_per_sample.max(1) - 1)) as f64
     }
+}
+
+fn trim_samples<S: hound::Sample + Level + Copy, R: std::io::Read>(
+    reader: hound::WavReader<R>,
+    input_path: &str,
+    output_path: &str,
+    mode: &TrimMode,
+) -> Result<TrimWavResult, MusicExportError> {
+    let spec = reader.spec();
+    let all_samples: Vec<S> = reader
+        .into_samples::<S>()
+        .collect::<Result<_, _>>()
+        .map_err(|e| MusicExportError::wav(input_path, e))?;
+
+    // Work in whole frames (one sample per channel) so the channels stay in
+    // order. hound refuses a file whose data ends mid-frame.
+    let channels = spec.channels.max(1) as usize;
+    let frames_per_second = spec.sample_rate as f64;
+    let total_frames = all_samples.len() / channels;
+
+    let (skip_start, skip_end) = match *mode {
+        TrimMode::Exact {
+            start_secs,
+            end_secs,
+        } => {
+            let skip_start = (start_secs * frames_per_second) as usize;
+            let skip_end = (end_secs * frames_per_second) as usize;
+            if total_frames <= skip_start + skip_end {
+                return Err(MusicExportError::TooShortToTrim {
+                    file: input_path.into(),
+                    duration_secs: total_frames as f64 / frames_per_second,
+                    trim_start_secs: start_secs,
+                    trim_end_secs: end_secs,
+                });
+            }
+            (skip_start, skip_end)
+        }
+        TrimMode::Silence {
+            threshold_dbfs,
+            margin_secs,
+        } => {
+            let threshold = 10f64.powf(threshold_dbfs / 20.);
+            let is_loud = |frame: &[S]| {
+                frame
+                    .iter()
+                    .any(|&s| s.level(spec.bits_per_sample) > threshold)
+            };
+            let mut frames = all_samples[..total_frames * channels].chunks_exact(channels);
+            let (Some(first_loud), Some(last_loud)) =
+                (frames.clone().position(is_loud), frames.rposition(is_loud))
+            else {
+                return Err(MusicExportError::EntirelySilent {
+                    file: input_path.into(),
+                    threshold_dbfs,
+                });
+            };
+            let margin = (margin_secs * frames_per_second) as usize;
+            (
+                first_loud.saturating_sub(margin),
+                (total_frames - 1 - last_loud).saturating_sub(margin),
+            )
+        }
+    };
 
-    let trimmed = &all_samples[skip_start..total - skip_end];
+    let kept_frames = total_frames - skip_start - skip_end;
+    let trimmed = &all_samples[skip_start * channels..(skip_start + kept_frames) * channels];
 
-    let mut writer = hound::WavWriter::create(output_path, spec)?;
+    let write_error = |e| match e {
+        hound::Error::IoError(e) => MusicExportError::io(output_path, e),
+        other => MusicExportError::wav(output_path, other),
+    };
+    let mut writer = hound::WavWriter::create(output_path, spec).map_err(write_error)?;
     for &sample in trimmed {
-        writer.write_sample(sample)?;
+        writer.write_sample(sample).map_err(write_error)?;
     }
-    writer.finalize()?;
+    writer.finalize().map_err(write_error)?;
 
     Ok(TrimWavResult {
-        new_duration_secs: (total - skip_start - skip_end) as f64 / samples_per_second,
+        new_duration_secs: kept_frames as f64 / frames_per_second,
+        trimmed_start_secs: skip_start as f64 / frames_per_second,
+        trimmed_end_secs: skip_end as f64 / frames_per_second,
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        trimmed_start_frames: skip_start as u64,
+        kept_frames: kept_frames as u64,
     })
 }
 
-pub fn wav_to_ogg(input_path: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let output = std::process::Command::new("ffmpeg")
-        .args([
-            "-y",
-            "-i",
-            input_path,
-            "-c:a",
-            "libvorbis",
-            "-q:a",
-            "5",
-            output_path,
-        ])
-        .output()?;
+/// Run `ffmpeg` or `ffprobe` on `file`, failing unless it exits successfully.
+fn run(program: &'static str, file: &str, args: &[&str]) -> Result<Output, MusicExportError> {
+    let output = Command::new(program).args(args).output().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            MusicExportError::FfmpegMissing {
+                program,
+                file: file.into(),
+            }
+        } else {
+            MusicExportError::io(file, e)
+        }
+    })?;
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("ffmpeg failed: {stderr}").into());
+        return Err(MusicExportError::FfmpegFailed {
+            program,
+            file: file.into(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
     }
+    Ok(output)
+}
+
+/// Duration of an audio file in seconds, from `ffprobe`.
+fn probe_duration(path: &str) -> Result<f64, MusicExportError> {
+    let probe = run(
+        "ffprobe",
+        path,
+        &[
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+            path,
+        ],
+    )?;
+    let output = String::from_utf8_lossy(&probe.stdout);
+    output
+        .trim()
+        .parse()
+        .map_err(|_| MusicExportError::FfprobeParse {
+            file: path.into(),
+            output: output.into_owned(),
+        })
+}
 
+/// EBU R128 loudness of an audio file, from `ffmpeg`'s `loudnorm` analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness in LUFS; `-inf` for silence.
+    pub integrated_lufs: f64,
+    /// True peak in dBTP.
+    pub true_peak_dbtp: f64,
+    /// Loudness range in LU.
+    pub loudness_range_lu: f64,
+}
+
+pub fn measure_loudness(path: &str) -> Result<LoudnessMeasurement, MusicExportError> {
+    let analysis = run(
+        "ffmpeg",
+        path,
+        &[
+            "-hide_banner",
+            "-i",
+            path,
+            "-af",
+            "loudnorm=print_format=json",
+            "-f",
+            "null",
+            "-",
+        ],
+    )?;
+
+    parse_loudnorm(&String::from_utf8_lossy(&analysis.stderr))
+        .ok_or_else(|| MusicExportError::LoudnessParse { file: path.into() })
+}
+
+/// loudnorm prints a JSON object of quoted numbers at the end of stderr.
+fn parse_loudnorm(stderr: &str) -> Option<LoudnessMeasurement> {
+    let field = |key: &str| -> Option<f64> {
+        let after_key = stderr.rsplit_once(&format!("\"{key}\""))?.1;
+        let value = after_key.trim_start().strip_prefix(':')?.trim_start();
+        value
+            .strip_prefix('"')?
+            .split('"')
+            .next()?
+            .trim()
+            .parse()
+            .ok()
+    };
+    Some(LoudnessMeasurement {
+        integrated_lufs: field("input_i")?,
+        true_peak_dbtp: field("input_tp")?,
+        loudness_range_lu: field("input_lra")?,
+    })
+}
+
+/// Encodes `input_path` as OGG Vorbis, first amplifying it by `gain_db` if
+/// given. Loop points are written as the `LOOPSTART` / `LOOPLENGTH` comments
+/// (in samples) that looping players read.
+pub fn wav_to_ogg(
+    input_path: &str,
+    output_path: &str,
+    loop_points: Option<&LoopPoints>,
+    gain_db: Option<f64>,
+) -> Result<(), MusicExportError> {
+    let mut args = vec!["-y".to_string(), "-i".to_string(), input_path.to_string()];
+    if let Some(gain_db) = gain_db {
+        args.push("-af".to_string());
+        args.push(format!("volume={gain_db:.2}dB"));
+    }
+    args.extend([
+        "-c:a".to_string(),
+        "libvorbis".to_string(),
+        "-q:a".to_string(),
+        "5".to_string(),
+    ]);
+    if let Some(points) = loop_points {
+        args.push("-metadata".to_string());
+        args.push(format!("LOOPSTART={}", points.start_frame));
+        args.push("-metadata".to_string());
+        args.push(format!("LOOPLENGTH={}", points.length_frames()));
+    }
+    args.push(output_path.to_string());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    run("ffmpeg", input_path, &args)?;
     Ok(())
 }
 
@@ -77,32 +280,15 @@ pub fn export_production_wav_file(
     loops: u32,
     fade_duration_secs: f64,
     lead_in_silence_secs: f64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let probe = std::process::Command::new("ffprobe")
-        .args([
-            "-v",
-            "error",
-            "-show_entries",
-            "format=duration",
-            "-of",
-            "csv=p=0",
-            seamlessly_looping_wav_path,
-        ])
-        .output()?;
-
-    if !probe.status.success() {
-        let stderr = String::from_utf8_lossy(&probe.stderr);
-        return Err(format!("ffprobe failed: {stderr}").into());
-    }
-
-    let duration_str = String::from_utf8_lossy(&probe.stdout);
-    let input_duration: f64 = duration_str.trim().parse()?;
+) -> Result<(), MusicExportError> {
+    let input_duration = probe_duration(seamlessly_looping_wav_path)?;
 
     if fade_duration_secs > input_duration {
-        return Err(format!(
-            "Fade duration ({fade_duration_secs}s) is longer than a single loop ({input_duration}s)"
-        )
-        .into());
+        return Err(MusicExportError::FadeLongerThanLoop {
+            file: seamlessly_looping_wav_path.into(),
+            fade_duration_secs,
+            loop_duration_secs: input_duration,
+        });
     }
 
     let delay_ms = (lead_in_silence_secs * 1000.0) as u32;
@@ -126,8 +312,10 @@ pub fn export_production_wav_file(
 
     // Pass 1: apply effects, detect peak volume
     let detect_filter = format!("{effects_filter},volumedetect");
-    let detect = std::process::Command::new("ffmpeg")
-        .args([
+    let detect = run(
+        "ffmpeg",
+        seamlessly_looping_wav_path,
+        &[
             "-y",
             "-stream_loop",
             &stream_loops,
@@ -138,13 +326,8 @@ pub fn export_production_wav_file(
             "-f",
             "null",
             "-",
-        ])
-        .output()?;
-
-    if !detect.status.success() {
-        let stderr = String::from_utf8_lossy(&detect.stderr);
-        return Err(format!("ffmpeg volumedetect failed: {stderr}").into());
-    }
+        ],
+    )?;
 
     let detect_stderr = String::from_utf8_lossy(&detect.stderr);
     let max_volume = detect_stderr
@@ -159,15 +342,19 @@ pub fn export_production_wav_file(
                 .parse::<f64>()
                 .ok()
         })
-        .ok_or("failed to parse max_volume from ffmpeg output")?;
+        .ok_or_else(|| MusicExportError::VolumeDetectParse {
+            file: seamlessly_looping_wav_path.into(),
+        })?;
 
     // Uniform gain to bring peak to -1 dBFS (1dB headroom)
     let gain = 2.0 - max_volume;
 
     // Pass 2: apply effects + uniform gain
     let final_filter = format!("{effects_filter},volume={gain}dB");
-    let output = std::process::Command::new("ffmpeg")
-        .args([
+    run(
+        "ffmpeg",
+        seamlessly_looping_wav_path,
+        &[
             "-y",
             "-stream_loop",
             &stream_loops,
@@ -176,13 +363,8 @@ pub fn export_production_wav_file(
             "-af",
             &final_filter,
             output_wav_path,
-        ])
-        .output()?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("ffmpeg failed: {stderr}").into());
-    }
+        ],
+    )?;
 
     Ok(())
 }
@@ -192,28 +374,13 @@ pub fn export_production_mp4(
     production_wav_path: &str,
     output_mp4_path: &str,
     video_image_path: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let probe = std::process::Command::new("ffprobe")
-        .args([
-            "-v",
-            "error",
-            "-show_entries",
-            "format=duration",
-            "-of",
-            "csv=p=0",
-            production_wav_path,
-        ])
-        .output()?;
-
-    if !probe.status.success() {
-        let stderr = String::from_utf8_lossy(&probe.stderr);
-        return Err(format!("ffprobe failed: {stderr}").into());
-    }
+) -> Result<(), MusicExportError> {
+    let duration = probe_duration(production_wav_path)?.to_string();
 
-    let duration = String::from_utf8_lossy(&probe.stdout).trim().to_string();
-
-    let output = std::process::Command::new("ffmpeg")
-        .args([
+    run(
+        "ffmpeg",
+        production_wav_path,
+        &[
             "-y",
             "-framerate",
             "2",
@@ -241,13 +408,247 @@ pub fn export_production_mp4(
             "-t",
             &duration,
             output_mp4_path,
-        ])
-        .output()?;
+        ],
+    )?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("ffmpeg failed: {stderr}").into());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const RATE: u32 = 10;
+
+    fn spec(channels: u16, bits_per_sample: u16, format: hound::SampleFormat) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate: RATE,
+            bits_per_sample,
+            sample_format: format,
+        }
     }
 
-    Ok(())
+    /// A path in the temp folder, unique to this test run.
+    fn temp_wav(name: &str) -> String {
+        let name = format!("ost_export {} {name}.wav", std::process::id());
+        std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Write `samples` as a WAV named `name`, trim it and read back what was
+    /// kept.
+    fn trim<S: hound::Sample + Copy>(
+        name: &str,
+        spec: hound::WavSpec,
+        samples: &[S],
+        mode: TrimMode,
+    ) -> Result<(TrimWavResult, hound::WavSpec, Vec<S>), MusicExportError> {
+        let input = temp_wav(&format!("{name} in"));
+        let output = temp_wav(&format!("{name} out"));
+        let mut writer = hound::WavWriter::create(&input, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        // A partial last frame is written, but reported as unfinished.
+        let _ = writer.finalize();
+
+        let trimmed = trim_wav(&input, &output, &mode).map(|result| {
+            let reader = hound::WavReader::open(&output).unwrap();
+            let spec = reader.spec();
+            let samples = reader.into_samples().map(Result::unwrap).collect();
+            (result, spec, samples)
+        });
+        let _ = fs::remove_file(&input);
+        let _ = fs::remove_file(&output);
+        trimmed
+    }
+
+    fn exact(start_secs: f64, end_secs: f64) -> TrimMode {
+        TrimMode::Exact {
+            start_secs,
+            end_secs,
+        }
+    }
+
+    #[test]
+    fn exact_trim_cuts_whole_frames_of_16_bit_audio() {
+        let spec = spec(2, 16, hound::SampleFormat::Int);
+        let samples: Vec<i16> = (1..=6).flat_map(|i| [i, -i]).collect();
+        let (result, written, kept) = trim("i16", spec, &samples, exact(0.2, 0.1)).unwrap();
+        assert_eq!(written, spec);
+        assert_eq!(kept, [3, -3, 4, -4, 5, -5]);
+        assert_eq!(result.trimmed_start_frames, 2);
+        assert_eq!(result.kept_frames, 3);
+        assert_eq!(result.channels, 2);
+        assert!((result.trimmed_start_secs - 0.2).abs() < 1e-9);
+        assert!((result.trimmed_end_secs - 0.1).abs() < 1e-9);
+        assert!((result.new_duration_secs - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exact_trim_keeps_24_bit_samples() {
+        let spec = spec(2, 24, hound::SampleFormat::Int);
+        let samples: Vec<i32> = (1..=5).flat_map(|i| [i * 1_000_000, -i]).collect();
+        let (result, written, kept) = trim("i24", spec, &samples, exact(0.1, 0.2)).unwrap();
+        assert_eq!(written, spec);
+        assert_eq!(kept, [2_000_000, -2, 3_000_000, -3]);
+        assert_eq!(result.kept_frames, 2);
+    }
+
+    #[test]
+    fn exact_trim_keeps_float_samples() {
+        let spec = spec(1, 32, hound::SampleFormat::Float);
+        let samples = [0.1f32, -0.2, 0.3, -0.4, 0.5];
+        let (result, written, kept) = trim("f32", spec, &samples, exact(0.1, 0.1)).unwrap();
+        assert_eq!(written, spec);
+        assert_eq!(kept, [-0.2, 0.3, -0.4]);
+        assert_eq!(result.trimmed_start_frames, 1);
+    }
+
+    #[test]
+    fn wav_ending_in_a_partial_frame_is_refused() {
+        let spec = spec(2, 16, hound::SampleFormat::Int);
+        let samples: [i16; 7] = [1, -1, 2, -2, 3, -3, 4];
+        let error = trim("partial", spec, &samples, exact(0.1, 0.))
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error, MusicExportError::UnsupportedWavFormat { .. }),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn exact_trim_longer_than_the_song_fails() {
+        let spec = spec(1, 16, hound::SampleFormat::Int);
+        let error = trim("short", spec, &[1i16, 2, 3], exact(0.2, 0.1))
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error, MusicExportError::TooShortToTrim { .. }),
+            "{error}"
+        );
+    }
+
+    fn silence(threshold_dbfs: f64, margin_secs: f64) -> TrimMode {
+        TrimMode::Silence {
+            threshold_dbfs,
+            margin_secs,
+        }
+    }
+
+    #[test]
+    fn silence_trim_keeps_the_loud_frames_and_the_margin() {
+        // -20 dBFS is a tenth of full scale: 3277 at 16 bits.
+        let spec = spec(1, 16, hound::SampleFormat::Int);
+        let samples: [i16; 10] = [0, 0, 100, 0, 10_000, 0, -20_000, 3_000, 0, 0];
+
+        let (result, _, kept) = trim("silence", spec, &samples, silence(-20., 0.)).unwrap();
+        assert_eq!(kept, [10_000, 0, -20_000]);
+        assert_eq!(result.trimmed_start_frames, 4);
+        assert!((result.trimmed_end_secs - 0.3).abs() < 1e-9);
+
+        let (result, _, kept) = trim("margin", spec, &samples, silence(-20., 0.1)).unwrap();
+        assert_eq!(kept, [0, 10_000, 0, -20_000, 3_000]);
+        assert_eq!(result.trimmed_start_frames, 3);
+        assert_eq!(result.kept_frames, 5);
+    }
+
+    #[test]
+    fn silence_margin_stops_at_the_ends_of_the_song() {
+        let spec = spec(1, 16, hound::SampleFormat::Int);
+        let samples: [i16; 4] = [0, 10_000, 0, 0];
+        let (result, _, kept) = trim("long margin", spec, &samples, silence(-20., 1.)).unwrap();
+        assert_eq!(kept, samples);
+        assert_eq!(result.trimmed_start_frames, 0);
+        assert_eq!(result.trimmed_end_secs, 0.);
+    }
+
+    #[test]
+    fn silence_is_a_frame_quiet_on_every_channel() {
+        let spec = spec(2, 32, hound::SampleFormat::Float);
+        let samples = [0f32, 0., 0., 0.5, 0.01, 0., 0., 0.];
+        let (_, _, kept) = trim("stereo", spec, &samples, silence(-20., 0.)).unwrap();
+        assert_eq!(kept, [0., 0.5]);
+    }
+
+    #[test]
+    fn silence_trim_of_a_silent_song_fails() {
+        let spec = spec(1, 24, hound::SampleFormat::Int);
+        let samples: [i32; 3] = [0, 1_000, -1_000];
+        let error = trim("silent", spec, &samples, silence(-20., 0.))
+            .err()
+            .unwrap();
+        assert!(
+            matches!(
+                error,
+                MusicExportError::EntirelySilent {
+                    threshold_dbfs: -20.,
+                    ..
+                }
+            ),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn loudnorm_analysis_is_read_from_the_end_of_stderr() {
+        let stderr = r#"Input #0, wav, from 'song.wav':
+  Duration: 00:00:10.00, bitrate: 1411 kb/s
+[Parsed_loudnorm_0 @ 0x5600] 
+{
+	"input_i" : "-23.45",
+	"input_tp" : "-4.20",
+	"input_lra" : "6.10",
+	"input_thresh" : "-33.70",
+	"output_i" : "-24.02",
+	"output_tp" : "-5.00",
+	"output_lra" : "5.80",
+	"output_thresh" : "-34.30",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.02"
+}
+"#;
+        assert_eq!(
+            parse_loudnorm(stderr),
+            Some(LoudnessMeasurement {
+                integrated_lufs: -23.45,
+                true_peak_dbtp: -4.2,
+                loudness_range_lu: 6.1,
+            })
+        );
+    }
+
+    #[test]
+    fn loudnorm_analysis_of_silence_is_negative_infinity() {
+        let stderr = r#"{
+	"input_i" : "-inf",
+	"input_tp" : "-inf",
+	"input_lra" : "0.00"
+}
+"#;
+        let measured = parse_loudnorm(stderr).unwrap();
+        assert_eq!(measured.integrated_lufs, f64::NEG_INFINITY);
+        assert_eq!(measured.true_peak_dbtp, f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn loudnorm_analysis_without_every_field_is_refused() {
+        assert_eq!(parse_loudnorm(""), None);
+        assert_eq!(
+            parse_loudnorm("{\n\t\"input_i\" : \"-23.45\",\n\t\"input_tp\" : \"-4.20\"\n}\n"),
+            None
+        );
+        assert_eq!(
+            parse_loudnorm(
+                "{ \"input_i\" : \"n/a\", \"input_tp\" : \"-1\", \"input_lra\" : \"1\" }"
+            ),
+            None
+        );
+    }
 }
@@ -0,0 +1,170 @@
+use std::{
+    fmt,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::MusicExportError;
+
+/// A step of exporting one song.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportStage {
+    /// Cutting the tracker's silence off the start and end.
+    Trim,
+    /// Game music: measuring loudness to normalize it.
+    MeasureLoudness,
+    /// Game music: converting to OGG.
+    EncodeOgg,
+    /// MP4: looping, fading and mastering the soundtrack version.
+    Master,
+    /// MP4: rendering the video over the still image.
+    RenderVideo,
+    /// The song is exported.
+    Done,
+}
+
+impl ExportStage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Trim => "trim",
+            Self::MeasureLoudness => "measure-loudness",
+            Self::EncodeOgg => "encode-ogg",
+            Self::Master => "master",
+            Self::RenderVideo => "render-video",
+            Self::Done => "done",
+        }
+    }
+}
+
+impl fmt::Display for ExportStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Passed to [ExportControl::on_progress] as each song enters a stage.
+#[derive(Debug, Clone, Copy)]
+pub struct ExportProgress<'a> {
+    /// The WAV file in the music folder.
+    pub file: &'a Path,
+    /// 0-based position of `file` among the songs being exported.
+    pub index: usize,
+    pub total: usize,
+    pub stage: ExportStage,
+}
+
+/// Cancels an export from another thread. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Called with each [ExportProgress] of an export.
+pub type ProgressCallback<'a> = Box<dyn FnMut(&ExportProgress) + 'a>;
+
+/// Progress reporting and cancellation for an export. The default reports
+/// nothing and can't be cancelled.
+#[derive(Default)]
+pub struct ExportControl<'a> {
+    pub on_progress: Option<ProgressCallback<'a>>,
+    /// Checked before every stage; a cancelled export removes the current
+    /// song's temp files and returns [MusicExportError::Cancelled].
+    pub cancel: Option<CancellationToken>,
+}
+
+impl ExportControl<'_> {
+    /// Start `progress.stage`, unless the export was cancelled.
+    pub(crate) fn enter(
+        &mut self,
+        progress: ExportProgress,
+        files_exported: usize,
+    ) -> Result<(), MusicExportError> {
+        if self.cancel.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(MusicExportError::Cancelled { files_exported });
+        }
+        self.report(progress);
+        Ok(())
+    }
+
+    pub(crate) fn report(&mut self, progress: ExportProgress) {
+        if let Some(on_progress) = &mut self.on_progress {
+            on_progress(&progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn progress(stage: ExportStage) -> ExportProgress<'static> {
+        ExportProgress {
+            file: Path::new("music/title.wav"),
+            index: 0,
+            total: 1,
+            stage,
+        }
+    }
+
+    #[test]
+    fn entering_a_stage_reports_it() {
+        let stages = RefCell::new(Vec::new());
+        let mut control = ExportControl {
+            on_progress: Some(Box::new(|p: &ExportProgress| {
+                stages.borrow_mut().push(p.stage)
+            })),
+            cancel: Some(CancellationToken::new()),
+        };
+        control.enter(progress(ExportStage::Trim), 0).unwrap();
+        control.enter(progress(ExportStage::EncodeOgg), 0).unwrap();
+        drop(control);
+        assert_eq!(
+            stages.into_inner(),
+            [ExportStage::Trim, ExportStage::EncodeOgg]
+        );
+    }
+
+    #[test]
+    fn cancelled_export_stops_before_the_next_stage() {
+        let reports = RefCell::new(0);
+        let token = CancellationToken::new();
+        let mut control = ExportControl {
+            on_progress: Some(Box::new(|_: &ExportProgress| *reports.borrow_mut() += 1)),
+            cancel: Some(token.clone()),
+        };
+        control.enter(progress(ExportStage::Trim), 0).unwrap();
+        token.cancel();
+        let error = control
+            .enter(progress(ExportStage::EncodeOgg), 3)
+            .unwrap_err();
+        assert!(
+            matches!(error, MusicExportError::Cancelled { files_exported: 3 }),
+            "{error}"
+        );
+        drop(control);
+        assert_eq!(reports.into_inner(), 1);
+    }
+
+    #[test]
+    fn default_control_is_never_cancelled() {
+        let mut control = ExportControl::default();
+        assert!(control.enter(progress(ExportStage::Done), 0).is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{fs, path::PathBuf};
 
 pub fn convert_to_pascal_case(input: &str) -> String {
     input
@@ -8,7 +8,7 @@ pub fn convert_to_pascal_case(input: &str) -> String {
 }
 
 pub trait PathStringUtil {
-    fn into_string(&self) -> String;
+    fn to_path_string(&self) -> String;
     /// Warning!: will panic if the Path does not have a file name for some reason
     fn unwrap_filename(&self) -> String;
 }
@@ -21,7 +21,19 @@ impl PathStringUtil for PathBuf {
             .to_string()
     }
 
-    fn into_string(&self) -> String {
+    fn to_path_string(&self) -> String {
         self.to_string_lossy().to_string()
     }
 }
+
+/// Temp files of the song being exported, removed when dropped so a failed
+/// or cancelled export doesn't leave them in the output folder.
+pub struct TempFiles(pub Vec<String>);
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -1,9 +1,16 @@
+use std::collections::HashSet;
 use std::fs;
 use std::io;
 use std::io::Write;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::gml::format::FormatOptions;
+use crate::gml::{self, LineIndex, Program, TokenKind};
+use crate::snippets::{Expansion, SnippetSet};
+use crate::undo_log::{self, RewriteKind};
+use crate::{code_generators, jsdoc, project_config, self_writes};
+
 pub fn process_gml_file_change(file: &Path) {
     if let Err(err) = process_gml_file_change_impl(file) {
         eprintln!(
@@ -15,35 +22,231 @@ pub fn process_gml_file_change(file: &Path) {
 }
 
 fn process_gml_file_change_impl(file: &Path) -> io::Result<()> {
-    let content = fs::read_to_string(file)?;
-    let mut changed = false;
-    let mut output_lines = Vec::new();
-
-    for line in content.lines() {
-        if let Some(replacement) = try_expand_line_command(line) {
-            output_lines.push(replacement);
-            changed = true;
-        } else {
-            output_lines.push(line.to_string());
+    let raw = fs::read_to_string(file)?;
+    let (style, content) = SourceStyle::detect(&raw);
+    let program = gml::parse(content);
+    let command_lines = command_comment_lines(content, &program);
+
+    let mut new_content = content.to_string();
+    let mut cursor = None;
+    if !command_lines.is_empty() {
+        let snippets = SnippetSet::load_for_file(file);
+        let raw_lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let lines: Vec<&str> = raw_lines.iter().map(|l| strip_line_ending(l)).collect();
+
+        let mut expansions: Vec<LineExpansion> = Vec::new();
+        for index in 0..lines.len() {
+            if !command_lines.contains(&(index + 1)) {
+                continue;
+            }
+            match expand_command_line(content, &program, &lines, index, file, &snippets) {
+                // A regenerated doc comment may already cover this line.
+                Some(Ok(expansion))
+                    if expansions
+                        .last()
+                        .is_none_or(|prev| prev.last < expansion.first) =>
+                {
+                    expansions.push(expansion);
+                }
+                Some(Ok(_)) | None => {}
+                Some(Err(e)) => eprintln!("{}:{}:1: error: {e}", file.display(), index + 1),
+            }
+        }
+
+        // Splice expansions over the lines they replace; every other line
+        // keeps its bytes, line ending included.
+        let mut output = String::with_capacity(content.len());
+        let mut output_line_count = 0;
+        let mut next = 0;
+        for LineExpansion {
+            expansion,
+            first,
+            last,
+        } in &expansions
+        {
+            for raw_line in &raw_lines[next..*first] {
+                output.push_str(raw_line);
+            }
+            output_line_count += first - next;
+            if let Some((row, column)) = expansion.cursor
+                && cursor.is_none()
+            {
+                cursor = Some((output_line_count + row + 1, column + 1));
+            }
+            if !expansion.lines.is_empty() {
+                output.push_str(&expansion.lines.join(style.newline()));
+                output.push_str(&raw_lines[*last][lines[*last].len()..]);
+            }
+            output_line_count += expansion.lines.len();
+            next = last + 1;
+        }
+        for raw_line in &raw_lines[next..] {
+            output.push_str(raw_line);
         }
+        new_content = output;
     }
+    let expanded = new_content != content;
 
-    if !changed {
+    // Expansions leave the cursor on a blank line the formatter would drop,
+    // so a save that expanded anything is formatted on the next save.
+    let mut format_refused = false;
+    if !expanded && let Some(options) = format_on_save_options(file) {
+        match gml::format::format(content, &options) {
+            Ok(formatted) => new_content = style.convert_newlines(&formatted),
+            Err(_) => format_refused = true,
+        }
+    }
+
+    if new_content == content {
+        // Errors are only worth a message on every save when they stopped
+        // the formatter; Igor reports them in the build anyway.
+        if format_refused {
+            report_syntax_errors(file, content, &program);
+        }
         return Ok(());
     }
 
-    let mut new_content = output_lines.join("\n");
-    if content.ends_with('\n') {
-        new_content.push('\n');
+    let kind = if expanded {
+        RewriteKind::Expand
+    } else {
+        RewriteKind::Format
+    };
+    rewrite_source(file, style.with_bom(&new_content).as_bytes(), kind)?;
+    if expanded {
+        println!("Expanded command comments in {}", file.display());
+    } else {
+        println!("Formatted {}", file.display());
+    }
+    if let Some((line, column)) = cursor {
+        println!("  Cursor: {}:{line}:{column}", file.display());
+    }
+    report_syntax_errors(file, &new_content, &gml::parse(&new_content));
+    Ok(())
+}
+
+/// How a source file is laid out on disk beyond its text: a UTF-8 byte order
+/// mark and the line ending it was saved with (GameMaker on Windows writes
+/// CRLF). Rewrites keep both so a small edit stays a small diff.
+#[derive(Clone, Copy)]
+pub struct SourceStyle {
+    bom: bool,
+    crlf: bool,
+}
+
+impl SourceStyle {
+    /// Split a file's contents into its style and the text after any BOM.
+    /// The first line ending decides the style; files without one are LF.
+    pub fn detect(raw: &str) -> (Self, &str) {
+        let (bom, text) = match raw.strip_prefix('\u{feff}') {
+            Some(text) => (true, text),
+            None => (false, raw),
+        };
+        let crlf = text.find('\n').is_some_and(|i| text[..i].ends_with('\r'));
+        (Self { bom, crlf }, text)
     }
 
-    let bytes = new_content.as_bytes();
-    replace_via_temp(file, bytes)?;
-    nudge_in_place_save(file, bytes)?;
-    println!("Expanded command comments in {}", file.display());
+    pub fn newline(self) -> &'static str {
+        if self.crlf { "\r\n" } else { "\n" }
+    }
+
+    /// `text` with bare `\n` line endings turned into the file's style.
+    pub fn convert_newlines(self, text: &str) -> String {
+        if !self.crlf {
+            return text.to_string();
+        }
+        let mut output = String::with_capacity(text.len() + text.len() / 32);
+        for line in text.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(body) if !body.ends_with('\r') => {
+                    output.push_str(body);
+                    output.push_str("\r\n");
+                }
+                _ => output.push_str(line),
+            }
+        }
+        output
+    }
+
+    /// `text` ready to write back, with the BOM restored if there was one.
+    pub fn with_bom(self, text: &str) -> String {
+        if self.bom {
+            format!("\u{feff}{text}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// A line from `split_inclusive('\n')` without its `\n` or `\r\n`.
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Formatter settings when the file's project has `fmt.formatOnSave` on.
+fn format_on_save_options(file: &Path) -> Option<FormatOptions> {
+    let yyp = project_config::find_project_for(file)?;
+    let config = project_config::load_for_project(&yyp).ok()?;
+    config
+        .fmt
+        .format_on_save
+        .then(|| config.fmt.format_options())
+}
+
+/// 1-based numbers of lines holding only a `//:` comment. Going through the
+/// lexer keeps look-alikes inside strings and block comments from expanding.
+pub fn command_comment_lines(content: &str, program: &Program) -> HashSet<usize> {
+    let index = LineIndex::new(content);
+    program
+        .comments
+        .iter()
+        .filter(|c| c.kind == TokenKind::LineComment && c.span.text(content).starts_with("//:"))
+        .filter_map(|c| {
+            let (line, column) = index.line_col(c.span.start);
+            let before = &content[c.span.start - (column - 1)..c.span.start];
+            before.trim().is_empty().then_some(line)
+        })
+        .collect()
+}
+
+/// Print syntax errors as `file:line:col` so they show up before Igor runs.
+fn report_syntax_errors(file: &Path, content: &str, program: &Program) {
+    if program.errors.is_empty() {
+        return;
+    }
+    let index = LineIndex::new(content);
+    for error in &program.errors {
+        let (line, column) = index.line_col(error.span.start);
+        eprintln!(
+            "{}:{line}:{column}: error: {}",
+            file.display(),
+            error.message
+        );
+    }
+}
+
+/// [write_source] for an automatic rewrite, logged so `gmhelper undo` can
+/// revert it.
+pub fn rewrite_source(file: &Path, contents: &[u8], kind: RewriteKind) -> io::Result<()> {
+    let before = fs::read(file)?;
+    write_source(file, contents)?;
+    if let Err(e) = undo_log::record(file, kind, &before, contents) {
+        eprintln!(
+            "Warning: could not log the rewrite of {}: {e}",
+            file.display()
+        );
+    }
     Ok(())
 }
 
+/// Rewrite a source file that may be open in the GameMaker IDE: atomic replace,
+/// then an in-place save so the IDE notices. Recorded as gmhelper's own write.
+pub fn write_source(file: &Path, contents: &[u8]) -> io::Result<()> {
+    self_writes::record(file, contents);
+    replace_via_temp(file, contents)?;
+    nudge_in_place_save(file, contents)
+}
+
 /// In-place write so an open script view in GameMaker may resync (like saving from another editor).
 fn nudge_in_place_save(path: &Path, contents: &[u8]) -> io::Result<()> {
     let mut f = fs::File::create(path)?;
@@ -53,18 +256,15 @@ fn nudge_in_place_save(path: &Path, contents: &[u8]) -> io::Result<()> {
 }
 
 /// Write to a sibling `.tmp` file, then rename over `path` (same directory → atomic on Windows).
-fn replace_via_temp(path: &Path, contents: &[u8]) -> io::Result<()> {
+pub fn replace_via_temp(path: &Path, contents: &[u8]) -> io::Result<()> {
     let dir = path
         .parent()
         .filter(|p| !p.as_os_str().is_empty())
         .unwrap_or_else(|| Path::new("."));
 
-    let name = path.file_name().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "path has no file name",
-        )
-    })?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
 
     let stamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -87,40 +287,85 @@ fn replace_via_temp(path: &Path, contents: &[u8]) -> io::Result<()> {
     }
 }
 
-fn try_expand_line_command(line: &str) -> Option<String> {
-    let trimmed = line.trim_start();
-    let indent_len = line.len() - trimmed.len();
-    let indent = &line[..indent_len];
+/// An expanded command and the 0-based, inclusive range of lines it
+/// replaces: the command line itself, or for `//: doc;` also the doc comment
+/// it regenerates.
+pub struct LineExpansion {
+    pub expansion: Expansion,
+    pub first: usize,
+    pub last: usize,
+}
 
-    // Command format: //: <command>;
-    if !trimmed.starts_with("//:") {
+/// Expand the `//:` command on line `index` of `content`, split into `lines`
+/// without their line endings.
+pub fn expand_command_line(
+    content: &str,
+    program: &Program,
+    lines: &[&str],
+    index: usize,
+    file: &Path,
+    snippets: &SnippetSet,
+) -> Option<Result<LineExpansion, String>> {
+    let line = lines[index];
+    if let Some(result) = try_expand_line_command(line, file, snippets) {
+        return Some(result.map(|expansion| LineExpansion {
+            expansion,
+            first: index,
+            last: index,
+        }));
+    }
+    // Unless a snippet claims the name, `doc` documents the function below.
+    if command_text(line)? != "doc" {
         return None;
     }
+    Some(
+        jsdoc::expand_doc_command(content, program, lines, index).map(
+            |(expansion, first, last)| LineExpansion {
+                expansion,
+                first,
+                last,
+            },
+        ),
+    )
+}
 
-    let command_with_end = trimmed.trim_start_matches("//:").trim_start();
+/// The command of a `//: <command>;` line.
+fn command_text(line: &str) -> Option<&str> {
+    let command_with_end = line.trim_start().strip_prefix("//:")?.trim_start();
     let command_end = command_with_end.find(';')?;
     let command = command_with_end[..command_end].trim();
-    if command.is_empty() {
-        return None;
-    }
-
-    expand_command(command).map(|expanded| format!("{indent}{expanded}"))
+    (!command.is_empty()).then_some(command)
 }
 
-fn expand_command(command: &str) -> Option<String> {
-    let mut parts = command.split_whitespace();
-    let name = parts.next()?;
+/// Expand a `//: <command>;` line into indented snippet lines.
+fn try_expand_line_command(
+    line: &str,
+    file: &Path,
+    snippets: &SnippetSet,
+) -> Option<Result<Expansion, String>> {
+    let command = command_text(line)?;
+    let indent = &line[..line.len() - line.trim_start().len()];
 
-    match name {
-        "for" => {
-            let variable = parts.next()?;
-            if parts.next().is_some() {
-                return None;
-            }
-            Some(format!(
-                "for(var i = 0;i < array_length({variable}); i++) \n{{ \n}}"
-            ))
+    // Project and user snippets may shadow the generators.
+    let expanded = snippets
+        .expand(command)
+        .or_else(|| code_generators::expand(command, file))?;
+    Some(expanded.map(|expansion| {
+        Expansion {
+            lines: expansion
+                .lines
+                .into_iter()
+                .map(|l| {
+                    if l.is_empty() {
+                        l
+                    } else {
+                        format!("{indent}{l}")
+                    }
+                })
+                .collect(),
+            cursor: expansion
+                .cursor
+                .map(|(row, column)| (row, column + indent.len())),
         }
-        _ => None,
-    }
+    }))
 }
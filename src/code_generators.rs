@@ -0,0 +1,140 @@
+//! `//:` commands whose output depends on the project rather than a fixed
+//! template, e.g. a `switch` over every member of an enum.
+
+use std::path::{Path, PathBuf};
+
+use crate::gml_project;
+use crate::project_config;
+use crate::snippets::{self, Expansion};
+
+/// Expand a generator command for `file`. `None` if `command` names no
+/// generator.
+pub fn expand(command: &str, file: &Path) -> Option<Result<Expansion, String>> {
+    let args = snippets::split_args(command);
+    let (name, args) = args.split_first()?;
+    match name.as_str() {
+        "switch" => Some(switch_on_enum(args, file)),
+        "ctor" => Some(constructor(args, file)),
+        _ => None,
+    }
+}
+
+/// `//: switch eState [expression];`
+fn switch_on_enum(args: &[String], file: &Path) -> Result<Expansion, String> {
+    let [enum_name, rest @ ..] = args else {
+        return Err("usage: //: switch <enum> [expression];".to_string());
+    };
+    let project_dir = project_dir_for(file)?;
+    let def = gml_project::find_enum(&project_dir, enum_name)
+        .ok_or_else(|| format!("enum '{enum_name}' not found in the project"))?;
+
+    // Without an expression, leave the cursor where it goes.
+    let (expression, cursor) = match rest {
+        [] => (String::new(), Some((0, "switch (".len()))),
+        _ => (rest.join(" "), None),
+    };
+
+    let mut lines = vec![format!("switch ({expression})"), "{".to_string()];
+    for member in &def.members {
+        lines.push(format!("\tcase {}.{member}:", def.name));
+        lines.push("\t\tbreak;".to_string());
+    }
+    lines.push("\tdefault:".to_string());
+    lines.push("\t\tbreak;".to_string());
+    lines.push("}".to_string());
+
+    Ok(Expansion { lines, cursor })
+}
+
+/// `//: ctor Name [: Parent] field[=default]...;`
+fn constructor(args: &[String], file: &Path) -> Result<Expansion, String> {
+    let [name, rest @ ..] = args else {
+        return Err("usage: //: ctor <Name> [: <Parent>] <field>[=default]...;".to_string());
+    };
+
+    let (parent, field_args) = match rest {
+        [colon, parent, fields @ ..] if colon == ":" => (Some(parent.as_str()), fields),
+        [parent, fields @ ..] if parent.len() > 1 && parent.starts_with(':') => {
+            (Some(&parent[1..]), fields)
+        }
+        _ => (None, rest),
+    };
+    let fields: Vec<(&str, Option<&str>)> = field_args
+        .iter()
+        .map(|f| match f.split_once('=') {
+            Some((n, d)) => (n, Some(d)),
+            None => (f.as_str(), None),
+        })
+        .collect();
+
+    let parent_fields = match parent {
+        Some(parent) => {
+            let project_dir = project_dir_for(file)?;
+            let def = gml_project::find_constructor(&project_dir, parent)
+                .ok_or_else(|| format!("constructor '{parent}' not found in the project"))?;
+            gml_project::all_fields(&project_dir, &def)
+        }
+        None => Vec::new(),
+    };
+
+    let param = |field: &str, default: Option<&str>| match default {
+        Some(d) => format!("_{field} = {d}"),
+        None => format!("_{field}"),
+    };
+    let params: Vec<String> = parent_fields
+        .iter()
+        .map(|f| param(f, None))
+        .chain(fields.iter().map(|(f, d)| param(f, *d)))
+        .collect();
+
+    let mut header = format!("function {name}({})", params.join(", "));
+    if let Some(parent) = parent {
+        let forwarded: Vec<String> = parent_fields.iter().map(|f| format!("_{f}")).collect();
+        header.push_str(&format!(" : {parent}({})", forwarded.join(", ")));
+    }
+    header.push_str(" constructor");
+
+    let mut lines = vec![header, "{".to_string()];
+    for (field, _) in &fields {
+        lines.push(format!("\t{field} = _{field};"));
+    }
+    if !fields.is_empty() {
+        lines.push(String::new());
+    }
+
+    let described: Vec<String> = parent_fields
+        .iter()
+        .map(String::as_str)
+        .chain(fields.iter().map(|(f, _)| *f))
+        .enumerate()
+        .map(|(i, f)| {
+            let separator = if i == 0 {
+                format!("{name}(")
+            } else {
+                ", ".to_string()
+            };
+            format!("\"{separator}{f}: \" + string({f})")
+        })
+        .collect();
+    let summary = if described.is_empty() {
+        format!("\"{name}()\"")
+    } else {
+        format!("{} + \")\"", described.join(" + "))
+    };
+    lines.push("\tstatic toString = function()".to_string());
+    lines.push("\t{".to_string());
+    lines.push(format!("\t\treturn {summary};"));
+    lines.push("\t};".to_string());
+    lines.push("}".to_string());
+
+    Ok(Expansion {
+        lines,
+        cursor: None,
+    })
+}
+
+fn project_dir_for(file: &Path) -> Result<PathBuf, String> {
+    project_config::find_project_for(file)
+        .and_then(|yyp| yyp.parent().map(Path::to_path_buf))
+        .ok_or_else(|| format!("{} is not inside a GameMaker project", file.display()))
+}
@@ -0,0 +1,48 @@
+//! JSON files in the data dir that several gmhelper processes may update at
+//! once, e.g. a running `dev` recording its writes while `undo` runs in
+//! another terminal.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// The contents of `path`, or the default if it is missing or unreadable.
+/// Writes go through a rename, so this never sees a half-written file.
+pub fn read<T: Default + DeserializeOwned>(path: &Path) -> T {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+/// Read, change and write back `path` while holding an exclusive lock on
+/// `<path>.lock`, so concurrent updates from other processes aren't lost.
+pub fn update<T, R>(path: &Path, change: impl FnOnce(&mut T) -> R) -> io::Result<R>
+where
+    T: Default + Serialize + DeserializeOwned,
+{
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let lock = File::create(with_suffix(path, "lock"))?;
+    lock.lock()?;
+
+    let mut value = read(path);
+    let result = change(&mut value);
+    let json = serde_json::to_string_pretty(&value)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let temp = with_suffix(path, &format!("{}.tmp", std::process::id()));
+    fs::write(&temp, json)?;
+    fs::rename(&temp, path)?;
+    Ok(result)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,115 @@
+use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use crate::aseprite_exporter::{ensure_script_available, export_tags};
+use crate::hot_reloader::{self, ReloadOverrides, Reloader};
+use crate::project_config;
+
+/// `gmhelper dev`: one watcher whose events are dispatched to the sprite
+/// exporter (`.aseprite`), code expansion (`.gml`) and the build supervisor,
+/// so a single terminal shows all of them.
+pub fn run_dev(yyp_path: PathBuf, sprites_dir: Option<PathBuf>, overrides: ReloadOverrides) {
+    let mut reloader = Reloader::new(&yyp_path, overrides).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    });
+    let project_dir = reloader.project_dir().to_path_buf();
+
+    let sprites_dir =
+        resolve_sprites_dir(&yyp_path, &project_dir, sprites_dir).unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        });
+
+    let script_path = ensure_script_available().unwrap_or_else(|e| {
+        eprintln!("Error: Failed to set up export script: {e}");
+        std::process::exit(1);
+    });
+
+    reloader.print_summary();
+    println!("Importing sprites from: {}", sprites_dir.display());
+    println!("Press Ctrl+C to stop...\n");
+
+    let (tx, rx) = mpsc::channel();
+
+    let mut watcher =
+        RecommendedWatcher::new(tx, Config::default()).expect("Failed to create file watcher");
+
+    watcher
+        .watch(&project_dir, RecursiveMode::Recursive)
+        .expect("Failed to watch project directory");
+    if !sprites_dir.starts_with(&project_dir) {
+        watcher
+            .watch(&sprites_dir, RecursiveMode::Recursive)
+            .expect("Failed to watch sprites directory");
+    }
+
+    loop {
+        match rx.recv_timeout(hot_reloader::POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                if hot_reloader::is_change_event(&event.kind) {
+                    for path in &event.paths {
+                        if path.extension().is_some_and(|ext| ext == "aseprite") {
+                            if path.exists() && path.starts_with(&sprites_dir) {
+                                import_sprite(
+                                    path,
+                                    &script_path,
+                                    &yyp_path,
+                                    &sprites_dir,
+                                    &mut reloader,
+                                );
+                            }
+                        } else if path.starts_with(&project_dir) {
+                            reloader.handle_change(path);
+                        }
+                    }
+                }
+            }
+            Ok(Err(e)) => eprintln!("Watch error: {e}"),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        reloader.tick();
+    }
+}
+
+/// Export an `.aseprite` file into the project and schedule a rebuild; the
+/// imported files are gmhelper's own writes, so the watcher won't do it.
+fn import_sprite(
+    path: &Path,
+    script_path: &Path,
+    yyp_path: &Path,
+    sprites_dir: &Path,
+    reloader: &mut Reloader,
+) {
+    println!("Processing: {}", path.display());
+    match export_tags(path, script_path, Some(yyp_path), sprites_dir) {
+        Ok(()) => reloader.mark_changed("sprites"),
+        Err(e) => eprintln!("Error exporting {}: {}", path.display(), e),
+    }
+}
+
+/// CLI flag first, then `dev.spritesDir` (relative to the project), then the
+/// project folder.
+fn resolve_sprites_dir(
+    yyp_path: &Path,
+    project_dir: &Path,
+    cli_dir: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let dir = match cli_dir {
+        Some(dir) => dir,
+        None => match project_config::load_for_project(yyp_path)?.dev.sprites_dir {
+            Some(dir) => project_dir.join(dir),
+            None => project_dir.to_path_buf(),
+        },
+    };
+    if !dir.is_dir() {
+        return Err(format!(
+            "Sprites directory '{}' does not exist",
+            dir.display()
+        ));
+    }
+    std::path::absolute(&dir).map_err(|e| format!("Failed to resolve {}: {e}", dir.display()))
+}
@@ -0,0 +1,424 @@
+//! Syntax tree produced by [super::parse]. Operators and literals keep their
+//! source text, so tools can tell `and` from `&&` or report a literal exactly
+//! as written.
+
+use super::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Expr(Expr),
+    /// `var`, `static` or `globalvar` with one or more names.
+    Declare {
+        kind: DeclareKind,
+        vars: Vec<VarDeclarator>,
+    },
+    Block(Vec<Stmt>),
+    If {
+        condition: Expr,
+        then_branch: Box<Stmt>,
+        else_branch: Option<Box<Stmt>>,
+    },
+    While {
+        condition: Expr,
+        body: Box<Stmt>,
+    },
+    DoUntil {
+        body: Box<Stmt>,
+        condition: Expr,
+    },
+    For {
+        init: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        step: Option<Box<Stmt>>,
+        body: Box<Stmt>,
+    },
+    Repeat {
+        count: Expr,
+        body: Box<Stmt>,
+    },
+    With {
+        target: Expr,
+        body: Box<Stmt>,
+    },
+    Switch {
+        subject: Expr,
+        cases: Vec<SwitchCase>,
+    },
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    Exit,
+    Throw(Expr),
+    Delete(Expr),
+    Try {
+        body: Box<Stmt>,
+        catch: Option<CatchClause>,
+        finally: Option<Box<Stmt>>,
+    },
+    Function(Function),
+    Enum(EnumDecl),
+    Macro(MacroDecl),
+    /// `#region` with its (possibly empty) label.
+    Region(String),
+    EndRegion,
+    /// Any other `#` directive, kept only for its span.
+    Directive,
+    Empty,
+    /// Tokens the parser skipped while recovering from a syntax error.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclareKind {
+    Var,
+    Static,
+    GlobalVar,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDeclarator {
+    pub name: Ident,
+    pub init: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCase {
+    /// `None` for `default:`.
+    pub value: Option<Expr>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatchClause {
+    pub binding: Option<Ident>,
+    pub body: Box<Stmt>,
+}
+
+/// A function declaration or expression, including constructors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: Option<Ident>,
+    pub params: Vec<Param>,
+    /// `: Parent(args)` on a constructor.
+    pub parent: Option<ParentCall>,
+    pub is_constructor: bool,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: Ident,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParentCall {
+    pub name: Ident,
+    pub args: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDecl {
+    pub name: Ident,
+    pub members: Vec<EnumMember>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumMember {
+    pub name: Ident,
+    pub value: Option<Expr>,
+}
+
+/// `#macro [Config:]NAME value`; the value is raw text, as GML substitutes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacroDecl {
+    pub name: Ident,
+    pub config: Option<String>,
+    pub value: String,
+    pub value_span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    /// Numeric literal as written (`1.5`, `$FF`, `#00FF00`, ...).
+    Number(String),
+    /// String literal as written, quotes included.
+    String(String),
+    Template(Vec<TemplatePart>),
+    Ident(String),
+    Array(Vec<Expr>),
+    Struct(Vec<StructField>),
+    /// Prefix operator: `!`, `not`, `-`, `+`, `~`, `++`, `--`.
+    Unary {
+        op: String,
+        operand: Box<Expr>,
+    },
+    /// `x++` / `x--`.
+    Postfix {
+        op: String,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: String,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// Only at statement level; `=` inside an expression is a comparison.
+    Assign {
+        op: String,
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    Ternary {
+        condition: Box<Expr>,
+        then_value: Box<Expr>,
+        else_value: Box<Expr>,
+    },
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Member {
+        object: Box<Expr>,
+        field: Ident,
+    },
+    /// `a[i]`, `a[i, j]` and accessors such as `map[? key]`.
+    Index {
+        object: Box<Expr>,
+        accessor: String,
+        indices: Vec<Expr>,
+    },
+    New {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    Function(Box<Function>),
+    /// Something that should have been an expression but wasn't.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    Text(String),
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructField {
+    pub name: Ident,
+    /// `None` for the `{ x }` shorthand.
+    pub value: Option<Expr>,
+}
+
+/// Depth-first traversal. Override a method to look at a node, and call the
+/// matching `walk_*` function from it to keep descending.
+pub trait Visitor {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+}
+
+pub fn walk_stmts<V: Visitor + ?Sized>(visitor: &mut V, stmts: &[Stmt]) {
+    for stmt in stmts {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &Stmt) {
+    match &stmt.kind {
+        StmtKind::Expr(expr) | StmtKind::Throw(expr) | StmtKind::Delete(expr) => {
+            visitor.visit_expr(expr)
+        }
+        StmtKind::Declare { vars, .. } => {
+            for var in vars {
+                if let Some(init) = &var.init {
+                    visitor.visit_expr(init);
+                }
+            }
+        }
+        StmtKind::Block(stmts) => walk_stmts(visitor, stmts),
+        StmtKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(then_branch);
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(else_branch);
+            }
+        }
+        StmtKind::While { condition, body } | StmtKind::DoUntil { body, condition } => {
+            visitor.visit_expr(condition);
+            visitor.visit_stmt(body);
+        }
+        StmtKind::For {
+            init,
+            condition,
+            step,
+            body,
+        } => {
+            if let Some(init) = init {
+                visitor.visit_stmt(init);
+            }
+            if let Some(condition) = condition {
+                visitor.visit_expr(condition);
+            }
+            if let Some(step) = step {
+                visitor.visit_stmt(step);
+            }
+            visitor.visit_stmt(body);
+        }
+        StmtKind::Repeat { count: expr, body } | StmtKind::With { target: expr, body } => {
+            visitor.visit_expr(expr);
+            visitor.visit_stmt(body);
+        }
+        StmtKind::Switch { subject, cases } => {
+            visitor.visit_expr(subject);
+            for case in cases {
+                if let Some(value) = &case.value {
+                    visitor.visit_expr(value);
+                }
+                walk_stmts(visitor, &case.body);
+            }
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            visitor.visit_stmt(body);
+            if let Some(catch) = catch {
+                visitor.visit_stmt(&catch.body);
+            }
+            if let Some(finally) = finally {
+                visitor.visit_stmt(finally);
+            }
+        }
+        StmtKind::Function(function) => visitor.visit_function(function),
+        StmtKind::Enum(decl) => {
+            for member in &decl.members {
+                if let Some(value) = &member.value {
+                    visitor.visit_expr(value);
+                }
+            }
+        }
+        StmtKind::Macro(_)
+        | StmtKind::Region(_)
+        | StmtKind::EndRegion
+        | StmtKind::Directive
+        | StmtKind::Break
+        | StmtKind::Continue
+        | StmtKind::Exit
+        | StmtKind::Empty
+        | StmtKind::Error => {}
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    for param in &function.params {
+        if let Some(default) = &param.default {
+            visitor.visit_expr(default);
+        }
+    }
+    if let Some(parent) = &function.parent {
+        for arg in &parent.args {
+            visitor.visit_expr(arg);
+        }
+    }
+    walk_stmts(visitor, &function.body);
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Template(parts) => {
+            for part in parts {
+                if let TemplatePart::Expr(expr) = part {
+                    visitor.visit_expr(expr);
+                }
+            }
+        }
+        ExprKind::Array(items) => {
+            for item in items {
+                visitor.visit_expr(item);
+            }
+        }
+        ExprKind::Struct(fields) => {
+            for field in fields {
+                if let Some(value) = &field.value {
+                    visitor.visit_expr(value);
+                }
+            }
+        }
+        ExprKind::Unary { operand, .. } | ExprKind::Postfix { operand, .. } => {
+            visitor.visit_expr(operand)
+        }
+        ExprKind::Binary { lhs, rhs, .. } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        ExprKind::Assign { target, value, .. } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+        ExprKind::Ternary {
+            condition,
+            then_value,
+            else_value,
+        } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_value);
+            visitor.visit_expr(else_value);
+        }
+        ExprKind::Call { callee, args } | ExprKind::New { callee, args } => {
+            visitor.visit_expr(callee);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        ExprKind::Member { object, .. } => visitor.visit_expr(object),
+        ExprKind::Index {
+            object, indices, ..
+        } => {
+            visitor.visit_expr(object);
+            for index in indices {
+                visitor.visit_expr(index);
+            }
+        }
+        ExprKind::Function(function) => visitor.visit_function(function),
+        ExprKind::Number(_) | ExprKind::String(_) | ExprKind::Ident(_) | ExprKind::Error => {}
+    }
+}
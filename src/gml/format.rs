@@ -0,0 +1,634 @@
+//! Whitespace-only GML formatter. It works on tokens rather than the AST, so
+//! comments stay where they are and literals are never rewritten; the output
+//! is checked to contain exactly the input's tokens.
+
+use super::{LineIndex, Token, TokenKind, is_keyword, tokenize};
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// One level of indentation, e.g. `"\t"` or four spaces.
+    pub indent: String,
+    /// Opening braces of blocks on their own line; otherwise at the end of
+    /// the header line (`if (x) {`).
+    pub allman: bool,
+    /// Longest run of blank lines kept.
+    pub max_blank_lines: usize,
+}
+
+/// Keywords a block `{` can follow directly.
+const BLOCK_KEYWORDS: &[&str] = &["else", "do", "try", "finally", "constructor"];
+
+/// Keywords that continue the statement of a `}` on the previous line.
+const CONTINUATION_KEYWORDS: &[&str] = &["else", "catch", "finally", "until"];
+
+/// Statement headers whose body may be a single unbraced statement.
+const HEADER_KEYWORDS: &[&str] = &["if", "else", "while", "for", "with", "repeat", "do"];
+
+const ACCESSORS: &[&str] = &["[", "[@", "[|", "[?", "[#", "[$"];
+
+/// Format `source`. Fails on code with syntax errors rather than guess.
+pub fn format(source: &str, options: &FormatOptions) -> Result<String, String> {
+    let program = super::parse(source);
+    if let Some(error) = program.errors.first() {
+        let (line, column) = LineIndex::new(source).line_col(error.span.start);
+        return Err(format!(
+            "syntax error at {line}:{column}: {}",
+            error.message
+        ));
+    }
+
+    let tokens = tokenize(source).tokens;
+    let roles = operator_roles(source, &tokens);
+    let mut lines = source_lines(source, &tokens);
+    if options.allman {
+        lines = split_braces(source, &tokens, lines);
+    } else {
+        lines = join_braces(source, &tokens, lines);
+    }
+
+    let output = render(source, &tokens, &roles, &lines, options);
+    if token_texts(source, &tokens) != token_texts(&output, &tokenize(&output).tokens) {
+        return Err("formatting would change the code; left as is".into());
+    }
+    Ok(output)
+}
+
+fn token_texts<'a>(source: &'a str, tokens: &[Token]) -> Vec<&'a str> {
+    tokens.iter().map(|t| t.span.text(source)).collect()
+}
+
+/// An output line: token indices plus the blank lines kept before it.
+#[derive(Debug, Clone)]
+struct Line {
+    tokens: Vec<usize>,
+    blank_before: usize,
+}
+
+fn source_lines(source: &str, tokens: &[Token]) -> Vec<Line> {
+    let index = LineIndex::new(source);
+    let mut lines: Vec<Line> = Vec::new();
+    let mut last_line = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let (start_line, _) = index.line_col(token.span.start);
+        let (end_line, _) = index.line_col(token.span.end.saturating_sub(1).max(token.span.start));
+        match lines.last_mut() {
+            Some(line) if start_line == last_line => line.tokens.push(i),
+            _ => lines.push(Line {
+                tokens: vec![i],
+                blank_before: if lines.is_empty() {
+                    0
+                } else {
+                    start_line - last_line - 1
+                },
+            }),
+        }
+        last_line = end_line;
+    }
+    lines
+}
+
+fn text<'a>(source: &'a str, tokens: &[Token], i: usize) -> &'a str {
+    tokens[i].span.text(source)
+}
+
+fn is_punct(source: &str, tokens: &[Token], i: usize, punct: &str) -> bool {
+    tokens[i].kind == TokenKind::Punct && text(source, tokens, i) == punct
+}
+
+/// Index of the nearest non-comment token before `i`.
+fn previous_code(tokens: &[Token], i: usize) -> Option<usize> {
+    (0..i).rev().find(|&j| !tokens[j].is_comment())
+}
+
+/// Whether the `{` at `i` opens a statement block rather than a struct
+/// literal.
+fn opens_block(source: &str, tokens: &[Token], i: usize) -> bool {
+    let Some(prev) = previous_code(tokens, i) else {
+        return true;
+    };
+    let prev_text = text(source, tokens, prev);
+    match tokens[prev].kind {
+        TokenKind::Punct => matches!(prev_text, ")" | ";" | "{" | "}"),
+        TokenKind::Ident if BLOCK_KEYWORDS.contains(&prev_text) => true,
+        // `enum Name {`
+        TokenKind::Ident => {
+            previous_code(tokens, prev).is_some_and(|p| text(source, tokens, p) == "enum")
+        }
+        TokenKind::Macro | TokenKind::Region | TokenKind::EndRegion | TokenKind::Directive => true,
+        _ => false,
+    }
+}
+
+/// Whether the token at `i` opens a bracket of any kind.
+fn is_open_bracket(source: &str, tokens: &[Token], i: usize) -> bool {
+    tokens[i].kind == TokenKind::Punct
+        && matches!(
+            text(source, tokens, i),
+            "{" | "(" | "[" | "[@" | "[|" | "[?" | "[#" | "[$"
+        )
+}
+
+/// Index of the bracket closing the one at `open`.
+fn matching_close(source: &str, tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.kind != TokenKind::Punct {
+            continue;
+        }
+        match text(source, tokens, i) {
+            "{" | "(" | "[" | "[@" | "[|" | "[?" | "[#" | "[$" => depth += 1,
+            "}" | ")" | "]" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Allman: move a block's `{` off its header line, and `else`-like keywords
+/// off the line of the preceding `}`.
+fn split_braces(source: &str, tokens: &[Token], lines: Vec<Line>) -> Vec<Line> {
+    let mut output = Vec::new();
+    for mut line in lines {
+        // `} else ...` -> `}` / `else ...`
+        if line.tokens.len() > 1
+            && is_punct(source, tokens, line.tokens[0], "}")
+            && CONTINUATION_KEYWORDS.contains(&text(source, tokens, line.tokens[1]))
+        {
+            output.push(Line {
+                tokens: vec![line.tokens[0]],
+                blank_before: line.blank_before,
+            });
+            line = Line {
+                tokens: line.tokens[1..].to_vec(),
+                blank_before: 0,
+            };
+        }
+
+        // `header {` -> `header` / `{`, unless the block closes on this line,
+        // or the header leaves a bracket open: a function expression passed
+        // as an argument or struct member keeps its `{`, which would
+        // otherwise sit a level deeper than the `})` closing it.
+        let last_code = line.tokens.iter().rposition(|&i| !tokens[i].is_comment());
+        let split_at = last_code.filter(|&at| {
+            let i = line.tokens[at];
+            let closes_here = |open: usize| {
+                matching_close(source, tokens, open)
+                    .is_some_and(|close| line.tokens.contains(&close))
+            };
+            at > 0
+                && is_punct(source, tokens, i, "{")
+                && opens_block(source, tokens, i)
+                && !closes_here(i)
+                && line.tokens[..at]
+                    .iter()
+                    .all(|&open| !is_open_bracket(source, tokens, open) || closes_here(open))
+        });
+        match split_at {
+            Some(at) => {
+                output.push(Line {
+                    tokens: line.tokens[..at].to_vec(),
+                    blank_before: line.blank_before,
+                });
+                output.push(Line {
+                    tokens: line.tokens[at..].to_vec(),
+                    blank_before: 0,
+                });
+            }
+            None => output.push(line),
+        }
+    }
+    output
+}
+
+/// K&R: pull a lone block `{` up onto its header line, and `else`-like
+/// keywords up after the preceding `}`.
+fn join_braces(source: &str, tokens: &[Token], lines: Vec<Line>) -> Vec<Line> {
+    let mut output: Vec<Line> = Vec::new();
+    for line in lines {
+        let first = line.tokens[0];
+        let rest_are_comments = line.tokens[1..].iter().all(|&i| tokens[i].is_comment());
+        let joins = output.last().is_some_and(|previous| {
+            let last = *previous.tokens.last().unwrap_or(&first);
+            // A `{` can't follow a line comment.
+            if tokens[last].is_comment() {
+                return false;
+            }
+            let lone_brace = is_punct(source, tokens, first, "{")
+                && rest_are_comments
+                && opens_block(source, tokens, first)
+                && previous_code(tokens, first)
+                    .is_some_and(|p| !matches!(text(source, tokens, p), ";" | "{" | "}"));
+            let continuation = CONTINUATION_KEYWORDS.contains(&text(source, tokens, first))
+                && previous.tokens.len() == 1
+                && is_punct(source, tokens, last, "}");
+            lone_brace || continuation
+        });
+        match output.last_mut() {
+            Some(previous) if joins => previous.tokens.extend(line.tokens),
+            _ => output.push(line),
+        }
+    }
+    output
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// Not an operator: words, literals, brackets, separators.
+    Other,
+    Unary,
+    Postfix,
+    Binary,
+}
+
+/// Whether the token at `i` ends an operand, so a following `-` subtracts.
+fn ends_operand(source: &str, tokens: &[Token], roles: &[Role], i: usize) -> bool {
+    match tokens[i].kind {
+        TokenKind::Ident => !is_keyword(text(source, tokens, i)),
+        TokenKind::Number | TokenKind::String | TokenKind::TemplateString => true,
+        TokenKind::Punct => {
+            roles[i] == Role::Postfix || matches!(text(source, tokens, i), ")" | "]")
+        }
+        _ => false,
+    }
+}
+
+fn operator_roles(source: &str, tokens: &[Token]) -> Vec<Role> {
+    let mut roles = vec![Role::Other; tokens.len()];
+    for i in 0..tokens.len() {
+        if tokens[i].kind != TokenKind::Punct {
+            continue;
+        }
+        let after_operand =
+            previous_code(tokens, i).is_some_and(|p| ends_operand(source, tokens, &roles, p));
+        roles[i] = match text(source, tokens, i) {
+            "++" | "--" if after_operand => Role::Postfix,
+            "++" | "--" | "!" | "~" => Role::Unary,
+            "-" | "+" if !after_operand => Role::Unary,
+            "(" | ")" | "[" | "]" | "{" | "}" | "," | ";" | "." | ":" | "[@" | "[|" | "[?"
+            | "[#" | "[$" => Role::Other,
+            _ => Role::Binary,
+        };
+    }
+    roles
+}
+
+/// Per-line state for telling a ternary `:` from a label or field `:`.
+#[derive(Default)]
+struct LineState {
+    pending_ternaries: usize,
+    saw_function: bool,
+}
+
+fn wants_space(
+    source: &str,
+    tokens: &[Token],
+    roles: &[Role],
+    prev: usize,
+    next: usize,
+    state: &mut LineState,
+) -> bool {
+    let p = text(source, tokens, prev);
+    let n = text(source, tokens, next);
+    let p_punct = tokens[prev].kind == TokenKind::Punct;
+    let n_punct = tokens[next].kind == TokenKind::Punct;
+
+    if tokens[next].is_comment() || tokens[prev].kind == TokenKind::BlockComment {
+        return true;
+    }
+    if p_punct && matches!(p, "(" | "[") {
+        return false;
+    }
+    // `map[? key]`, `grid[# x, y]`, ...
+    if p_punct && ACCESSORS.contains(&p) {
+        return true;
+    }
+    if n_punct && matches!(n, ")" | "]" | "," | ";") {
+        return false;
+    }
+    if p_punct && matches!(p, "," | ";") {
+        return true;
+    }
+    if p_punct && p == "." || n_punct && n == "." {
+        return false;
+    }
+    if n_punct && n == ":" {
+        if state.pending_ternaries > 0 {
+            state.pending_ternaries -= 1;
+            return true;
+        }
+        // `function Child() : Parent() constructor`
+        return state.saw_function && p == ")";
+    }
+    if p_punct && p == ":" {
+        return true;
+    }
+    if n_punct && n == "?" {
+        state.pending_ternaries += 1;
+        return true;
+    }
+    if p_punct && p == "?" {
+        return true;
+    }
+    if n_punct && n == "(" {
+        return tokens[prev].kind == TokenKind::Ident && is_keyword(p) && p != "function"
+            || roles[prev] == Role::Binary;
+    }
+    if n_punct && ACCESSORS.contains(&n) {
+        return !ends_operand(source, tokens, roles, prev)
+            && (roles[prev] == Role::Binary || tokens[prev].kind == TokenKind::Ident);
+    }
+    if p_punct && p == "{" && n_punct && n == "}" {
+        return false;
+    }
+    if p_punct && p == "{" || n_punct && matches!(n, "{" | "}") || p_punct && p == "}" {
+        return true;
+    }
+    if roles[next] == Role::Postfix || roles[prev] == Role::Unary {
+        return false;
+    }
+    if roles[next] == Role::Unary {
+        return !(p_punct && matches!(p, "(" | "[") || roles[prev] == Role::Unary);
+    }
+    if roles[prev] == Role::Binary || roles[next] == Role::Binary {
+        return true;
+    }
+    if !n_punct && (!p_punct || matches!(p, ")" | "]")) {
+        return true;
+    }
+    // Anything else keeps whether the source had a space.
+    tokens[prev].span.end < tokens[next].span.start
+}
+
+fn render(
+    source: &str,
+    tokens: &[Token],
+    roles: &[Role],
+    lines: &[Line],
+    options: &FormatOptions,
+) -> String {
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Open {
+        Block,
+        Switch,
+        Other,
+    }
+    // Open brackets, each with the output line it was opened on.
+    let mut stack: Vec<(Open, usize)> = Vec::new();
+    let mut output = String::new();
+    // Set after a `switch (...)` header, so its `{` is known.
+    let mut after_switch = false;
+    let mut previous_header = false;
+
+    for (number, line) in lines.iter().enumerate() {
+        let first = line.tokens[0];
+        let first_text = text(source, tokens, first);
+
+        // Leading closers belong to the enclosing level.
+        let mut depth_stack = stack.clone();
+        for &i in &line.tokens {
+            if tokens[i].kind == TokenKind::Punct
+                && matches!(text(source, tokens, i), "}" | ")" | "]")
+            {
+                depth_stack.pop();
+            } else {
+                break;
+            }
+        }
+        let mut opened_on: Vec<usize> = depth_stack.iter().map(|&(_, line)| line).collect();
+        opened_on.dedup();
+        let mut level = opened_on.len();
+        level += depth_stack
+            .iter()
+            .filter(|(kind, _)| *kind == Open::Switch)
+            .count();
+        let starts_case = tokens[first].kind == TokenKind::Ident
+            && matches!(first_text, "case" | "default")
+            && depth_stack
+                .last()
+                .is_some_and(|(kind, _)| *kind == Open::Switch);
+        if starts_case {
+            level -= 1;
+        }
+        if previous_header && !(first_text == "{" && tokens[first].kind == TokenKind::Punct) {
+            level += 1;
+        }
+
+        let closes_block = is_punct(source, tokens, first, "}");
+        let after_open = output.ends_with("{\n");
+        let blank = if closes_block || after_open || number == 0 {
+            0
+        } else {
+            line.blank_before.min(options.max_blank_lines)
+        };
+        for _ in 0..blank {
+            output.push('\n');
+        }
+
+        let mut state = LineState::default();
+        for (position, &i) in line.tokens.iter().enumerate() {
+            let token_text = text(source, tokens, i);
+            if position == 0 {
+                for _ in 0..level {
+                    output.push_str(&options.indent);
+                }
+            } else if wants_space(
+                source,
+                tokens,
+                roles,
+                line.tokens[position - 1],
+                i,
+                &mut state,
+            ) {
+                output.push(' ');
+            }
+            output.push_str(token_text);
+
+            if tokens[i].kind == TokenKind::Ident && token_text == "function" {
+                state.saw_function = true;
+            }
+            if tokens[i].kind != TokenKind::Punct {
+                if token_text == "switch" {
+                    after_switch = true;
+                }
+                continue;
+            }
+            match token_text {
+                "{" => {
+                    let kind = if after_switch {
+                        Open::Switch
+                    } else if opens_block(source, tokens, i) {
+                        Open::Block
+                    } else {
+                        Open::Other
+                    };
+                    after_switch = false;
+                    stack.push((kind, number));
+                }
+                "(" | "[" | "[@" | "[|" | "[?" | "[#" | "[$" => stack.push((Open::Other, number)),
+                "}" | ")" | "]" => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+        output.push('\n');
+
+        previous_header = is_unbraced_header(source, tokens, line);
+    }
+    output
+}
+
+/// A control header whose body starts on the next line, e.g. `if (x)` or
+/// `else`.
+fn is_unbraced_header(source: &str, tokens: &[Token], line: &Line) -> bool {
+    let code: Vec<usize> = line
+        .tokens
+        .iter()
+        .copied()
+        .filter(|&i| !tokens[i].is_comment())
+        .collect();
+    let (Some(&first), Some(&last)) = (code.first(), code.last()) else {
+        return false;
+    };
+    let mut first_word = first;
+    if is_punct(source, tokens, first, "}") {
+        match code.get(1) {
+            Some(&next) => first_word = next,
+            None => return false,
+        }
+    }
+    if tokens[first_word].kind != TokenKind::Ident
+        || !HEADER_KEYWORDS.contains(&text(source, tokens, first_word))
+    {
+        return false;
+    }
+    let last_text = text(source, tokens, last);
+    if matches!(last_text, "else" | "do") {
+        return true;
+    }
+    // Any other `do` line carries its `until (...)` as well, which ends the
+    // statement: `do { i++ } until (i > 3)`.
+    if text(source, tokens, first_word) == "do" {
+        return false;
+    }
+    // The header's parentheses must close on this line.
+    last_text == ")"
+        && code
+            .iter()
+            .position(|&i| is_punct(source, tokens, i, "("))
+            .and_then(|at| matching_close(source, tokens, code[at]))
+            == Some(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(allman: bool) -> FormatOptions {
+        FormatOptions {
+            indent: "\t".into(),
+            allman,
+            max_blank_lines: 1,
+        }
+    }
+
+    fn fmt(source: &str) -> String {
+        format(source, &options(false)).unwrap()
+    }
+
+    fn fmt_allman(source: &str) -> String {
+        format(source, &options(true)).unwrap()
+    }
+
+    #[test]
+    fn one_line_do_until_without_semicolon_ends_the_statement() {
+        assert_eq!(
+            fmt("do { i++ } until (i > 3)\nenum E {\nA,\nB\n}\n"),
+            "do { i++ } until (i > 3)\nenum E {\n\tA,\n\tB\n}\n"
+        );
+    }
+
+    #[test]
+    fn unbraced_one_line_do_until_ends_the_statement() {
+        assert_eq!(
+            fmt("do i++; until (i > 3)\nx = 1;\n"),
+            "do i++; until (i > 3)\nx = 1;\n"
+        );
+    }
+
+    #[test]
+    fn until_after_a_closing_brace_ends_the_statement() {
+        assert_eq!(
+            fmt("do {\ni++;\n}\nuntil (i > 3)\nx = 1;\n"),
+            "do {\n\ti++;\n} until (i > 3)\nx = 1;\n"
+        );
+        assert_eq!(
+            fmt_allman("do {\ni++;\n} until (i > 3)\nx = 1;\n"),
+            "do\n{\n\ti++;\n}\nuntil (i > 3)\nx = 1;\n"
+        );
+    }
+
+    #[test]
+    fn unbraced_do_body_is_indented() {
+        assert_eq!(
+            fmt("do\ni++;\nuntil (i > 3)\nx = 1;\n"),
+            "do\n\ti++;\nuntil (i > 3)\nx = 1;\n"
+        );
+    }
+
+    #[test]
+    fn unbraced_if_body_is_indented() {
+        assert_eq!(
+            fmt("if (x)\ny = 1;\nz = 2;\n"),
+            "if (x)\n\ty = 1;\nz = 2;\n"
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sources = [
+            "do { i++ } until (i > 3)\nenum E { A, B }\n",
+            "do { i++ } until (i > 3);\nx = 1;\n",
+            "do i++; until (i > 3)\nx = 1;\n",
+            "do\n{\ni++;\n}\nuntil (i > 3)\nfunction f(a, b = 2) {\nreturn a+b;\n}\n",
+            "switch (state) {\ncase 1:\nx = 1;\nbreak;\ndefault:\nx = 0;\n}\n",
+            "if (a) {\nb();\n} else if (c)\nd();\nelse {\ne();\n}\n",
+            "var s = { a: 1, b: [1, 2] };\n\n\n\nwith (obj) {\nx += 1; // step\n}\n",
+            "array_foreach(a, function(v, i) {\nshow_debug_message(v);\n});\n",
+            "var s = {\nf: function() {\nreturn 1;\n},\ng: function() { return 2; }\n};\n",
+        ];
+        for source in sources {
+            for options in [options(false), options(true)] {
+                let once = format(source, &options).unwrap();
+                let twice = format(&once, &options).unwrap();
+                assert_eq!(once, twice, "not idempotent for {source:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn allman_keeps_the_brace_of_a_function_passed_as_an_argument() {
+        assert_eq!(
+            fmt_allman("array_foreach(a, function(v, i) {\nshow_debug_message(v);\n});\n"),
+            "array_foreach(a, function(v, i) {\n\tshow_debug_message(v);\n});\n"
+        );
+        assert_eq!(
+            fmt_allman("var s = { f: function() {\nreturn 1;\n} };\n"),
+            "var s = { f: function() {\n\treturn 1;\n} };\n"
+        );
+        assert_eq!(
+            fmt_allman("var s = {\nf: function() {\nreturn 1;\n}\n};\n"),
+            "var s = {\n\tf: function()\n\t{\n\t\treturn 1;\n\t}\n};\n"
+        );
+    }
+
+    #[test]
+    fn refuses_code_with_syntax_errors() {
+        assert!(format("x = ;\n", &options(false)).is_err());
+    }
+}
@@ -0,0 +1,379 @@
+use super::{Span, SyntaxError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Identifiers and keywords alike.
+    Ident,
+    /// Decimal, `$FF`/`0xFF` hex, `0b101` binary and `#RRGGBB` colour literals.
+    Number,
+    /// `"..."`, `'...'` and verbatim `@"..."` strings, quotes included.
+    String,
+    /// `$"... {expr} ..."`, quotes included.
+    TemplateString,
+    /// Operators and delimiters, longest match first (`<<=`, `[@`, `??`, ...).
+    Punct,
+    /// `// ...` up to (not including) the line break; `///` doc comments too.
+    LineComment,
+    /// `/* ... */`
+    BlockComment,
+    /// A whole `#macro` line, including `\` continuation lines.
+    Macro,
+    /// `#region` line.
+    Region,
+    /// `#endregion` line.
+    EndRegion,
+    /// Any other `#` directive line.
+    Directive,
+    /// A character GML has no use for.
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn is_comment(&self) -> bool {
+        matches!(self.kind, TokenKind::LineComment | TokenKind::BlockComment)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Lexed {
+    pub tokens: Vec<Token>,
+    pub errors: Vec<SyntaxError>,
+}
+
+const PUNCTUATION: &[&str] = &[
+    "<<=", ">>=", "??=", "??", "==", "!=", "<>", "<=", ">=", "&&", "||", "^^", "<<", ">>", "++",
+    "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", ":=", "[@", "[|", "[?", "[#", "[$", "{",
+    "}", "(", ")", "[", "]", ";", ",", ".", ":", "?", "+", "-", "*", "/", "%", "&", "|", "^", "~",
+    "!", "<", ">", "=",
+];
+
+/// Split GML source into tokens. Never fails: unterminated strings and
+/// comments run to the end of input and are reported in `errors`.
+pub fn tokenize(source: &str) -> Lexed {
+    Lexer {
+        source,
+        bytes: source.as_bytes(),
+        pos: 0,
+        line_start: true,
+        lexed: Lexed::default(),
+    }
+    .run()
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    /// Only whitespace since the last line break, so `#` starts a directive.
+    line_start: bool,
+    lexed: Lexed,
+}
+
+impl Lexer<'_> {
+    fn run(mut self) -> Lexed {
+        while let Some(&b) = self.bytes.get(self.pos) {
+            if b == b'\n' {
+                self.pos += 1;
+                self.line_start = true;
+                continue;
+            }
+            if b.is_ascii_whitespace() {
+                self.pos += 1;
+                continue;
+            }
+
+            let start = self.pos;
+            let kind = self.token(b);
+            self.line_start = false;
+            self.lexed.tokens.push(Token {
+                kind,
+                span: Span::new(start, self.pos),
+            });
+        }
+        self.lexed
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.bytes.get(self.pos + offset).copied()
+    }
+
+    fn error(&mut self, start: usize, message: &str) {
+        self.lexed.errors.push(SyntaxError {
+            message: message.to_string(),
+            span: Span::new(start, self.pos),
+        });
+    }
+
+    fn token(&mut self, b: u8) -> TokenKind {
+        let start = self.pos;
+        match b {
+            b'/' if self.peek(1) == Some(b'/') => {
+                self.skip_to_line_end();
+                TokenKind::LineComment
+            }
+            b'/' if self.peek(1) == Some(b'*') => {
+                match self.source[self.pos + 2..].find("*/") {
+                    Some(end) => self.pos += 2 + end + 2,
+                    None => {
+                        self.pos = self.bytes.len();
+                        self.error(start, "unterminated block comment");
+                    }
+                }
+                TokenKind::BlockComment
+            }
+            b'#' if self.is_colour_literal() => {
+                self.pos += 7;
+                TokenKind::Number
+            }
+            b'#' if self.line_start && self.peek(1).is_some_and(|c| c.is_ascii_alphabetic()) => {
+                self.directive()
+            }
+            b'"' | b'\'' => {
+                self.pos += 1;
+                self.string_body(b, start, true);
+                TokenKind::String
+            }
+            b'@' if matches!(self.peek(1), Some(b'"' | b'\'')) => {
+                let quote = self.bytes[self.pos + 1];
+                self.pos += 2;
+                self.string_body(quote, start, false);
+                TokenKind::String
+            }
+            b'$' if self.peek(1) == Some(b'"') => {
+                self.template_string(start);
+                TokenKind::TemplateString
+            }
+            b'$' if self.peek(1).is_some_and(|c| c.is_ascii_hexdigit()) => {
+                self.pos += 1;
+                self.eat_while(|c| c.is_ascii_hexdigit() || c == b'_');
+                TokenKind::Number
+            }
+            b'0' if matches!(self.peek(1), Some(b'x' | b'X' | b'b' | b'B')) => {
+                self.pos += 2;
+                self.eat_while(|c| c.is_ascii_hexdigit() || c == b'_');
+                TokenKind::Number
+            }
+            b'0'..=b'9' => {
+                self.number();
+                TokenKind::Number
+            }
+            b'.' if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => {
+                self.number();
+                TokenKind::Number
+            }
+            b if b == b'_' || b.is_ascii_alphabetic() => {
+                self.eat_while(|c| c == b'_' || c.is_ascii_alphanumeric());
+                TokenKind::Ident
+            }
+            _ => {
+                if let Some(punct) = PUNCTUATION
+                    .iter()
+                    .find(|p| self.source[self.pos..].starts_with(**p))
+                {
+                    self.pos += punct.len();
+                    TokenKind::Punct
+                } else {
+                    // Step over a whole (possibly multi-byte) character.
+                    let len = self.source[self.pos..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
+                    self.pos += len;
+                    TokenKind::Unknown
+                }
+            }
+        }
+    }
+
+    fn eat_while(&mut self, predicate: impl Fn(u8) -> bool) {
+        while self.peek(0).is_some_and(&predicate) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_to_line_end(&mut self) {
+        self.eat_while(|c| c != b'\n');
+        // Leave a `\r` of a CRLF line ending out of the token.
+        if self.pos > 0 && self.bytes[self.pos - 1] == b'\r' {
+            self.pos -= 1;
+        }
+    }
+
+    fn number(&mut self) {
+        self.eat_while(|c| c.is_ascii_digit() || c == b'_');
+        if self.peek(0) == Some(b'.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+            self.eat_while(|c| c.is_ascii_digit() || c == b'_');
+        } else if self.peek(0) == Some(b'.') {
+            // `1.` is a valid real.
+            self.pos += 1;
+        }
+    }
+
+    /// `#RRGGBB` not followed by more identifier characters.
+    fn is_colour_literal(&self) -> bool {
+        (1..=6).all(|i| self.peek(i).is_some_and(|c| c.is_ascii_hexdigit()))
+            && !self
+                .peek(7)
+                .is_some_and(|c| c == b'_' || c.is_ascii_alphanumeric())
+    }
+
+    fn directive(&mut self) -> TokenKind {
+        let word_end = self.source[self.pos + 1..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .map_or(self.bytes.len(), |i| self.pos + 1 + i);
+        let kind = match &self.source[self.pos + 1..word_end] {
+            "macro" => TokenKind::Macro,
+            "region" => TokenKind::Region,
+            "endregion" => TokenKind::EndRegion,
+            _ => TokenKind::Directive,
+        };
+
+        loop {
+            let line_start = self.pos;
+            self.skip_to_line_end();
+            let line = &self.source[line_start..self.pos];
+            // Macros continue onto the next line after a trailing backslash.
+            if kind == TokenKind::Macro && line.trim_end().ends_with('\\') {
+                self.eat_while(|c| c != b'\n');
+                if self.peek(0) == Some(b'\n') {
+                    self.pos += 1;
+                    continue;
+                }
+            }
+            break;
+        }
+        kind
+    }
+
+    /// Scan past the closing `quote`, starting just after the opening one.
+    fn string_body(&mut self, quote: u8, start: usize, escapes: bool) {
+        while let Some(c) = self.peek(0) {
+            self.pos += 1;
+            if c == b'\\' && escapes {
+                self.pos = (self.pos + 1).min(self.bytes.len());
+            } else if c == quote {
+                return;
+            } else if c == b'\n' && escapes {
+                // Regular strings can't span lines; stop so the rest of the
+                // file still lexes sensibly.
+                self.pos -= 1;
+                self.error(start, "unterminated string");
+                return;
+            }
+        }
+        self.error(start, "unterminated string");
+    }
+
+    fn template_string(&mut self, start: usize) {
+        self.pos += 2;
+        let mut depth = 0usize;
+        while let Some(c) = self.peek(0) {
+            match c {
+                b'\\' if depth == 0 => self.pos += 2,
+                b'{' => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                b'}' if depth > 0 => {
+                    depth -= 1;
+                    self.pos += 1;
+                }
+                b'"' if depth > 0 => {
+                    let string_start = self.pos;
+                    self.pos += 1;
+                    self.string_body(b'"', string_start, true);
+                }
+                b'"' => {
+                    self.pos += 1;
+                    return;
+                }
+                b'\n' => break,
+                _ => self.pos += 1,
+            }
+        }
+        self.pos = self.pos.min(self.bytes.len());
+        self.error(start, "unterminated template string");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds_and_text(source: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(source)
+            .tokens
+            .iter()
+            .map(|t| (t.kind, &source[t.span.start..t.span.end]))
+            .collect()
+    }
+
+    #[test]
+    fn macro_continues_after_trailing_backslash() {
+        let source = "#macro SUM 1 + \\\n    2\nx = SUM;";
+        let tokens = kinds_and_text(source);
+        assert_eq!(tokens[0], (TokenKind::Macro, "#macro SUM 1 + \\\n    2"));
+        assert_eq!(tokens[1], (TokenKind::Ident, "x"));
+    }
+
+    #[test]
+    fn continuation_stops_at_a_blank_line() {
+        let source = "#macro A 1 \\\n\nvar b = 2;";
+        let tokens = kinds_and_text(source);
+        assert_eq!(tokens[0], (TokenKind::Macro, "#macro A 1 \\\n"));
+        assert_eq!(tokens[1], (TokenKind::Ident, "var"));
+    }
+
+    #[test]
+    fn backslash_on_an_earlier_line_does_not_continue_a_macro() {
+        let source = "s = \"a\\\\\";\n#macro A 1\nvar b = 2;";
+        let tokens = kinds_and_text(source);
+        let at = tokens
+            .iter()
+            .position(|(kind, _)| *kind == TokenKind::Macro)
+            .unwrap();
+        assert_eq!(tokens[at].1, "#macro A 1");
+        assert_eq!(tokens[at + 1], (TokenKind::Ident, "var"));
+    }
+
+    #[test]
+    fn crlf_macro_continuation() {
+        let source = "#macro A 1 \\\r\n2\r\nb";
+        let tokens = kinds_and_text(source);
+        assert_eq!(tokens[0], (TokenKind::Macro, "#macro A 1 \\\r\n2"));
+        assert_eq!(tokens[1], (TokenKind::Ident, "b"));
+    }
+
+    #[test]
+    fn other_directives_never_continue() {
+        let source = "#region setup \\\nx = 1;";
+        let tokens = kinds_and_text(source);
+        assert_eq!(tokens[0], (TokenKind::Region, "#region setup \\"));
+        assert_eq!(tokens[1], (TokenKind::Ident, "x"));
+    }
+
+    #[test]
+    fn hash_mid_line_is_a_colour_not_a_directive() {
+        let tokens = kinds_and_text("c = #FF0000;");
+        assert_eq!(tokens[2], (TokenKind::Number, "#FF0000"));
+    }
+
+    #[test]
+    fn unterminated_string_is_reported_and_lexing_continues() {
+        let lexed = tokenize("a = \"open\nb = 1;");
+        assert_eq!(lexed.errors.len(), 1);
+        assert!(
+            lexed
+                .tokens
+                .iter()
+                .any(|t| t.kind == TokenKind::Number && t.span == Span::new(14, 15))
+        );
+    }
+}
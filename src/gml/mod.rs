@@ -0,0 +1,100 @@
+//! GML source model shared by the code tools: a lexer, a tolerant parser that
+//! always produces an AST (collecting syntax errors instead of stopping), and
+//! helpers for mapping byte spans back to lines and columns.
+
+pub mod ast;
+pub mod format;
+mod lexer;
+mod parser;
+
+pub use lexer::{Token, TokenKind, tokenize};
+pub use parser::{Program, parse};
+
+/// Byte range into the source text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn text(self, source: &str) -> &str {
+        &source[self.start..self.end]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub message: String,
+    pub span: Span,
+}
+
+/// Maps byte offsets to 1-based line and column numbers.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// `(line, column)`, both 1-based; the column counts bytes.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        (line + 1, offset - self.line_starts[line] + 1)
+    }
+}
+
+/// GML's reserved words. Built-in constants such as `true` or `self` are
+/// ordinary identifiers to the parser.
+pub const KEYWORDS: &[&str] = &[
+    "and",
+    "begin",
+    "break",
+    "case",
+    "catch",
+    "constructor",
+    "continue",
+    "default",
+    "delete",
+    "div",
+    "do",
+    "else",
+    "end",
+    "enum",
+    "exit",
+    "finally",
+    "for",
+    "function",
+    "globalvar",
+    "if",
+    "mod",
+    "new",
+    "not",
+    "or",
+    "repeat",
+    "return",
+    "static",
+    "switch",
+    "then",
+    "throw",
+    "try",
+    "until",
+    "var",
+    "while",
+    "with",
+    "xor",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word)
+}
@@ -0,0 +1,1027 @@
+use super::ast::*;
+use super::lexer::{Token, TokenKind, tokenize};
+use super::{Span, SyntaxError, is_keyword};
+
+/// A parsed source file.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    /// Comments in source order; they are not part of the tree.
+    pub comments: Vec<Token>,
+    /// Lexer and parser errors in source order. The tree is still usable:
+    /// broken regions become `Error` nodes.
+    pub errors: Vec<SyntaxError>,
+}
+
+/// Binary operators from loosest to tightest binding. `=` compares when it
+/// isn't the assignment of an expression statement.
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["??"],
+    &["||", "or"],
+    &["^^", "xor"],
+    &["&&", "and"],
+    &["==", "!=", "<>", "="],
+    &["<", ">", "<=", ">="],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%", "div", "mod"],
+];
+
+const ASSIGNMENT_OPS: &[&str] = &[
+    "=", ":=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=", "??=",
+];
+
+const PREFIX_OPS: &[&str] = &["!", "not", "-", "+", "~", "++", "--"];
+
+const INDEX_OPENERS: &[&str] = &["[", "[@", "[|", "[?", "[#", "[$"];
+
+pub fn parse(source: &str) -> Program {
+    let lexed = tokenize(source);
+    let (comments, tokens): (Vec<Token>, Vec<Token>) =
+        lexed.tokens.into_iter().partition(Token::is_comment);
+
+    let mut parser = Parser::new(source, tokens);
+    let statements = parser.statements_until(|_| false);
+
+    let mut errors = lexed.errors;
+    errors.extend(parser.errors);
+    errors.sort_by_key(|e| e.span.start);
+
+    Program {
+        statements,
+        comments,
+        errors,
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    errors: Vec<SyntaxError>,
+    /// Parsing the target of an expression statement, where `=` assigns.
+    assign_allowed: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str, tokens: Vec<Token>) -> Self {
+        Self {
+            source,
+            tokens,
+            pos: 0,
+            errors: Vec::new(),
+            assign_allowed: false,
+        }
+    }
+
+    // -- Token helpers ------------------------------------------------------
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<Token> {
+        self.tokens.get(self.pos + offset).copied()
+    }
+
+    fn text(&self, token: Token) -> &'a str {
+        token.span.text(self.source)
+    }
+
+    fn peek_text(&self) -> &'a str {
+        self.peek().map_or("", |t| self.text(t))
+    }
+
+    fn at(&self, text: &str) -> bool {
+        self.peek().is_some_and(|t| {
+            matches!(t.kind, TokenKind::Ident | TokenKind::Punct) && self.text(t) == text
+        })
+    }
+
+    fn at_any(&self, options: &[&str]) -> bool {
+        options.iter().any(|o| self.at(o))
+    }
+
+    fn at_ident(&self) -> bool {
+        self.peek()
+            .is_some_and(|t| t.kind == TokenKind::Ident && !is_keyword(self.text(t)))
+    }
+
+    fn bump(&mut self) -> Option<Token> {
+        let token = self.peek();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let found = self.at(text);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, text: &str) -> bool {
+        let found = self.eat(text);
+        if !found {
+            self.error_here(format!("expected `{text}`"));
+        }
+        found
+    }
+
+    fn error_here(&mut self, message: String) {
+        let span = match self.peek() {
+            Some(token) => token.span,
+            None => Span::new(self.source.len(), self.source.len()),
+        };
+        // One report per position: recovery often trips over the same token.
+        if self
+            .errors
+            .last()
+            .is_some_and(|e| e.span.start == span.start)
+        {
+            return;
+        }
+        self.errors.push(SyntaxError { message, span });
+    }
+
+    fn start(&self) -> usize {
+        self.peek().map_or(self.source.len(), |t| t.span.start)
+    }
+
+    fn prev_end(&self) -> usize {
+        match self.pos.checked_sub(1) {
+            Some(i) => self.tokens[i].span.end,
+            None => 0,
+        }
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.prev_end().max(start))
+    }
+
+    /// No line break between the previous token and the next one.
+    fn next_on_same_line(&self) -> bool {
+        self.peek()
+            .is_some_and(|t| !self.source[self.prev_end()..t.span.start].contains('\n'))
+    }
+
+    fn ident(&mut self) -> Option<Ident> {
+        if !self.at_ident() {
+            let found = self.peek_text();
+            self.error_here(if found.is_empty() {
+                "expected identifier".to_string()
+            } else {
+                format!("expected identifier, found `{found}`")
+            });
+            return None;
+        }
+        let token = self.bump()?;
+        Some(Ident {
+            name: self.text(token).to_string(),
+            span: token.span,
+        })
+    }
+
+    // -- Statements ---------------------------------------------------------
+
+    fn statements_until(&mut self, stop: impl Fn(&Self) -> bool) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        while self.peek().is_some() && !stop(self) {
+            let before = self.pos;
+            stmts.push(self.statement());
+            if self.pos == before {
+                // Nothing could start here; skip the token so parsing moves on.
+                let token = self.bump().expect("peeked above");
+                if self.errors.last().map(|e| e.span.start) != Some(token.span.start) {
+                    self.errors.push(SyntaxError {
+                        message: format!("unexpected `{}`", self.text(token)),
+                        span: token.span,
+                    });
+                }
+                stmts.push(Stmt {
+                    kind: StmtKind::Error,
+                    span: token.span,
+                });
+            }
+        }
+        stmts
+    }
+
+    fn statement(&mut self) -> Stmt {
+        let start = self.start();
+        let kind = match self.peek() {
+            None => StmtKind::Empty,
+            Some(token) => match token.kind {
+                TokenKind::Macro => {
+                    self.bump();
+                    self.macro_decl(token)
+                }
+                TokenKind::Region => {
+                    self.bump();
+                    let label = self.text(token)["#region".len()..].trim();
+                    StmtKind::Region(label.to_string())
+                }
+                TokenKind::EndRegion => {
+                    self.bump();
+                    StmtKind::EndRegion
+                }
+                TokenKind::Directive => {
+                    self.bump();
+                    StmtKind::Directive
+                }
+                _ => self.statement_kind(),
+            },
+        };
+        self.eat(";");
+        Stmt {
+            kind,
+            span: self.span_from(start),
+        }
+    }
+
+    fn statement_kind(&mut self) -> StmtKind {
+        match self.peek_text() {
+            "{" | "begin" => self.block().kind,
+            ";" => StmtKind::Empty,
+            "var" => self.declare(DeclareKind::Var),
+            "static" => self.declare(DeclareKind::Static),
+            "globalvar" => self.declare(DeclareKind::GlobalVar),
+            "if" => {
+                self.bump();
+                let condition = self.expression();
+                self.eat("then");
+                let then_branch = Box::new(self.statement());
+                let else_branch = if self.eat("else") {
+                    Some(Box::new(self.statement()))
+                } else {
+                    None
+                };
+                StmtKind::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                }
+            }
+            "while" => {
+                self.bump();
+                let condition = self.expression();
+                self.eat("do");
+                StmtKind::While {
+                    condition,
+                    body: Box::new(self.statement()),
+                }
+            }
+            "repeat" => {
+                self.bump();
+                let count = self.expression();
+                StmtKind::Repeat {
+                    count,
+                    body: Box::new(self.statement()),
+                }
+            }
+            "with" => {
+                self.bump();
+                let target = self.expression();
+                StmtKind::With {
+                    target,
+                    body: Box::new(self.statement()),
+                }
+            }
+            "do" => {
+                self.bump();
+                let body = Box::new(self.statement());
+                self.expect("until");
+                StmtKind::DoUntil {
+                    body,
+                    condition: self.expression(),
+                }
+            }
+            "for" => self.for_statement(),
+            "switch" => self.switch_statement(),
+            "return" => {
+                self.bump();
+                let has_value = self.next_on_same_line() && !self.at_any(&[";", "}"]);
+                StmtKind::Return(has_value.then(|| self.expression()))
+            }
+            "break" => {
+                self.bump();
+                StmtKind::Break
+            }
+            "continue" => {
+                self.bump();
+                StmtKind::Continue
+            }
+            "exit" => {
+                self.bump();
+                StmtKind::Exit
+            }
+            "throw" => {
+                self.bump();
+                StmtKind::Throw(self.expression())
+            }
+            "delete" => {
+                self.bump();
+                StmtKind::Delete(self.expression())
+            }
+            "try" => self.try_statement(),
+            "enum" => self.enum_decl(),
+            "function"
+                if self
+                    .peek_at(1)
+                    .is_some_and(|t| t.kind == TokenKind::Ident && !is_keyword(self.text(t))) =>
+            {
+                StmtKind::Function(self.function())
+            }
+            _ => self.expression_statement(),
+        }
+    }
+
+    fn block(&mut self) -> Stmt {
+        let start = self.start();
+        if !self.eat("{") && !self.eat("begin") {
+            self.error_here("expected `{`".to_string());
+            return Stmt {
+                kind: StmtKind::Error,
+                span: Span::new(start, start),
+            };
+        }
+        let body = self.statements_until(|p| p.at_any(&["}", "end"]));
+        if !self.eat("}") && !self.eat("end") {
+            self.error_here("expected `}`".to_string());
+        }
+        Stmt {
+            kind: StmtKind::Block(body),
+            span: self.span_from(start),
+        }
+    }
+
+    fn declare(&mut self, kind: DeclareKind) -> StmtKind {
+        self.bump();
+        let mut vars = Vec::new();
+        while let Some(name) = self.ident() {
+            let init = if self.eat("=") {
+                Some(self.expression())
+            } else {
+                None
+            };
+            vars.push(VarDeclarator { name, init });
+            if !self.eat(",") {
+                break;
+            }
+        }
+        StmtKind::Declare { kind, vars }
+    }
+
+    /// A `for` header clause: a `var` declaration or an expression statement.
+    fn simple_statement(&mut self) -> Stmt {
+        let start = self.start();
+        let kind = if self.at("var") {
+            self.declare(DeclareKind::Var)
+        } else {
+            self.expression_statement()
+        };
+        Stmt {
+            kind,
+            span: self.span_from(start),
+        }
+    }
+
+    fn for_statement(&mut self) -> StmtKind {
+        self.bump();
+        self.expect("(");
+        let init = (!self.at(";")).then(|| Box::new(self.simple_statement()));
+        self.expect(";");
+        let condition = (!self.at(";")).then(|| self.expression());
+        self.expect(";");
+        let step = (!self.at(")")).then(|| Box::new(self.simple_statement()));
+        self.expect(")");
+        StmtKind::For {
+            init,
+            condition,
+            step,
+            body: Box::new(self.statement()),
+        }
+    }
+
+    fn switch_statement(&mut self) -> StmtKind {
+        self.bump();
+        let subject = self.expression();
+        let mut cases = Vec::new();
+        if !self.expect("{") {
+            return StmtKind::Switch { subject, cases };
+        }
+
+        while self.peek().is_some() && !self.at("}") {
+            let start = self.start();
+            let value = if self.eat("case") {
+                Some(self.expression())
+            } else if self.eat("default") {
+                None
+            } else {
+                self.error_here(format!(
+                    "expected `case` or `default`, found `{}`",
+                    self.peek_text()
+                ));
+                self.bump();
+                continue;
+            };
+            self.expect(":");
+            let body = self.statements_until(|p| p.at_any(&["case", "default", "}"]));
+            cases.push(SwitchCase {
+                value,
+                body,
+                span: self.span_from(start),
+            });
+        }
+        self.expect("}");
+        StmtKind::Switch { subject, cases }
+    }
+
+    fn try_statement(&mut self) -> StmtKind {
+        self.bump();
+        let body = Box::new(self.block());
+        let catch = if self.eat("catch") {
+            let binding = if self.eat("(") {
+                let binding = self.ident();
+                self.expect(")");
+                binding
+            } else {
+                None
+            };
+            Some(CatchClause {
+                binding,
+                body: Box::new(self.block()),
+            })
+        } else {
+            None
+        };
+        let finally = if self.eat("finally") {
+            Some(Box::new(self.block()))
+        } else {
+            None
+        };
+        StmtKind::Try {
+            body,
+            catch,
+            finally,
+        }
+    }
+
+    fn enum_decl(&mut self) -> StmtKind {
+        self.bump();
+        let Some(name) = self.ident() else {
+            return StmtKind::Error;
+        };
+        let mut members = Vec::new();
+        if self.expect("{") {
+            while self.peek().is_some() && !self.at("}") {
+                let Some(member) = self.ident() else {
+                    break;
+                };
+                let value = if self.eat("=") {
+                    Some(self.expression())
+                } else {
+                    None
+                };
+                members.push(EnumMember {
+                    name: member,
+                    value,
+                });
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect("}");
+        }
+        StmtKind::Enum(EnumDecl { name, members })
+    }
+
+    fn macro_decl(&mut self, token: Token) -> StmtKind {
+        let after_keyword = token.span.start + "#macro".len();
+        let rest = &self.source[after_keyword..token.span.end];
+        let name_start = after_keyword + (rest.len() - rest.trim_start().len());
+        let rest = &self.source[name_start..token.span.end];
+
+        let name_len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':'))
+            .unwrap_or(rest.len());
+        let full_name = &rest[..name_len];
+        let (config, name, name_offset) = match full_name.split_once(':') {
+            Some((config, name)) => (
+                Some(config.to_string()),
+                name,
+                name_start + config.len() + 1,
+            ),
+            None => (None, full_name, name_start),
+        };
+        if name.is_empty() {
+            self.errors.push(SyntaxError {
+                message: "expected macro name".to_string(),
+                span: token.span,
+            });
+            return StmtKind::Directive;
+        }
+
+        let value = &rest[name_len..];
+        let value_start = name_start + name_len + (value.len() - value.trim_start().len());
+        let value = value.trim();
+        StmtKind::Macro(MacroDecl {
+            name: Ident {
+                name: name.to_string(),
+                span: Span::new(name_offset, name_offset + name.len()),
+            },
+            config,
+            value: value.to_string(),
+            value_span: Span::new(value_start, value_start + value.len()),
+        })
+    }
+
+    fn expression_statement(&mut self) -> StmtKind {
+        let start = self.start();
+        let saved = std::mem::replace(&mut self.assign_allowed, true);
+        let target = self.ternary();
+        self.assign_allowed = saved;
+
+        if self.at_any(ASSIGNMENT_OPS) {
+            let op = self.bump().map(|t| self.text(t)).unwrap_or_default();
+            let value = self.expression();
+            return StmtKind::Expr(Expr {
+                kind: ExprKind::Assign {
+                    op: op.to_string(),
+                    target: Box::new(target),
+                    value: Box::new(value),
+                },
+                span: self.span_from(start),
+            });
+        }
+        StmtKind::Expr(target)
+    }
+
+    // -- Expressions --------------------------------------------------------
+
+    /// An expression in which `=` compares.
+    fn expression(&mut self) -> Expr {
+        let saved = std::mem::replace(&mut self.assign_allowed, false);
+        let expr = self.ternary();
+        self.assign_allowed = saved;
+        expr
+    }
+
+    fn ternary(&mut self) -> Expr {
+        let start = self.start();
+        let condition = self.binary(0);
+        if !self.eat("?") {
+            return condition;
+        }
+        let then_value = self.expression();
+        self.expect(":");
+        let else_value = self.expression();
+        Expr {
+            kind: ExprKind::Ternary {
+                condition: Box::new(condition),
+                then_value: Box::new(then_value),
+                else_value: Box::new(else_value),
+            },
+            span: self.span_from(start),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Expr {
+        if level == BINARY_LEVELS.len() {
+            return self.unary();
+        }
+        let start = self.start();
+        let mut lhs = self.binary(level + 1);
+        while let Some(token) = self.peek() {
+            let op = self.text(token);
+            let is_operator = match token.kind {
+                TokenKind::Punct => true,
+                TokenKind::Ident => is_keyword(op),
+                _ => false,
+            };
+            if !is_operator
+                || !BINARY_LEVELS[level].contains(&op)
+                || (op == "=" && self.assign_allowed)
+            {
+                break;
+            }
+            self.bump();
+            let rhs = self.binary(level + 1);
+            lhs = Expr {
+                kind: ExprKind::Binary {
+                    op: op.to_string(),
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                span: self.span_from(start),
+            };
+        }
+        lhs
+    }
+
+    fn unary(&mut self) -> Expr {
+        if !self.at_any(PREFIX_OPS) {
+            return self.postfix();
+        }
+        let start = self.start();
+        let op = self.bump().map(|t| self.text(t)).unwrap_or_default();
+        let operand = self.unary();
+        Expr {
+            kind: ExprKind::Unary {
+                op: op.to_string(),
+                operand: Box::new(operand),
+            },
+            span: self.span_from(start),
+        }
+    }
+
+    fn postfix(&mut self) -> Expr {
+        let start = self.start();
+        let mut expr = self.primary();
+        loop {
+            let kind = if self.eat("(") {
+                ExprKind::Call {
+                    callee: Box::new(expr),
+                    args: self.arguments(")"),
+                }
+            } else if self.eat(".") {
+                let Some(token) = self.peek().filter(|t| t.kind == TokenKind::Ident) else {
+                    self.error_here("expected field name after `.`".to_string());
+                    break;
+                };
+                self.bump();
+                ExprKind::Member {
+                    object: Box::new(expr),
+                    field: Ident {
+                        name: self.text(token).to_string(),
+                        span: token.span,
+                    },
+                }
+            } else if self.at_any(INDEX_OPENERS) {
+                let accessor = self.bump().map(|t| self.text(t)).unwrap_or_default();
+                ExprKind::Index {
+                    object: Box::new(expr),
+                    accessor: accessor.to_string(),
+                    indices: self.arguments("]"),
+                }
+            } else if self.at_any(&["++", "--"]) && self.next_on_same_line() {
+                let op = self.bump().map(|t| self.text(t)).unwrap_or_default();
+                ExprKind::Postfix {
+                    op: op.to_string(),
+                    operand: Box::new(expr),
+                }
+            } else {
+                break;
+            };
+            expr = Expr {
+                kind,
+                span: self.span_from(start),
+            };
+        }
+        expr
+    }
+
+    /// Comma-separated expressions up to and including `close`; the opening
+    /// delimiter has already been consumed. Allows a trailing comma.
+    fn arguments(&mut self, close: &str) -> Vec<Expr> {
+        let mut args = Vec::new();
+        while self.peek().is_some() && !self.at(close) {
+            args.push(self.expression());
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect(close);
+        args
+    }
+
+    fn primary(&mut self) -> Expr {
+        let start = self.start();
+        let Some(token) = self.peek() else {
+            self.error_here("unexpected end of input".to_string());
+            return Expr {
+                kind: ExprKind::Error,
+                span: Span::new(start, start),
+            };
+        };
+        let text = self.text(token);
+
+        let kind = match token.kind {
+            TokenKind::Number => {
+                self.bump();
+                ExprKind::Number(text.to_string())
+            }
+            TokenKind::String => {
+                self.bump();
+                ExprKind::String(text.to_string())
+            }
+            TokenKind::TemplateString => {
+                self.bump();
+                ExprKind::Template(self.template_parts(token))
+            }
+            TokenKind::Ident if text == "function" => ExprKind::Function(Box::new(self.function())),
+            TokenKind::Ident if text == "new" => {
+                self.bump();
+                let callee_start = self.start();
+                let mut callee = match self.ident() {
+                    Some(name) => Expr {
+                        kind: ExprKind::Ident(name.name),
+                        span: name.span,
+                    },
+                    None => Expr {
+                        kind: ExprKind::Error,
+                        span: Span::new(callee_start, callee_start),
+                    },
+                };
+                while self.eat(".") {
+                    let Some(field) = self.ident() else {
+                        break;
+                    };
+                    callee = Expr {
+                        kind: ExprKind::Member {
+                            object: Box::new(callee),
+                            field,
+                        },
+                        span: self.span_from(callee_start),
+                    };
+                }
+                let args = if self.eat("(") {
+                    self.arguments(")")
+                } else {
+                    Vec::new()
+                };
+                ExprKind::New {
+                    callee: Box::new(callee),
+                    args,
+                }
+            }
+            TokenKind::Ident if !is_keyword(text) => {
+                self.bump();
+                ExprKind::Ident(text.to_string())
+            }
+            TokenKind::Punct if text == "(" => {
+                self.bump();
+                let inner = self.expression();
+                self.expect(")");
+                return Expr {
+                    kind: inner.kind,
+                    span: self.span_from(start),
+                };
+            }
+            TokenKind::Punct if text == "[" => {
+                self.bump();
+                ExprKind::Array(self.arguments("]"))
+            }
+            TokenKind::Punct if text == "{" => self.struct_literal(),
+            _ => {
+                self.error_here(format!("expected expression, found `{text}`"));
+                return Expr {
+                    kind: ExprKind::Error,
+                    span: Span::new(start, start),
+                };
+            }
+        };
+        Expr {
+            kind,
+            span: self.span_from(start),
+        }
+    }
+
+    fn struct_literal(&mut self) -> ExprKind {
+        self.bump();
+        let mut fields = Vec::new();
+        while self.peek().is_some() && !self.at("}") {
+            let Some(token) = self
+                .peek()
+                .filter(|t| matches!(t.kind, TokenKind::Ident | TokenKind::String))
+            else {
+                self.error_here(format!("expected field name, found `{}`", self.peek_text()));
+                break;
+            };
+            self.bump();
+            let name = Ident {
+                name: self.text(token).trim_matches('"').to_string(),
+                span: token.span,
+            };
+            let value = if self.eat(":") {
+                Some(self.expression())
+            } else {
+                None
+            };
+            fields.push(StructField { name, value });
+            if !self.eat(",") {
+                break;
+            }
+        }
+        self.expect("}");
+        ExprKind::Struct(fields)
+    }
+
+    fn function(&mut self) -> Function {
+        let start = self.start();
+        self.bump();
+        let name = if self.at_ident() { self.ident() } else { None };
+
+        let mut params = Vec::new();
+        if self.expect("(") {
+            while self.peek().is_some() && !self.at(")") {
+                let Some(name) = self.ident() else {
+                    break;
+                };
+                let default = if self.eat("=") {
+                    Some(self.expression())
+                } else {
+                    None
+                };
+                params.push(Param { name, default });
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(")");
+        }
+
+        let parent = if self.eat(":") {
+            let name = self.ident();
+            let args = if self.eat("(") {
+                self.arguments(")")
+            } else {
+                Vec::new()
+            };
+            name.map(|name| ParentCall { name, args })
+        } else {
+            None
+        };
+        let is_constructor = self.eat("constructor");
+
+        let body = match self.block().kind {
+            StmtKind::Block(body) => body,
+            _ => Vec::new(),
+        };
+        Function {
+            name,
+            params,
+            parent,
+            is_constructor,
+            body,
+            span: self.span_from(start),
+        }
+    }
+
+    /// Split `$"text {expr} text"` into parts, parsing each interpolation.
+    fn template_parts(&mut self, token: Token) -> Vec<TemplatePart> {
+        let content_start = token.span.start + 2;
+        let content_end = if self.text(token).len() > 2 && self.text(token).ends_with('"') {
+            token.span.end - 1
+        } else {
+            token.span.end
+        };
+
+        let bytes = self.source.as_bytes();
+        let mut parts = Vec::new();
+        let mut text_start = content_start;
+        let mut i = content_start;
+        while i < content_end {
+            match bytes[i] {
+                b'\\' => i += 2,
+                b'{' => {
+                    if text_start < i {
+                        parts.push(TemplatePart::Text(self.source[text_start..i].to_string()));
+                    }
+                    let expr_start = i + 1;
+                    let expr_end = matching_brace(bytes, expr_start, content_end);
+                    parts.push(TemplatePart::Expr(
+                        self.sub_expression(expr_start, expr_end),
+                    ));
+                    i = expr_end + 1;
+                    text_start = i;
+                }
+                _ => i += 1,
+            }
+        }
+        if text_start < content_end {
+            parts.push(TemplatePart::Text(
+                self.source[text_start..content_end].to_string(),
+            ));
+        }
+        parts
+    }
+
+    /// Parse `source[start..end]` as a single expression.
+    fn sub_expression(&mut self, start: usize, end: usize) -> Expr {
+        let shift = |span: Span| Span::new(span.start + start, span.end + start);
+        let lexed = tokenize(&self.source[start..end]);
+        let tokens = lexed
+            .tokens
+            .into_iter()
+            .filter(|t| !t.is_comment())
+            .map(|t| Token {
+                kind: t.kind,
+                span: shift(t.span),
+            })
+            .collect();
+
+        let mut sub = Parser::new(self.source, tokens);
+        let expr = sub.expression();
+        if sub.peek().is_some() {
+            sub.error_here("unexpected tokens in template interpolation".to_string());
+        }
+        self.errors
+            .extend(lexed.errors.into_iter().map(|e| SyntaxError {
+                message: e.message,
+                span: shift(e.span),
+            }));
+        self.errors.extend(sub.errors);
+        expr
+    }
+}
+
+/// Index of the `}` closing an interpolation that starts at `start`, skipping
+/// nested braces and strings; `end` if there is none.
+fn matching_brace(bytes: &[u8], start: usize, end: usize) -> usize {
+    let mut depth = 0usize;
+    let mut i = start;
+    while i < end {
+        match bytes[i] {
+            b'"' => {
+                i += 1;
+                while i < end && bytes[i] != b'"' {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return i,
+            b'}' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(source: &str) -> Vec<StmtKind> {
+        let program = parse(source);
+        assert_eq!(program.errors, Vec::new(), "errors parsing {source:?}");
+        program.statements.into_iter().map(|s| s.kind).collect()
+    }
+
+    #[test]
+    fn multi_line_macro_keeps_its_whole_value() {
+        let kinds = statements("#macro SUM 1 + \\\n    2\nvar x = SUM;");
+        let StmtKind::Macro(decl) = &kinds[0] else {
+            panic!("expected a macro, got {:?}", kinds[0]);
+        };
+        assert_eq!(decl.name.name, "SUM");
+        assert_eq!(decl.value, "1 + \\\n    2");
+        assert!(matches!(kinds[1], StmtKind::Declare { .. }));
+    }
+
+    #[test]
+    fn statement_after_blank_line_is_not_part_of_a_macro() {
+        let kinds = statements("#macro A 1 \\\n\nvar b = 2;");
+        assert!(matches!(&kinds[0], StmtKind::Macro(decl) if decl.value == "1 \\"));
+        assert!(matches!(kinds[1], StmtKind::Declare { .. }));
+    }
+
+    #[test]
+    fn macro_config_prefix() {
+        let kinds = statements("#macro Desktop:SCALE 2");
+        let StmtKind::Macro(decl) = &kinds[0] else {
+            panic!("expected a macro, got {:?}", kinds[0]);
+        };
+        assert_eq!(decl.config.as_deref(), Some("Desktop"));
+        assert_eq!(decl.name.name, "SCALE");
+        assert_eq!(decl.value, "2");
+    }
+
+    #[test]
+    fn do_until_without_semicolon_ends_the_statement() {
+        let kinds = statements("do { i++ } until (i > 3)\nenum E { A, B }");
+        assert!(matches!(kinds[0], StmtKind::DoUntil { .. }));
+        assert!(matches!(&kinds[1], StmtKind::Enum(decl) if decl.members.len() == 2));
+    }
+
+    #[test]
+    fn recovers_after_a_syntax_error() {
+        let program = parse("x = ;\ny = 2;");
+        assert!(!program.errors.is_empty());
+        assert!(program.statements.iter().any(|s| matches!(
+            &s.kind,
+            StmtKind::Expr(Expr { kind: ExprKind::Assign { target, .. }, .. })
+                if target.kind == ExprKind::Ident("y".into())
+        )));
+    }
+}
@@ -0,0 +1,162 @@
+//! Project-wide lookups over a project's `.gml` sources for the definitions
+//! code generators build on: enums and constructor functions.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::gml::{
+    self,
+    ast::{self, Expr, ExprKind, Function, Stmt, StmtKind, Visitor},
+};
+
+/// `enum Name { A, B = 2, C }`
+#[derive(Debug, Clone)]
+pub struct EnumDef {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+/// `function Name(params) [: Parent(args)] constructor { ... }`
+#[derive(Debug, Clone)]
+pub struct ConstructorDef {
+    pub name: String,
+    pub parent: Option<String>,
+    /// Instance variables assigned at the top level of the body, excluding
+    /// methods and statics.
+    pub fields: Vec<String>,
+}
+
+/// Every `.gml` file under `dir`, skipping hidden folders.
+pub fn collect_gml_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_into(dir, &mut files);
+    files
+}
+
+fn collect_into(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = dir.read_dir() else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_into(&path, out);
+        } else if path.extension().and_then(|e| e.to_str()) == Some("gml") {
+            out.push(path);
+        }
+    }
+}
+
+pub fn find_enum(project_dir: &Path, name: &str) -> Option<EnumDef> {
+    collect_gml_files(project_dir).into_iter().find_map(|file| {
+        let definitions = Definitions::of(&fs::read_to_string(file).ok()?);
+        definitions.enums.into_iter().find(|e| e.name == name)
+    })
+}
+
+pub fn find_constructor(project_dir: &Path, name: &str) -> Option<ConstructorDef> {
+    collect_gml_files(project_dir).into_iter().find_map(|file| {
+        let definitions = Definitions::of(&fs::read_to_string(file).ok()?);
+        definitions
+            .constructors
+            .into_iter()
+            .find(|c| c.name == name)
+    })
+}
+
+/// Every field a constructor's instances have, inherited ones first.
+pub fn all_fields(project_dir: &Path, constructor: &ConstructorDef) -> Vec<String> {
+    let mut chain = vec![constructor.clone()];
+    while let Some(parent) = chain.last().and_then(|c| c.parent.clone()) {
+        // Guard against cycles from malformed code.
+        if chain.iter().any(|c| c.name == parent) {
+            break;
+        }
+        match find_constructor(project_dir, &parent) {
+            Some(def) => chain.push(def),
+            None => break,
+        }
+    }
+
+    let mut fields: Vec<String> = Vec::new();
+    for def in chain.iter().rev() {
+        for field in &def.fields {
+            if !fields.contains(field) {
+                fields.push(field.clone());
+            }
+        }
+    }
+    fields
+}
+
+/// Enums and constructors declared anywhere in one source file.
+#[derive(Default)]
+struct Definitions {
+    enums: Vec<EnumDef>,
+    constructors: Vec<ConstructorDef>,
+}
+
+impl Definitions {
+    fn of(source: &str) -> Self {
+        let mut definitions = Self::default();
+        ast::walk_stmts(&mut definitions, &gml::parse(source).statements);
+        definitions
+    }
+}
+
+impl Visitor for Definitions {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        if let StmtKind::Enum(decl) = &stmt.kind {
+            self.enums.push(EnumDef {
+                name: decl.name.name.clone(),
+                members: decl.members.iter().map(|m| m.name.name.clone()).collect(),
+            });
+        }
+        ast::walk_stmt(self, stmt);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        if function.is_constructor
+            && let Some(name) = &function.name
+        {
+            self.constructors.push(ConstructorDef {
+                name: name.name.clone(),
+                parent: function.parent.as_ref().map(|p| p.name.name.clone()),
+                fields: constructor_fields(&function.body),
+            });
+        }
+        ast::walk_function(self, function);
+    }
+}
+
+/// `name = value` / `self.name = value` statements directly in a constructor
+/// body, skipping methods.
+fn constructor_fields(body: &[Stmt]) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for stmt in body {
+        let StmtKind::Expr(Expr {
+            kind: ExprKind::Assign { op, target, value },
+            ..
+        }) = &stmt.kind
+        else {
+            continue;
+        };
+        if op != "=" || matches!(value.kind, ExprKind::Function(_)) {
+            continue;
+        }
+        let name = match &target.kind {
+            ExprKind::Ident(name) => name,
+            ExprKind::Member { object, field } if object.kind == ExprKind::Ident("self".into()) => {
+                &field.name
+            }
+            _ => continue,
+        };
+        if !fields.contains(name) {
+            fields.push(name.clone());
+        }
+    }
+    fields
+}
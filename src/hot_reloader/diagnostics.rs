@@ -0,0 +1,175 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A compiler message from Igor, e.g.
+/// `Error : gml_Object_oPlayer_Step_0(5) : Assignment operator expected`.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Compiled code unit name, e.g. `gml_Script_scr_move`.
+    pub symbol: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub message: String,
+    /// Source file the symbol maps to, when it could be found in the project.
+    pub file: Option<PathBuf>,
+}
+
+impl fmt::Display for Diagnostic {
+    /// `file:line:col: severity: message`, the format editors and terminals
+    /// turn into jump links. Falls back to the raw symbol when unmapped.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = match &self.file {
+            Some(path) => path.display().to_string(),
+            None => self.symbol.clone(),
+        };
+        write!(
+            f,
+            "{location}:{}:{}: {}: {}",
+            self.line,
+            self.column.unwrap_or(1),
+            self.severity,
+            self.message
+        )
+    }
+}
+
+/// Parse one line of Igor output. Returns `None` for anything that is not a
+/// compiler error or warning.
+pub fn parse_line(line: &str, project_dir: &Path) -> Option<Diagnostic> {
+    let line = line.trim();
+    let (severity, rest) = if let Some(rest) = line.strip_prefix("Error") {
+        (Severity::Error, rest)
+    } else if let Some(rest) = line.strip_prefix("Warning") {
+        (Severity::Warning, rest)
+    } else {
+        return None;
+    };
+
+    let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+    if !rest.starts_with("gml_") {
+        return None;
+    }
+
+    let open = rest.find('(')?;
+    let close = open + rest[open..].find(')')?;
+    let symbol = rest[..open].trim();
+    let position = &rest[open + 1..close];
+    let (line_str, column_str) = match position.split_once([':', ',']) {
+        Some((l, c)) => (l.trim(), Some(c.trim())),
+        None => (position.trim(), None),
+    };
+    let line_number: u32 = line_str.parse().ok()?;
+    let column = column_str.and_then(|c| c.parse().ok());
+
+    let message = rest[close + 1..]
+        .trim_start()
+        .trim_start_matches(':')
+        .trim()
+        .to_string();
+
+    Some(Diagnostic {
+        severity,
+        symbol: symbol.to_string(),
+        line: line_number,
+        column,
+        message,
+        file: source_path(project_dir, symbol),
+    })
+}
+
+/// Map a compiled code unit name back to the `.gml` file it came from:
+///
+/// * `gml_Script_foo` / `gml_GlobalScript_foo` -> `scripts/foo/foo.gml`, or the
+///   script that declares `function foo`
+/// * `gml_Object_oPlayer_Step_0` -> `objects/oPlayer/Step_0.gml`
+/// * `gml_RoomCC_rmMain_0_inst_1A2B` -> `rooms/rmMain/InstanceCreationCode_inst_1A2B.gml`
+/// * `gml_Room_rmMain_Create` -> `rooms/rmMain/RoomCreationCode.gml`
+pub fn source_path(project_dir: &Path, symbol: &str) -> Option<PathBuf> {
+    if let Some(name) = symbol
+        .strip_prefix("gml_Script_")
+        .or_else(|| symbol.strip_prefix("gml_GlobalScript_"))
+    {
+        let direct = project_dir
+            .join("scripts")
+            .join(name)
+            .join(format!("{name}.gml"));
+        if direct.exists() {
+            return Some(direct);
+        }
+        return find_function_script(project_dir, name);
+    }
+
+    if let Some(rest) = symbol.strip_prefix("gml_Object_") {
+        // Object names may contain underscores, so try every split point.
+        return split_points(rest).find_map(|(object, event)| {
+            let path = project_dir
+                .join("objects")
+                .join(object)
+                .join(format!("{event}.gml"));
+            path.exists().then_some(path)
+        });
+    }
+
+    if let Some(rest) = symbol.strip_prefix("gml_RoomCC_") {
+        return split_points(rest).find_map(|(room, remainder)| {
+            let (_, instance) = remainder.split_once('_')?;
+            let path = project_dir
+                .join("rooms")
+                .join(room)
+                .join(format!("InstanceCreationCode_{instance}.gml"));
+            path.exists().then_some(path)
+        });
+    }
+
+    if let Some(rest) = symbol.strip_prefix("gml_Room_") {
+        let room = rest.strip_suffix("_Create").unwrap_or(rest);
+        let path = project_dir
+            .join("rooms")
+            .join(room)
+            .join("RoomCreationCode.gml");
+        return path.exists().then_some(path);
+    }
+
+    None
+}
+
+/// Every `(before, after)` split of `s` at an underscore.
+fn split_points(s: &str) -> impl Iterator<Item = (&str, &str)> {
+    s.match_indices('_')
+        .map(move |(i, _)| (&s[..i], &s[i + 1..]))
+}
+
+/// Scan `scripts/*/*.gml` for the file that declares `function <name>`.
+fn find_function_script(project_dir: &Path, name: &str) -> Option<PathBuf> {
+    let needle = format!("function {name}");
+    let scripts = project_dir.join("scripts").read_dir().ok()?;
+    scripts.flatten().find_map(|entry| {
+        let dir = entry.path();
+        let file = dir.join(format!("{}.gml", entry.file_name().to_string_lossy()));
+        let content = fs::read_to_string(&file).ok()?;
+        content
+            .match_indices(&needle)
+            .any(|(i, _)| {
+                let after = content[i + needle.len()..].trim_start();
+                after.starts_with('(')
+            })
+            .then_some(file)
+    })
+}
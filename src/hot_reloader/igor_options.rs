@@ -0,0 +1,206 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::runtime::{GmRuntime, PRODUCT_FOLDERS, TargetPlatform};
+
+const OPTIONS_FILE_NAME: &str = "build.bff";
+const MACROS_FILE_NAME: &str = "macros.json";
+const PREFERENCES_FILE_NAME: &str = "preferences.json";
+const TARGET_OPTIONS_FILE_NAME: &str = "targetoptions.json";
+const DEFAULT_CONFIG: &str = "Default";
+
+/// Inputs for a generated Igor options file.
+pub struct IgorOptionsSpec<'a> {
+    pub yyp_path: &'a Path,
+    /// GameMaker build configuration name (e.g. "Default").
+    pub config: Option<&'a str>,
+    pub target: TargetPlatform,
+    pub runtime: &'a GmRuntime,
+    /// Where Igor writes the compiled game.
+    pub output_dir: &'a Path,
+    /// Igor scratch space; the options file and its companions are written here.
+    pub temp_dir: &'a Path,
+    /// The IDE user folder (`<login>_<id>`) holding the licence. Optional for
+    /// runtimes that do not need it.
+    pub user_dir: Option<&'a Path>,
+}
+
+/// Mirrors the JSON layout of the `build.bff` the IDE writes on Run. Igor reads
+/// every value as a string, booleans included.
+#[derive(Serialize)]
+struct BuildOptions {
+    #[serde(rename = "targetFile")]
+    target_file: String,
+    #[serde(rename = "assetCompiler")]
+    asset_compiler: String,
+    debug: String,
+    compile_output_file_name: String,
+    #[serde(rename = "useShaders")]
+    use_shaders: String,
+    config: String,
+    #[serde(rename = "outputFolder")]
+    output_folder: String,
+    #[serde(rename = "projectName")]
+    project_name: String,
+    macros: String,
+    #[serde(rename = "projectDir")]
+    project_dir: String,
+    preferences: String,
+    #[serde(rename = "projectPath")]
+    project_path: String,
+    #[serde(rename = "tempFolder")]
+    temp_folder: String,
+    #[serde(rename = "tempFolderUnmapped")]
+    temp_folder_unmapped: String,
+    #[serde(rename = "userDir")]
+    user_dir: String,
+    #[serde(rename = "runtimeLocation")]
+    runtime_location: String,
+    #[serde(rename = "targetOptions")]
+    target_options: String,
+    #[serde(rename = "targetMask")]
+    target_mask: String,
+    verbose: String,
+    #[serde(rename = "SteamIDE")]
+    steam_ide: String,
+}
+
+/// Write `build.bff` plus the macros, preferences and target-options files it
+/// references into `spec.temp_dir`. Returns the path of the options file.
+pub fn write_options_file(spec: &IgorOptionsSpec) -> Result<PathBuf, String> {
+    let yyp_path = absolute(spec.yyp_path)?;
+    let project_dir = yyp_path
+        .parent()
+        .ok_or_else(|| "Could not determine project directory from .yyp path".to_string())?;
+    let project_name = yyp_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| "Could not extract project name from .yyp path".to_string())?;
+
+    fs::create_dir_all(spec.temp_dir)
+        .map_err(|e| format!("Failed to create Igor temp directory: {e}"))?;
+    fs::create_dir_all(spec.output_dir)
+        .map_err(|e| format!("Failed to create Igor output directory: {e}"))?;
+
+    let project_dir = project_dir.to_path_buf();
+    let temp_dir = absolute(spec.temp_dir)?;
+    let output_dir = absolute(spec.output_dir)?;
+    let runtime_dir = absolute(&spec.runtime.dir)?;
+    let user_dir = spec.user_dir.map(absolute).transpose()?;
+    let config = spec.config.unwrap_or(DEFAULT_CONFIG);
+
+    let macros_path = temp_dir.join(MACROS_FILE_NAME);
+    let preferences_path = temp_dir.join(PREFERENCES_FILE_NAME);
+    let target_options_path = temp_dir.join(TARGET_OPTIONS_FILE_NAME);
+
+    let macros = serde_json::json!({
+        "project_dir": path_str(&project_dir),
+        "project_name": project_name,
+        "project_full_filename": path_str(&yyp_path),
+        "project_cache_directory_name": "cache",
+        "options_dir": path_str(&project_dir.join("options")),
+        "runtimeLocation": path_str(&runtime_dir),
+        "runtimeBaseLocation": path_str(&runtime_dir),
+        "temp_directory": path_str(&temp_dir),
+        "asset_compiler_cache_directory": path_str(&temp_dir.join("cache")),
+        "user_directory": user_dir.as_deref().map(path_str).unwrap_or_default(),
+        "output_directory": path_str(&output_dir),
+        "current_config": config,
+    });
+    write_json(&macros_path, &macros)?;
+    write_json(&preferences_path, &serde_json::json!({}))?;
+    write_json(
+        &target_options_path,
+        &serde_json::json!({ "runtime": "VM" }),
+    )?;
+
+    let options = BuildOptions {
+        target_file: String::new(),
+        asset_compiler: String::new(),
+        debug: "False".to_string(),
+        compile_output_file_name: path_str(&output_dir.join(format!(
+            "{project_name}.{}",
+            data_file_extension(spec.target)
+        ))),
+        use_shaders: "True".to_string(),
+        config: config.to_string(),
+        output_folder: path_str(&output_dir),
+        project_name: project_name.to_string(),
+        macros: path_str(&macros_path),
+        project_dir: path_str(&project_dir),
+        preferences: path_str(&preferences_path),
+        project_path: path_str(&yyp_path),
+        temp_folder: path_str(&temp_dir),
+        temp_folder_unmapped: path_str(&temp_dir),
+        user_dir: user_dir.as_deref().map(path_str).unwrap_or_default(),
+        runtime_location: path_str(&runtime_dir),
+        target_options: path_str(&target_options_path),
+        target_mask: target_mask(spec.target).to_string(),
+        verbose: "True".to_string(),
+        steam_ide: "False".to_string(),
+    };
+
+    let options_path = temp_dir.join(OPTIONS_FILE_NAME);
+    let options_json = serde_json::to_value(&options)
+        .map_err(|e| format!("Failed to serialize Igor options: {e}"))?;
+    write_json(&options_path, &options_json)?;
+    Ok(options_path)
+}
+
+/// Default scratch directory for a project's generated build files, inside the
+/// gmhelper data dir so nothing is written into the project itself.
+pub fn default_temp_dir(yyp_path: &Path) -> Result<PathBuf, String> {
+    let project_name = yyp_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string());
+    let data_dir = crate::history::data_dir().map_err(|e| e.to_string())?;
+    Ok(data_dir.join("build").join(project_name))
+}
+
+/// Find the IDE user folder (e.g. `%AppData%/GameMakerStudio2/jdoe_123456`).
+/// It is the only subfolder holding a `local_settings.json`.
+pub fn find_user_dir() -> Option<PathBuf> {
+    let base = dirs::config_dir()?;
+    PRODUCT_FOLDERS.iter().find_map(|product| {
+        base.join(product)
+            .read_dir()
+            .ok()?
+            .flatten()
+            .map(|entry| entry.path())
+            .find(|path| path.join("local_settings.json").exists())
+    })
+}
+
+/// Data file Igor produces for a VM build on each target.
+fn data_file_extension(target: TargetPlatform) -> &'static str {
+    match target {
+        TargetPlatform::Windows => "win",
+        TargetPlatform::Linux => "unx",
+        TargetPlatform::Mac => "ios",
+    }
+}
+
+/// Platform bit the IDE puts in `targetMask`.
+fn target_mask(target: TargetPlatform) -> u32 {
+    match target {
+        TargetPlatform::Windows => 64,
+        TargetPlatform::Mac => 2,
+        TargetPlatform::Linux => 128,
+    }
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, String> {
+    std::path::absolute(path).map_err(|e| format!("Failed to resolve {}: {e}", path.display()))
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn write_json(path: &Path, value: &serde_json::Value) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {e}", path.display()))?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
@@ -0,0 +1,608 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::gml::ast::{self, DeclareKind, Expr, ExprKind, Function, Stmt, StmtKind, Visitor};
+use crate::gml::{self, LineIndex};
+use crate::project_config::LiveConfig;
+use crate::sprites::gm_import;
+use crate::undo_log::RewriteKind;
+use crate::{code_editor, gml_project, self_writes};
+
+/// Trailing comment that marks a `#macro` as live-tweakable.
+const LIVE_MARKER: &str = "//:live";
+const LIVE_FUNCTION: &str = "gmhelper_live";
+const COMPANION_SCRIPT: &str = "__gmhelper_live";
+/// Read by the companion script from the game's save area (file sandbox).
+const LIVE_FILE_NAME: &str = "gmhelper_live.json";
+
+const COMPANION_GML: &str = r#"// Generated by gmhelper for live value tweaking. Do not edit: it is rewritten
+// whenever `gmhelper reload --live` starts.
+//
+// `#macro NAME value //:live` lines are rewritten to call gmhelper_live(), so
+// edits to those values are pushed to the running game without a rebuild.
+
+#macro GMHELPER_LIVE_FILE "gmhelper_live.json"
+#macro GMHELPER_LIVE_POLL_FRAMES 15
+
+global.__gmhelper_live_values = {};
+global.__gmhelper_live_version = -1;
+
+/// @function gmhelper_live(name, value)
+/// @param {String} name   Macro name the value belongs to
+/// @param {Any} value     Value compiled into the build
+/// @returns {Any} The latest pushed value for `name`, or `value` if none
+function gmhelper_live(_name, _value) {
+    var _live = global.__gmhelper_live_values;
+    return variable_struct_exists(_live, _name) ? _live[$ _name] : _value;
+}
+
+function __gmhelper_live_poll() {
+    if (!file_exists(GMHELPER_LIVE_FILE)) return;
+    var _buffer = buffer_load(GMHELPER_LIVE_FILE);
+    if (_buffer == -1) return;
+    var _text = buffer_read(_buffer, buffer_string);
+    buffer_delete(_buffer);
+
+    var _data;
+    try {
+        _data = json_parse(_text);
+    } catch (_e) {
+        return;
+    }
+    if (!is_struct(_data) || _data.version == global.__gmhelper_live_version) return;
+
+    global.__gmhelper_live_version = _data.version;
+    global.__gmhelper_live_values = _data.values;
+    show_debug_message("[gmhelper] live values updated");
+}
+
+time_source_start(time_source_create(time_source_global, GMHELPER_LIVE_POLL_FRAMES, time_source_units_frames, __gmhelper_live_poll, [], -1));
+"#;
+
+/// Pushes edits to `//:live` macros into the running game through a JSON file
+/// the companion script polls, so those edits skip the Igor rebuild.
+pub struct LiveChannel {
+    live_file: PathBuf,
+    version: u64,
+    values: BTreeMap<String, serde_json::Value>,
+    /// Each file's content with live values blanked out, as of the last build.
+    /// A change that leaves this untouched only changed live values.
+    skeletons: HashMap<PathBuf, String>,
+    /// Where each file needs names to be constants, and what its macros use.
+    uses: HashMap<PathBuf, ConstantUses>,
+    /// Names GML needs as compile-time constants, with where they are used.
+    /// Live macros among them keep their plain form, as a function call
+    /// can't stand in a `case` label or an enum value.
+    constants: BTreeMap<String, String>,
+    /// Constant live macros already reported, so each is reported once.
+    reported: HashSet<String>,
+}
+
+/// A `#macro NAME value //:live` line, in either its hand-written or rewritten form.
+struct LiveMacro {
+    name: String,
+    /// The literal exactly as written in the source.
+    literal: String,
+    value: serde_json::Value,
+    /// Already in the `gmhelper_live("NAME", value)` form.
+    rewritten: bool,
+    /// Used where GML needs a constant, so it stays a plain macro.
+    constant: bool,
+}
+
+impl LiveChannel {
+    /// Install the companion script, rewrite every `//:live` macro in the
+    /// project to read through it, and publish the current values.
+    pub fn start(yyp_path: &Path, project_dir: &Path, config: &LiveConfig) -> Result<Self, String> {
+        let project_name = yyp_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| "Could not extract project name from .yyp path".to_string())?;
+        let save_dir = match &config.save_dir {
+            Some(dir) => dir.clone(),
+            None => default_save_dir(project_name)?,
+        };
+
+        if install_companion_script(yyp_path, project_dir, project_name)? {
+            println!("Installed {COMPANION_SCRIPT} script; live values apply after the next build");
+        }
+
+        let version = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut channel = Self {
+            live_file: save_dir.join(LIVE_FILE_NAME),
+            version,
+            values: BTreeMap::new(),
+            skeletons: HashMap::new(),
+            uses: HashMap::new(),
+            constants: BTreeMap::new(),
+            reported: HashSet::new(),
+        };
+
+        let mut sources = Vec::new();
+        for file in gml_project::collect_gml_files(project_dir) {
+            let Ok(content) = fs::read_to_string(&file) else {
+                continue;
+            };
+            channel
+                .uses
+                .insert(file.clone(), ConstantUses::of(&file, &content));
+            sources.push((file, content));
+        }
+        channel.constants = constant_names(&channel.uses);
+
+        for (file, content) in sources {
+            let content = channel.sync_file(&file, &content)?;
+            let (skeleton, macros) = channel.scan(&content);
+            if macros.is_empty() {
+                continue;
+            }
+            for m in macros.into_iter().filter(|m| !m.constant) {
+                channel.values.insert(m.name, m.value);
+            }
+            channel.skeletons.insert(file, skeleton);
+        }
+
+        channel.publish()?;
+        println!(
+            "Live tweaking: {} value{} via {}",
+            channel.values.len(),
+            if channel.values.len() == 1 { "" } else { "s" },
+            channel.live_file.display()
+        );
+        Ok(channel)
+    }
+
+    /// Handle a change to `file`. Returns `true` when only live values changed
+    /// and they were pushed to the game, meaning no rebuild is needed.
+    pub fn try_apply(&mut self, file: &Path) -> bool {
+        let Ok(content) = fs::read_to_string(file) else {
+            return false;
+        };
+        let (skeleton, _) = self.scan(&content);
+        let unchanged_code = self.skeletons.get(file) == Some(&skeleton);
+
+        self.uses
+            .insert(file.to_path_buf(), ConstantUses::of(file, &content));
+        let constants = constant_names(&self.uses);
+        if constants != self.constants {
+            self.constants = constants;
+            self.reported
+                .retain(|name| self.constants.contains_key(name));
+            // Macros elsewhere may have to switch form too.
+            let others: Vec<PathBuf> = self
+                .skeletons
+                .keys()
+                .filter(|other| other.as_path() != file)
+                .cloned()
+                .collect();
+            for other in others {
+                self.resync(&other);
+            }
+        }
+
+        let macros = self.resync_content(file, &content);
+
+        let mut changed = Vec::new();
+        for m in macros.into_iter().filter(|m| !m.constant) {
+            if self.values.get(&m.name) != Some(&m.value) {
+                changed.push(format!("{} = {}", m.name, m.literal));
+                self.values.insert(m.name, m.value);
+            }
+        }
+
+        if !unchanged_code || changed.is_empty() {
+            return false;
+        }
+
+        match self.publish() {
+            Ok(()) => {
+                println!("Live: {}", changed.join(", "));
+                true
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                false
+            }
+        }
+    }
+
+    /// Re-read `file` and bring its live macros in line with `constants`.
+    fn resync(&mut self, file: &Path) {
+        if let Ok(content) = fs::read_to_string(file) {
+            self.resync_content(file, &content);
+        }
+    }
+
+    /// Bring the live macros in `content` in line with `constants` and
+    /// remember the result's skeleton. Returns the live macros.
+    fn resync_content(&mut self, file: &Path, content: &str) -> Vec<LiveMacro> {
+        let content = match self.sync_file(file, content) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                content.to_string()
+            }
+        };
+        let (skeleton, macros) = self.scan(&content);
+        if macros.is_empty() {
+            self.skeletons.remove(file);
+        } else {
+            self.skeletons.insert(file.to_path_buf(), skeleton);
+        }
+        macros
+    }
+
+    /// Split `content` into its skeleton (live literals replaced by a
+    /// placeholder) and the live macros it defines. Constant live macros
+    /// are left in the skeleton, so editing them still rebuilds.
+    fn scan(&mut self, content: &str) -> (String, Vec<LiveMacro>) {
+        let mut skeleton = String::with_capacity(content.len());
+        let mut macros = Vec::new();
+        for line in content.split_inclusive('\n') {
+            match self.parse_live_macro(line) {
+                Some(m) => {
+                    if m.constant {
+                        skeleton.push_str(line);
+                    } else {
+                        skeleton.push_str(&format!("#macro {} <live>\n", m.name));
+                    }
+                    macros.push(m);
+                }
+                None => skeleton.push_str(line),
+            }
+        }
+        (skeleton, macros)
+    }
+
+    /// Rewrite hand-written `#macro NAME value //:live` lines into the
+    /// `gmhelper_live("NAME", value)` form the companion script serves, and
+    /// constant ones back to their plain form. Returns the new content.
+    fn sync_file(&mut self, file: &Path, content: &str) -> Result<String, String> {
+        let mut output = String::with_capacity(content.len() + 64);
+        let mut changed = false;
+        for line in content.split_inclusive('\n') {
+            let Some(m) = self.parse_live_macro(line) else {
+                output.push_str(line);
+                continue;
+            };
+            if m.rewritten != m.constant {
+                output.push_str(line);
+                continue;
+            }
+            let body = line.trim_end_matches(['\r', '\n']);
+            let indent = &body[..body.len() - body.trim_start().len()];
+            let ending = &line[body.len()..];
+            let value = if m.constant {
+                m.literal
+            } else {
+                format!("{LIVE_FUNCTION}(\"{}\", {})", m.name, m.literal)
+            };
+            output.push_str(&format!(
+                "{indent}#macro {} {value} {LIVE_MARKER}{ending}",
+                m.name
+            ));
+            changed = true;
+        }
+        if !changed {
+            return Ok(output);
+        }
+
+        code_editor::rewrite_source(file, output.as_bytes(), RewriteKind::LiveMacros)
+            .map_err(|e| format!("Failed to rewrite live macros in {}: {e}", file.display()))?;
+        println!("Prepared live macros in {}", file.display());
+        Ok(output)
+    }
+
+    /// `line` as a live macro, reporting it the first time it turns out to
+    /// be a constant.
+    fn parse_live_macro(&mut self, line: &str) -> Option<LiveMacro> {
+        let mut m = parse_live_macro(line)?;
+        if let Some(used) = self.constants.get(&m.name) {
+            m.constant = true;
+            if self.reported.insert(m.name.clone()) {
+                println!(
+                    "Live: {} is used {used}, where GML needs a constant; it stays a plain macro",
+                    m.name
+                );
+            }
+        }
+        Some(m)
+    }
+
+    fn publish(&mut self) -> Result<(), String> {
+        self.version += 1;
+        let data = serde_json::json!({
+            "version": self.version,
+            "values": self.values,
+        });
+        if let Some(dir) = self.live_file.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&data)
+            .map_err(|e| format!("Failed to serialize live values: {e}"))?;
+        fs::write(&self.live_file, json)
+            .map_err(|e| format!("Failed to write {}: {e}", self.live_file.display()))
+    }
+}
+
+fn parse_live_macro(line: &str) -> Option<LiveMacro> {
+    let marker = line.find(LIVE_MARKER)?;
+    if !line[marker + LIVE_MARKER.len()..].trim().is_empty() {
+        return None;
+    }
+
+    let code = line[..marker].trim();
+    let rest = code.strip_prefix("#macro")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let name_end = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    let name = &rest[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let body = rest[name_end..].trim();
+
+    let prefix = format!("{LIVE_FUNCTION}(\"{name}\",");
+    let (literal, rewritten) = match body.strip_prefix(&prefix).and_then(|b| b.strip_suffix(')')) {
+        Some(inner) => (inner.trim(), true),
+        None => (body, false),
+    };
+
+    Some(LiveMacro {
+        name: name.to_string(),
+        literal: literal.to_string(),
+        value: literal_to_json(literal)?,
+        rewritten,
+        constant: false,
+    })
+}
+
+/// Where one file puts names in a spot GML needs a compile-time constant,
+/// and the names each of its macros expands to.
+#[derive(Default)]
+struct ConstantUses {
+    /// (name, where it's used)
+    uses: Vec<(String, String)>,
+    macros: Vec<(String, Vec<String>)>,
+}
+
+impl ConstantUses {
+    fn of(file: &Path, source: &str) -> Self {
+        let mut collector = ConstantCollector {
+            file,
+            lines: LineIndex::new(source),
+            context: None,
+            found: Self::default(),
+        };
+        ast::walk_stmts(&mut collector, &gml::parse(source).statements);
+        collector.found
+    }
+}
+
+/// Every name needed as a constant across the project: used directly in a
+/// constant context, or expanded from a macro that is.
+fn constant_names(uses: &HashMap<PathBuf, ConstantUses>) -> BTreeMap<String, String> {
+    let mut constants: BTreeMap<String, String> = BTreeMap::new();
+    for (name, used) in uses.values().flat_map(|file| &file.uses) {
+        constants
+            .entry(name.clone())
+            .or_insert_with(|| used.clone());
+    }
+    let macros: Vec<&(String, Vec<String>)> = uses.values().flat_map(|file| &file.macros).collect();
+    loop {
+        let mut added = false;
+        for (name, expands_to) in &macros {
+            let Some(used) = constants.get(name).cloned() else {
+                continue;
+            };
+            for inner in expands_to {
+                if !constants.contains_key(inner) {
+                    constants.insert(inner.clone(), format!("through {name} {used}"));
+                    added = true;
+                }
+            }
+        }
+        if !added {
+            return constants;
+        }
+    }
+}
+
+struct ConstantCollector<'a> {
+    file: &'a Path,
+    lines: LineIndex,
+    /// Set while inside an expression GML evaluates at compile time.
+    context: Option<&'static str>,
+    found: ConstantUses,
+}
+
+impl ConstantCollector<'_> {
+    fn visit_constant(&mut self, context: &'static str, expr: &Expr) {
+        self.context = Some(context);
+        self.visit_expr(expr);
+        self.context = None;
+    }
+}
+
+impl Visitor for ConstantCollector<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Switch { subject, cases } => {
+                self.visit_expr(subject);
+                for case in cases {
+                    if let Some(value) = &case.value {
+                        self.visit_constant("in a `case` label", value);
+                    }
+                    ast::walk_stmts(self, &case.body);
+                }
+            }
+            StmtKind::Enum(decl) => {
+                for value in decl.members.iter().filter_map(|m| m.value.as_ref()) {
+                    self.visit_constant("as an enum value", value);
+                }
+            }
+            StmtKind::Declare {
+                kind: DeclareKind::Static,
+                vars,
+            } => {
+                for init in vars.iter().filter_map(|v| v.init.as_ref()) {
+                    self.visit_constant("in a `static` initializer", init);
+                }
+            }
+            StmtKind::Macro(decl) => {
+                let names = decl
+                    .value
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .filter(|word| word.starts_with(|c: char| c.is_alphabetic() || c == '_'))
+                    .map(str::to_string)
+                    .collect();
+                self.found.macros.push((decl.name.name.clone(), names));
+            }
+            _ => ast::walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let (Some(context), ExprKind::Ident(name)) = (self.context, &expr.kind) {
+            let (line, _) = self.lines.line_col(expr.span.start);
+            self.found.uses.push((
+                name.clone(),
+                format!("{context} at {}:{line}", self.file.display()),
+            ));
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        for default in function.params.iter().filter_map(|p| p.default.as_ref()) {
+            self.visit_constant("as a default parameter value", default);
+        }
+        if let Some(parent) = &function.parent {
+            for arg in &parent.args {
+                self.visit_expr(arg);
+            }
+        }
+        // A nested function's body isn't part of the constant.
+        let context = self.context.take();
+        ast::walk_stmts(self, &function.body);
+        self.context = context;
+    }
+}
+
+/// Convert a GML number, string or boolean literal into JSON.
+fn literal_to_json(literal: &str) -> Option<serde_json::Value> {
+    match literal {
+        "true" => return Some(serde_json::Value::Bool(true)),
+        "false" => return Some(serde_json::Value::Bool(false)),
+        _ => {}
+    }
+    if literal.starts_with('"') {
+        return serde_json::from_str::<String>(literal)
+            .ok()
+            .map(serde_json::Value::String);
+    }
+    let (negative, digits) = match literal.strip_prefix('-') {
+        Some(d) => (true, d.trim_start()),
+        None => (false, literal),
+    };
+    let sign = if negative { -1 } else { 1 };
+    if let Some(hex) = digits
+        .strip_prefix('$')
+        .or_else(|| digits.strip_prefix("0x"))
+    {
+        return Some((sign * i64::from_str_radix(hex, 16).ok()?).into());
+    }
+    if let Ok(integer) = digits.parse::<i64>() {
+        return Some((sign * integer).into());
+    }
+    let number = digits.parse::<f64>().ok()?;
+    serde_json::Number::from_f64(sign as f64 * number).map(serde_json::Value::Number)
+}
+
+/// Where the game's file sandbox reads from: its save area, named after the
+/// project by default.
+fn default_save_dir(project_name: &str) -> Result<PathBuf, String> {
+    let base = if cfg!(target_os = "windows") {
+        dirs::data_local_dir()
+    } else if cfg!(target_os = "macos") {
+        dirs::data_dir()
+    } else {
+        dirs::config_dir()
+    };
+    base.map(|b| b.join(project_name)).ok_or_else(|| {
+        "Could not determine the game's save directory; set \"live.saveDir\" in gmhelper.json"
+            .to_string()
+    })
+}
+
+/// Write the companion script resource and register it in the `.yyp`.
+/// Returns `true` if anything had to be added or updated.
+fn install_companion_script(
+    yyp_path: &Path,
+    project_dir: &Path,
+    project_name: &str,
+) -> Result<bool, String> {
+    let script_dir = project_dir.join("scripts").join(COMPANION_SCRIPT);
+    fs::create_dir_all(&script_dir)
+        .map_err(|e| format!("Failed to create {}: {e}", script_dir.display()))?;
+
+    let yy = serde_json::json!({
+        "$GMScript": "v1",
+        "%Name": COMPANION_SCRIPT,
+        "isCompatibility": false,
+        "isDnD": false,
+        "name": COMPANION_SCRIPT,
+        "parent": { "name": project_name, "path": format!("{project_name}.yyp") },
+        "resourceType": "GMScript",
+        "resourceVersion": "2.0",
+    });
+    let yy_json = serde_json::to_string_pretty(&yy)
+        .map_err(|e| format!("Failed to serialize script .yy: {e}"))?;
+
+    let mut changed = write_if_different(
+        &script_dir.join(format!("{COMPANION_SCRIPT}.gml")),
+        COMPANION_GML,
+    )?;
+    changed |= write_if_different(&script_dir.join(format!("{COMPANION_SCRIPT}.yy")), &yy_json)?;
+
+    let mut project = gm_import::read_project_value(yyp_path)?;
+    let registered = project
+        .get("resources")
+        .and_then(|r| r.as_array())
+        .is_some_and(|resources| {
+            resources.iter().any(|entry| {
+                entry
+                    .get("id")
+                    .and_then(|id| id.get("name"))
+                    .and_then(|n| n.as_str())
+                    == Some(COMPANION_SCRIPT)
+            })
+        });
+    if !registered {
+        let resource_path = format!("scripts/{COMPANION_SCRIPT}/{COMPANION_SCRIPT}.yy");
+        gm_import::upsert_resource(&mut project, COMPANION_SCRIPT, &resource_path)?;
+        gm_import::write_project_value(yyp_path, &project)?;
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+fn write_if_different(path: &Path, contents: &str) -> Result<bool, String> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+    self_writes::record(path, contents.as_bytes());
+    fs::write(path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(true)
+}
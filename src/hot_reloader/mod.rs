@@ -0,0 +1,318 @@
+mod diagnostics;
+pub mod igor_options;
+mod live;
+mod output;
+mod platform;
+pub mod runtime;
+mod supervisor;
+mod watch_rules;
+
+use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
+
+use crate::project_config::{self, BusyPolicy, ReloadConfig};
+use crate::{code_editor, self_writes};
+use live::LiveChannel;
+use output::OutputFilter;
+use runtime::TargetPlatform;
+use supervisor::BuildSupervisor;
+use watch_rules::WatchRules;
+
+const IGOR_JOBS_ARG: &str = "-j=8";
+const DEBOUNCE: Duration = Duration::from_secs(1);
+/// How often watch loops wake up to poll the build when no events arrive.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Command-line overrides for the values in the project's `reload` config.
+#[derive(Debug, Default)]
+pub struct ReloadOverrides {
+    pub target: Option<String>,
+    pub runtime: Option<String>,
+    pub build_command: Option<String>,
+    /// Added to the config's `output.include` patterns.
+    pub include: Vec<String>,
+    /// Added to the config's `output.exclude` patterns.
+    pub exclude: Vec<String>,
+    /// Enables live tweaking even if the config leaves it off.
+    pub live: bool,
+}
+
+/// How a reload builds and launches the game.
+#[derive(Debug)]
+enum BuildCommand {
+    /// Igor from an installed runtime, driven by an options (`.bff`) file.
+    Igor {
+        igor: PathBuf,
+        options_file: PathBuf,
+    },
+    /// User-supplied shell command with placeholders already substituted.
+    Custom(String),
+}
+
+/// Everything resolved up front so the watch loop never touches config again.
+#[derive(Debug)]
+struct ReloadPlan {
+    yyp_path: PathBuf,
+    project_dir: PathBuf,
+    target: TargetPlatform,
+    /// Runner process to kill by name before relaunching, if known.
+    runner_name: Option<String>,
+    command: BuildCommand,
+    filter: Arc<OutputFilter>,
+    busy_policy: BusyPolicy,
+    watch_rules: WatchRules,
+}
+
+pub fn run_reload(yyp_path: PathBuf, overrides: ReloadOverrides) {
+    let mut reloader = Reloader::new(&yyp_path, overrides).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    });
+    reloader.print_summary();
+    println!("Press Ctrl+C to stop...\n");
+
+    let (tx, rx) = mpsc::channel();
+
+    let mut watcher =
+        RecommendedWatcher::new(tx, Config::default()).expect("Failed to create file watcher");
+
+    watcher
+        .watch(reloader.project_dir(), RecursiveMode::Recursive)
+        .expect("Failed to watch project directory");
+
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(Ok(event)) => {
+                if is_change_event(&event.kind) {
+                    for path in &event.paths {
+                        reloader.handle_change(path);
+                    }
+                }
+            }
+            Ok(Err(e)) => eprintln!("Watch error: {e}"),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        reloader.tick();
+    }
+}
+
+/// Watcher events that can mean a file's content changed.
+pub fn is_change_event(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
+    )
+}
+
+/// The reload pipeline without its file watcher: fed changed paths, it expands
+/// code commands, pushes live values and debounces rebuilds into the build
+/// supervisor. `reload` and `dev` each drive one from their own watch loop.
+pub struct Reloader {
+    plan: ReloadPlan,
+    supervisor: BuildSupervisor,
+    live: Option<LiveChannel>,
+    changed_resources: BTreeSet<String>,
+    last_change: Option<Instant>,
+}
+
+impl Reloader {
+    pub fn new(yyp_path: &Path, overrides: ReloadOverrides) -> Result<Self, String> {
+        if !yyp_path.exists() {
+            return Err(format!(
+                "Project file '{}' does not exist",
+                yyp_path.display()
+            ));
+        }
+        if yyp_path.extension().and_then(|e| e.to_str()) != Some("yyp") {
+            return Err(format!(
+                "'{}' is not a .yyp file. Provide a valid GameMaker project file.",
+                yyp_path.display()
+            ));
+        }
+
+        // Watcher events carry absolute paths, so resolve the project folder too.
+        let project_dir = std::path::absolute(yyp_path)
+            .ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .ok_or_else(|| "Could not determine project directory from .yyp path".to_string())?;
+
+        let config = project_config::load_for_project(yyp_path)?;
+        let live_config =
+            (overrides.live || config.reload.live.enabled).then(|| config.reload.live.clone());
+
+        let plan = resolve_plan(yyp_path, &project_dir, config.reload, overrides)?;
+        let live = live_config
+            .map(|live_config| LiveChannel::start(yyp_path, &project_dir, &live_config))
+            .transpose()?;
+
+        Ok(Self {
+            supervisor: BuildSupervisor::new(plan.busy_policy),
+            plan,
+            live,
+            changed_resources: BTreeSet::new(),
+            last_change: None,
+        })
+    }
+
+    pub fn project_dir(&self) -> &Path {
+        &self.plan.project_dir
+    }
+
+    pub fn print_summary(&self) {
+        println!("Hot-reloading project: {}", self.plan.yyp_path.display());
+        match &self.plan.command {
+            BuildCommand::Igor { igor, options_file } => {
+                println!("Target: {}", self.plan.target);
+                println!("Igor: {}", igor.display());
+                println!("Options: {}", options_file.display());
+            }
+            BuildCommand::Custom(command_line) => println!("Build command: {command_line}"),
+        }
+        println!(
+            "Watching {} in: {}",
+            self.plan.watch_rules.resources().join(", "),
+            self.plan.project_dir.display()
+        );
+    }
+
+    /// React to a watcher event for `path`.
+    pub fn handle_change(&mut self, path: &Path) {
+        // Our own rewrites (command expansion, sprite import) would otherwise
+        // trigger endless reloads.
+        if self_writes::is_own_write(path) {
+            return;
+        }
+        let Some(resource) = self.plan.watch_rules.classify(&self.plan.project_dir, path) else {
+            return;
+        };
+        if path.extension().and_then(|e| e.to_str()) == Some("gml") && path.exists() {
+            code_editor::process_gml_file_change(path);
+            // Only `//:live` values changed: already pushed.
+            if let Some(live) = &mut self.live
+                && live.try_apply(path)
+            {
+                return;
+            }
+        }
+        self.mark_changed(resource);
+    }
+
+    /// Schedule a rebuild for a change gmhelper made itself, such as a sprite
+    /// import, which [Reloader::handle_change] deliberately ignores.
+    pub fn mark_changed(&mut self, resource: impl Into<String>) {
+        self.changed_resources.insert(resource.into());
+        self.last_change = Some(Instant::now());
+    }
+
+    /// Called every loop tick: report build progress and start a rebuild once
+    /// changes have settled for [DEBOUNCE].
+    pub fn tick(&mut self) {
+        self.supervisor.poll(&self.plan);
+
+        if !self.changed_resources.is_empty()
+            && let Some(t) = self.last_change
+            && t.elapsed() >= DEBOUNCE
+        {
+            let changed: Vec<String> = std::mem::take(&mut self.changed_resources)
+                .into_iter()
+                .collect();
+            self.last_change = None;
+            println!("Detected changes in {}, reloading...", changed.join(", "));
+            self.supervisor.request_rebuild(&self.plan);
+        }
+    }
+}
+
+/// Combine the project config with CLI overrides and locate the build tools.
+fn resolve_plan(
+    yyp_path: &Path,
+    project_dir: &Path,
+    config: ReloadConfig,
+    overrides: ReloadOverrides,
+) -> Result<ReloadPlan, String> {
+    let target = match overrides.target.or(config.target) {
+        Some(name) => TargetPlatform::parse(&name)?,
+        None => TargetPlatform::host(),
+    };
+
+    let mut include = config.output.include;
+    include.extend(overrides.include);
+    let mut exclude = config.output.exclude;
+    exclude.extend(overrides.exclude);
+    let filter = Arc::new(OutputFilter {
+        include,
+        exclude,
+        timestamps: config.output.timestamps,
+    });
+    let watch_rules = WatchRules::new(config.watch);
+
+    if let Some(template) = overrides.build_command.or(config.build_command) {
+        let command_line = template
+            .replace("{project}", &yyp_path.to_string_lossy())
+            .replace("{project_dir}", &project_dir.to_string_lossy())
+            .replace("{target}", target.igor_name());
+        return Ok(ReloadPlan {
+            yyp_path: yyp_path.to_path_buf(),
+            project_dir: project_dir.to_path_buf(),
+            target,
+            runner_name: config.runner_name,
+            command: BuildCommand::Custom(command_line),
+            filter,
+            busy_policy: config.on_change_during_build,
+            watch_rules,
+        });
+    }
+
+    let runtimes = runtime::discover_runtimes(config.runtimes_dir.as_deref());
+    let selected =
+        runtime::select_runtime(&runtimes, overrides.runtime.or(config.runtime).as_deref())?;
+    let igor = selected.igor_path();
+    if !igor.exists() {
+        return Err(format!(
+            "Runtime {} has no Igor binary at {}",
+            selected.version,
+            igor.display()
+        ));
+    }
+
+    let options_file = match config.options_file {
+        Some(path) => path,
+        None => {
+            let temp_dir = match config.temp_dir {
+                Some(dir) => dir,
+                None => igor_options::default_temp_dir(yyp_path)?,
+            };
+            let output_dir = config.output_dir.unwrap_or_else(|| temp_dir.join("output"));
+            let user_dir = config.user_dir.or_else(igor_options::find_user_dir);
+            igor_options::write_options_file(&igor_options::IgorOptionsSpec {
+                yyp_path,
+                config: config.config.as_deref(),
+                target,
+                runtime: &selected,
+                output_dir: &output_dir,
+                temp_dir: &temp_dir,
+                user_dir: user_dir.as_deref(),
+            })?
+        }
+    };
+
+    Ok(ReloadPlan {
+        yyp_path: yyp_path.to_path_buf(),
+        project_dir: project_dir.to_path_buf(),
+        target,
+        runner_name: Some(
+            config
+                .runner_name
+                .unwrap_or_else(|| target.runner_name().to_string()),
+        ),
+        command: BuildCommand::Igor { igor, options_file },
+        filter,
+        busy_policy: config.on_change_during_build,
+        watch_rules,
+    })
+}
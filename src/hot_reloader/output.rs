@@ -0,0 +1,267 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use super::diagnostics::{self, Severity};
+
+/// Igor output lines after which everything printed comes from the game runner.
+const RUN_PHASE_MARKERS: &[&str] = &["Igor complete.", "[Run]", "Entering main loop."];
+/// Minimum run of `#` characters GameMaker frames runtime errors with.
+const ERROR_DELIMITER_LEN: usize = 20;
+
+/// Include/exclude rules for game output. Runtime errors are always shown.
+#[derive(Debug, Clone, Default)]
+pub struct OutputFilter {
+    /// When non-empty, only lines containing one of these substrings are shown.
+    pub include: Vec<String>,
+    /// Lines containing any of these substrings are dropped.
+    pub exclude: Vec<String>,
+    pub timestamps: bool,
+}
+
+impl OutputFilter {
+    pub fn allows(&self, line: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| line.contains(p));
+        included && !self.exclude.iter().any(|p| line.contains(p))
+    }
+}
+
+/// What the output readers have seen so far, shared with the build supervisor.
+#[derive(Debug, Clone, Default)]
+pub struct BuildProgress {
+    pub errors: Arc<AtomicUsize>,
+    pub warnings: Arc<AtomicUsize>,
+    /// Set once a run-phase marker shows the build is done and the game started.
+    pub run_started: Arc<AtomicBool>,
+}
+
+/// Per-stream settings handed to a reader thread.
+pub struct OutputContext {
+    pub project_dir: PathBuf,
+    pub filter: Arc<OutputFilter>,
+    pub progress: BuildProgress,
+    pub started: Instant,
+    /// `false` while Igor is still compiling; build chatter is hidden until a
+    /// run-phase marker shows up. Custom build commands show everything.
+    pub show_output: bool,
+}
+
+/// Read a build/run process stream line by line on a background thread.
+/// Compiler messages print as `file:line:col: message` until the game starts;
+/// from then on its output is filtered, timestamped and scanned for runtime
+/// errors.
+pub fn spawn_output_reader(stream: impl Read + Send + 'static, mut ctx: OutputContext) {
+    std::thread::spawn(move || {
+        let mut runtime_error = RuntimeErrorCollector::default();
+
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            // Only Igor's compile phase prints compiler messages; once the game
+            // runs, its own output may look like one without being one.
+            let building = !ctx.progress.run_started.load(Ordering::SeqCst);
+            if building && let Some(diagnostic) = diagnostics::parse_line(&line, &ctx.project_dir) {
+                let counter = match diagnostic.severity {
+                    Severity::Error => &ctx.progress.errors,
+                    Severity::Warning => &ctx.progress.warnings,
+                };
+                counter.fetch_add(1, Ordering::SeqCst);
+                eprintln!("{diagnostic}");
+                continue;
+            }
+
+            if RUN_PHASE_MARKERS.iter().any(|m| line.contains(m)) {
+                ctx.progress.run_started.store(true, Ordering::SeqCst);
+                if !ctx.show_output {
+                    ctx.show_output = true;
+                    continue;
+                }
+            }
+            if !ctx.show_output {
+                continue;
+            }
+
+            let mut collected = runtime_error.push(&line);
+            if let Collected::Finished(block) = collected {
+                print_runtime_error(&block, &ctx);
+                collected = runtime_error.push(&line);
+            }
+            if let Collected::NotInBlock = collected {
+                print_game_line(&line, &ctx);
+            }
+        }
+
+        if let Some(block) = runtime_error.finish() {
+            print_runtime_error(&block, &ctx);
+        }
+    });
+}
+
+fn print_game_line(line: &str, ctx: &OutputContext) {
+    // The runner prints a row of underscores ahead of each error block.
+    let trimmed = line.trim();
+    if trimmed.len() >= ERROR_DELIMITER_LEN && trimmed.chars().all(|c| c == '_') {
+        return;
+    }
+    if ctx.filter.allows(line) {
+        println!("{}{line}", timestamp_prefix(ctx));
+    }
+}
+
+fn timestamp_prefix(ctx: &OutputContext) -> String {
+    if !ctx.filter.timestamps {
+        return String::new();
+    }
+    let elapsed = ctx.started.elapsed();
+    let secs = elapsed.as_secs();
+    format!(
+        "[{:02}:{:02}.{:03}] ",
+        secs / 60,
+        secs % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+fn print_runtime_error(block: &RuntimeErrorBlock, ctx: &OutputContext) {
+    eprintln!("{}Runtime error:", timestamp_prefix(ctx));
+    for line in &block.message {
+        eprintln!("    {line}");
+    }
+    for frame in &block.callstack {
+        match diagnostics::source_path(&ctx.project_dir, &frame.symbol) {
+            Some(path) => eprintln!("    {}:{}:1: {}", path.display(), frame.line, frame.symbol),
+            None => eprintln!("    {} (line {})", frame.symbol, frame.line),
+        }
+    }
+}
+
+/// One `gml_... (line N)` entry of a runtime error callstack.
+#[derive(Debug, Clone)]
+struct StackFrame {
+    symbol: String,
+    line: i64,
+}
+
+#[derive(Debug, Default)]
+struct RuntimeErrorBlock {
+    message: Vec<String>,
+    callstack: Vec<StackFrame>,
+}
+
+enum Collected {
+    /// The line belonged to the current error block.
+    Consumed,
+    /// The line ended a block; it still needs to be pushed again.
+    Finished(RuntimeErrorBlock),
+    NotInBlock,
+}
+
+/// Accumulates a GameMaker runtime error, which looks like:
+///
+/// ```text
+/// ############################################################################################
+/// ERROR in action number 1
+/// of  Step Event0
+/// for object oPlayer:
+///
+/// Variable oPlayer.foo(100005, -2147483648) not set before reading it.
+///  at gml_Object_oPlayer_Step_0 (line 3) -     x += foo;
+/// ############################################################################################
+/// gml_Object_oPlayer_Step_0 (line 3)
+/// gml_Script_scr_move (line 10)
+/// ```
+#[derive(Default)]
+struct RuntimeErrorCollector {
+    block: Option<RuntimeErrorBlock>,
+    /// Between the two delimiter lines; after the closing one only
+    /// callstack frames belong to the block.
+    in_body: bool,
+    /// The callstack so far is just the top frame quoted in the body, to be
+    /// replaced by the full callstack if one follows.
+    frame_from_body: bool,
+}
+
+impl RuntimeErrorCollector {
+    fn push(&mut self, line: &str) -> Collected {
+        let delimiter = is_delimiter(line);
+        let Some(block) = &mut self.block else {
+            if delimiter {
+                self.block = Some(RuntimeErrorBlock::default());
+                self.in_body = true;
+                self.frame_from_body = false;
+                return Collected::Consumed;
+            }
+            return Collected::NotInBlock;
+        };
+
+        if delimiter {
+            if self.in_body {
+                self.in_body = false;
+                return Collected::Consumed;
+            }
+            // A new block right after the previous callstack.
+            return self
+                .finish()
+                .map_or(Collected::NotInBlock, Collected::Finished);
+        }
+
+        if let Some(frame) = parse_stack_frame(line) {
+            if self.in_body {
+                // The body quotes the top frame as " at gml_... (line N) - code".
+                if block.callstack.is_empty() {
+                    block.callstack.push(frame);
+                    self.frame_from_body = true;
+                }
+            } else {
+                if self.frame_from_body {
+                    block.callstack.clear();
+                    self.frame_from_body = false;
+                }
+                block.callstack.push(frame);
+            }
+            return Collected::Consumed;
+        }
+
+        if self.in_body {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.chars().all(|c| c == '_' || c == '-') {
+                block.message.push(trimmed.to_string());
+            }
+            return Collected::Consumed;
+        }
+
+        self.finish()
+            .map_or(Collected::NotInBlock, Collected::Finished)
+    }
+
+    fn finish(&mut self) -> Option<RuntimeErrorBlock> {
+        self.in_body = false;
+        self.frame_from_body = false;
+        self.block.take()
+    }
+}
+
+fn is_delimiter(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.len() >= ERROR_DELIMITER_LEN && trimmed.chars().all(|c| c == '#')
+}
+
+/// Parse `gml_Object_oPlayer_Step_0 (line 3)`, optionally prefixed with `at`
+/// and followed by ` - <source>`.
+fn parse_stack_frame(line: &str) -> Option<StackFrame> {
+    let trimmed = line.trim_start();
+    let trimmed = trimmed.strip_prefix("at ").unwrap_or(trimmed).trim_start();
+    if !trimmed.starts_with("gml_") {
+        return None;
+    }
+    let (symbol, rest) = trimmed.split_once(" (line ")?;
+    let (line_str, _) = rest.split_once(')')?;
+    Some(StackFrame {
+        symbol: symbol.trim().to_string(),
+        line: line_str.trim().parse().ok()?,
+    })
+}
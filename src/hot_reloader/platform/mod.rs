@@ -0,0 +1,12 @@
+//! OS-specific process handling for the reloader. Each backend exposes the same
+//! set of free functions so the core in `hot_reloader` stays platform-neutral.
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+pub use windows::*;
+
+#[cfg(not(windows))]
+mod unix;
+#[cfg(not(windows))]
+pub use unix::*;
@@ -0,0 +1,39 @@
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command};
+
+/// No console windows to hide outside Windows.
+pub fn hide_console(_cmd: &mut Command) {}
+
+/// Start the child in its own process group so [kill_tree] reaches everything
+/// it spawns (shell scripts, Igor's runner).
+pub fn isolate_process_group(cmd: &mut Command) {
+    cmd.process_group(0);
+}
+
+/// Kill a child started with [isolate_process_group] and all of its descendants.
+pub fn kill_tree(child: &mut Child) {
+    let _ = Command::new("kill")
+        .args(["-s", "KILL", "--", &format!("-{}", child.id())])
+        .output();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Build a command that runs `command_line` through `sh -c`.
+pub fn shell_command(command_line: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command_line);
+    cmd
+}
+
+/// Kill every process whose name matches exactly. Returns `true` if anything
+/// was killed.
+pub fn kill_process_by_name(name: &str) -> bool {
+    matches!(
+        Command::new("pkill").args(["-KILL", "-x", name]).output(),
+        Ok(output) if output.status.success()
+    )
+}
+
+/// Window managers handle focus themselves here; nothing to restore.
+pub fn keep_focus_after_launch() {}
@@ -0,0 +1,68 @@
+use std::os::windows::process::CommandExt;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+unsafe extern "system" {
+    fn GetForegroundWindow() -> isize;
+    fn SetForegroundWindow(hwnd: isize) -> i32;
+}
+
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+const FOCUS_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Keep background tools (Igor, taskkill) from flashing a console window.
+pub fn hide_console(cmd: &mut Command) {
+    cmd.creation_flags(CREATE_NO_WINDOW);
+}
+
+/// `taskkill /T` already walks the process tree, so no grouping is needed.
+pub fn isolate_process_group(_cmd: &mut Command) {}
+
+/// Kill a child and every process it started.
+pub fn kill_tree(child: &mut Child) {
+    let mut cmd = Command::new("taskkill");
+    cmd.args(["/F", "/T", "/PID", &child.id().to_string()]);
+    hide_console(&mut cmd);
+    let _ = cmd.output();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// Build a command that runs `command_line` through `cmd /C`.
+pub fn shell_command(command_line: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command_line);
+    cmd
+}
+
+/// Force-kill every process with the given image name. Returns `true` if
+/// anything was killed.
+pub fn kill_process_by_name(name: &str) -> bool {
+    let mut cmd = Command::new("taskkill");
+    cmd.args(["/F", "/IM", name]);
+    hide_console(&mut cmd);
+    matches!(cmd.output(), Ok(output) if output.status.success())
+}
+
+/// Prevent the freshly launched runner from stealing focus: poll until the
+/// foreground window changes (runner appeared), then restore the original one.
+pub fn keep_focus_after_launch() {
+    let saved_hwnd = unsafe { GetForegroundWindow() };
+    if saved_hwnd == 0 {
+        return;
+    }
+
+    std::thread::spawn(move || {
+        let start = Instant::now();
+        while start.elapsed() < FOCUS_TIMEOUT {
+            std::thread::sleep(Duration::from_millis(500));
+            let current = unsafe { GetForegroundWindow() };
+            if current != saved_hwnd {
+                unsafe {
+                    SetForegroundWindow(saved_hwnd);
+                }
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Per-release-channel folders GameMaker keeps its caches and temp files under.
+pub const PRODUCT_FOLDERS: &[&str] = &[
+    "GameMakerStudio2",
+    "GameMakerStudio2-Beta",
+    "GameMakerStudio2-LTS",
+];
+const RUNTIME_PREFIX: &str = "runtime-";
+
+/// Igor build target. Names match what Igor expects after `--`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetPlatform {
+    Windows,
+    Linux,
+    Mac,
+}
+
+impl TargetPlatform {
+    pub fn host() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::Mac
+        } else {
+            Self::Linux
+        }
+    }
+
+    /// Parse a user-supplied target name. Accepts the IDE's "Ubuntu" label for Linux.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "windows" | "win" => Ok(Self::Windows),
+            "linux" | "ubuntu" => Ok(Self::Linux),
+            "mac" | "macos" | "osx" => Ok(Self::Mac),
+            other => Err(format!(
+                "Unknown target platform '{other}' (expected Windows, Linux/Ubuntu or Mac)"
+            )),
+        }
+    }
+
+    pub fn igor_name(self) -> &'static str {
+        match self {
+            Self::Windows => "Windows",
+            Self::Linux => "Linux",
+            Self::Mac => "Mac",
+        }
+    }
+
+    /// Process name of the runner Igor launches for this target.
+    pub fn runner_name(self) -> &'static str {
+        match self {
+            Self::Windows => "Runner.exe",
+            Self::Linux | Self::Mac => "Runner",
+        }
+    }
+}
+
+impl fmt::Display for TargetPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.igor_name())
+    }
+}
+
+/// An installed runtime, e.g. `.../Cache/runtimes/runtime-2024.1400.4.968`.
+#[derive(Debug, Clone)]
+pub struct GmRuntime {
+    /// Version string without the `runtime-` prefix, e.g. "2024.1400.4.968".
+    pub version: String,
+    pub dir: PathBuf,
+}
+
+impl GmRuntime {
+    /// Path to the Igor executable for the host OS inside this runtime.
+    pub fn igor_path(&self) -> PathBuf {
+        let (os_dir, exe) = if cfg!(target_os = "windows") {
+            ("windows", "Igor.exe")
+        } else if cfg!(target_os = "macos") {
+            ("osx", "Igor")
+        } else {
+            ("linux", "Igor")
+        };
+        let arch = if cfg!(target_arch = "aarch64") {
+            "arm64"
+        } else {
+            "x64"
+        };
+        self.dir
+            .join("bin")
+            .join("igor")
+            .join(os_dir)
+            .join(arch)
+            .join(exe)
+    }
+
+    fn version_key(&self) -> Vec<u64> {
+        self.version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+}
+
+/// Standard runtime cache locations for the host OS, across IDE release channels.
+pub fn runtime_cache_dirs() -> Vec<PathBuf> {
+    let bases: Vec<PathBuf> = if cfg!(target_os = "windows") {
+        let program_data =
+            std::env::var_os("ProgramData").unwrap_or_else(|| r"C:\ProgramData".into());
+        vec![PathBuf::from(program_data)]
+    } else if cfg!(target_os = "macos") {
+        vec![PathBuf::from("/Users/Shared")]
+    } else {
+        dirs::data_local_dir().into_iter().collect()
+    };
+
+    bases
+        .iter()
+        .flat_map(|base| {
+            PRODUCT_FOLDERS
+                .iter()
+                .map(move |product| base.join(product).join("Cache").join("runtimes"))
+        })
+        .collect()
+}
+
+/// Find every `runtime-*` folder under `extra_dir` (if given) and the standard
+/// cache locations. The result is sorted newest version first.
+pub fn discover_runtimes(extra_dir: Option<&Path>) -> Vec<GmRuntime> {
+    let mut search_dirs: Vec<PathBuf> = extra_dir.map(Path::to_path_buf).into_iter().collect();
+    search_dirs.extend(runtime_cache_dirs());
+
+    let mut runtimes = Vec::new();
+    for dir in search_dirs {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(version) = name.strip_prefix(RUNTIME_PREFIX) {
+                runtimes.push(GmRuntime {
+                    version: version.to_string(),
+                    dir: path,
+                });
+            }
+        }
+    }
+
+    runtimes.sort_by_key(|r| std::cmp::Reverse(r.version_key()));
+    runtimes
+}
+
+/// Pick the requested runtime version, or the newest one that has an Igor
+/// binary for this host.
+pub fn select_runtime(runtimes: &[GmRuntime], wanted: Option<&str>) -> Result<GmRuntime, String> {
+    if runtimes.is_empty() {
+        return Err(
+            "No GameMaker runtimes found. Install one from the IDE or set \"runtimesDir\" in gmhelper.json"
+                .to_string(),
+        );
+    }
+
+    match wanted {
+        Some(version) => {
+            let version = version.trim_start_matches(RUNTIME_PREFIX);
+            runtimes
+                .iter()
+                .find(|r| r.version == version)
+                .cloned()
+                .ok_or_else(|| {
+                    let available: Vec<&str> =
+                        runtimes.iter().map(|r| r.version.as_str()).collect();
+                    format!(
+                        "Runtime {version} is not installed (found: {})",
+                        available.join(", ")
+                    )
+                })
+        }
+        None => runtimes
+            .iter()
+            .find(|r| r.igor_path().exists())
+            .cloned()
+            .ok_or_else(|| "No installed runtime contains an Igor binary for this OS".to_string()),
+    }
+}
@@ -0,0 +1,257 @@
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use super::output::{self, BuildProgress, OutputContext};
+use super::{BuildCommand, IGOR_JOBS_ARG, ReloadPlan, platform};
+use crate::project_config::BusyPolicy;
+
+/// Owns the build/run process and decides what a new change does to it.
+///
+/// A cycle goes `Building` -> `Running` -> (exit or next change). A change that
+/// lands while a build is still compiling either cancels it and starts over
+/// ([`BusyPolicy::Cancel`]) or queues exactly one rebuild for when it ends
+/// ([`BusyPolicy::Queue`]).
+pub struct BuildSupervisor {
+    policy: BusyPolicy,
+    state: State,
+    /// At most one rebuild waiting for the current build to finish.
+    queued: bool,
+    cycle: u32,
+}
+
+enum State {
+    Idle,
+    Building(ActiveProcess),
+    Running(ActiveProcess),
+}
+
+/// A spawned build/run process plus what its output readers report.
+struct ActiveProcess {
+    child: Child,
+    progress: BuildProgress,
+    cycle: u32,
+    started: Instant,
+    /// Time from spawn to the game starting, once known.
+    build_time: Option<Duration>,
+}
+
+impl BuildSupervisor {
+    pub fn new(policy: BusyPolicy) -> Self {
+        Self {
+            policy,
+            state: State::Idle,
+            queued: false,
+            cycle: 0,
+        }
+    }
+
+    /// A debounced change arrived: rebuild, superseding whatever is in flight.
+    pub fn request_rebuild(&mut self, plan: &ReloadPlan) {
+        if let State::Building(process) = &self.state {
+            match self.policy {
+                BusyPolicy::Queue => {
+                    if !self.queued {
+                        println!("  Build #{} still running; rebuild queued", process.cycle);
+                    }
+                    self.queued = true;
+                    return;
+                }
+                BusyPolicy::Cancel => {
+                    println!(
+                        "  Build #{} cancelled after {} (superseded by new changes)",
+                        process.cycle,
+                        format_duration(process.started.elapsed())
+                    );
+                }
+            }
+        }
+
+        self.stop(plan);
+        self.start(plan);
+    }
+
+    /// Called every loop tick to notice build outcomes and start a queued
+    /// rebuild once the current build has finished.
+    pub fn poll(&mut self, plan: &ReloadPlan) {
+        match &mut self.state {
+            State::Idle => {}
+            State::Building(process) => {
+                let errors = process.progress.errors.load(Ordering::SeqCst);
+                let warnings = process.progress.warnings.load(Ordering::SeqCst);
+                let elapsed = process.started.elapsed();
+
+                if errors > 0 {
+                    // Stop before a broken build can reach the runner.
+                    eprintln!(
+                        "  Build #{} failed in {}: {errors} error{}, {warnings} warning{}. Runner not launched.",
+                        process.cycle,
+                        format_duration(elapsed),
+                        plural(errors),
+                        plural(warnings)
+                    );
+                    self.stop(plan);
+                } else if process.progress.run_started.load(Ordering::SeqCst) {
+                    println!(
+                        "  Build #{} succeeded in {} ({warnings} warning{}); game running",
+                        process.cycle,
+                        format_duration(elapsed),
+                        plural(warnings)
+                    );
+                    process.build_time = Some(elapsed);
+                    if let State::Building(process) =
+                        std::mem::replace(&mut self.state, State::Idle)
+                    {
+                        self.state = State::Running(process);
+                    }
+                } else if let Some(status) = try_exit_status(&mut process.child) {
+                    if status.success() {
+                        println!(
+                            "  Build #{} finished in {}",
+                            process.cycle,
+                            format_duration(elapsed)
+                        );
+                    } else {
+                        eprintln!(
+                            "  Build #{} failed in {}: process exited with code {}",
+                            process.cycle,
+                            format_duration(elapsed),
+                            status.code().unwrap_or(-1)
+                        );
+                    }
+                    self.state = State::Idle;
+                }
+            }
+            State::Running(process) => {
+                if let Some(status) = try_exit_status(&mut process.child) {
+                    let run_time = process
+                        .started
+                        .elapsed()
+                        .saturating_sub(process.build_time.unwrap_or_default());
+                    println!(
+                        "  Game from build #{} exited after {} (code {})",
+                        process.cycle,
+                        format_duration(run_time),
+                        status.code().unwrap_or(-1)
+                    );
+                    self.state = State::Idle;
+                }
+            }
+        }
+
+        if self.queued && !matches!(self.state, State::Building(_)) {
+            self.queued = false;
+            println!("  Starting queued rebuild...");
+            self.stop(plan);
+            self.start(plan);
+        }
+    }
+
+    fn start(&mut self, plan: &ReloadPlan) {
+        self.cycle += 1;
+        self.state = match build_and_run(plan, self.cycle) {
+            Some(process) => State::Building(process),
+            None => State::Idle,
+        };
+    }
+
+    /// Kill the current process (if any) and any runner it left behind.
+    fn stop(&mut self, plan: &ReloadPlan) {
+        if let State::Building(mut process) | State::Running(mut process) =
+            std::mem::replace(&mut self.state, State::Idle)
+            && matches!(process.child.try_wait(), Ok(None))
+        {
+            platform::kill_tree(&mut process.child);
+        }
+
+        // Igor hands the game off to a separate runner process, so the child
+        // handle alone is not enough -- either way, proceed.
+        if let Some(name) = &plan.runner_name
+            && platform::kill_process_by_name(name)
+        {
+            println!("  Killed existing {name}");
+        }
+    }
+}
+
+fn try_exit_status(child: &mut Child) -> Option<ExitStatus> {
+    match child.try_wait() {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("  Error: Failed to check build process: {e}");
+            None
+        }
+    }
+}
+
+fn build_and_run(plan: &ReloadPlan, cycle: u32) -> Option<ActiveProcess> {
+    let project_name = plan
+        .yyp_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+
+    let (mut cmd, tool) = match &plan.command {
+        BuildCommand::Igor { igor, options_file } => {
+            let mut cmd = Command::new(igor);
+            cmd.arg(IGOR_JOBS_ARG)
+                .arg(format!("-options={}", options_file.display()))
+                .arg("-v")
+                .arg("--")
+                .arg(plan.target.igor_name())
+                .arg("Run");
+            (cmd, "Igor")
+        }
+        BuildCommand::Custom(command_line) => {
+            (platform::shell_command(command_line), "build command")
+        }
+    };
+    platform::hide_console(&mut cmd);
+    platform::isolate_process_group(&mut cmd);
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("  Error: Failed to launch {tool}: {e}");
+            return None;
+        }
+    };
+
+    println!("  Build #{cycle} started for {project_name}");
+    platform::keep_focus_after_launch();
+
+    let progress = BuildProgress::default();
+    let started = Instant::now();
+    let show_output = matches!(plan.command, BuildCommand::Custom(_));
+    let context = || OutputContext {
+        project_dir: plan.project_dir.clone(),
+        filter: Arc::clone(&plan.filter),
+        progress: progress.clone(),
+        started,
+        show_output,
+    };
+    if let Some(stdout) = child.stdout.take() {
+        output::spawn_output_reader(stdout, context());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        output::spawn_output_reader(stderr, context());
+    }
+
+    Some(ActiveProcess {
+        child,
+        progress,
+        cycle,
+        started,
+        build_time: None,
+    })
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.1}s", d.as_secs_f64())
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
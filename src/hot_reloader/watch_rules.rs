@@ -0,0 +1,81 @@
+use std::path::{Component, Path};
+
+/// Resource folders that trigger a reload when `watch` is not configured.
+pub const DEFAULT_WATCHED_RESOURCES: &[&str] = &[
+    "scripts",
+    "objects",
+    "rooms",
+    "sprites",
+    "shaders",
+    "sounds",
+    "fonts",
+    "tilesets",
+    "sequences",
+    "timelines",
+    "paths",
+    "extensions",
+    "datafiles",
+];
+
+/// Decides which changed paths under the project warrant a rebuild, by the
+/// top-level resource folder they live in (`scripts`, `sprites`, ...).
+#[derive(Debug, Clone)]
+pub struct WatchRules {
+    resources: Vec<String>,
+}
+
+impl WatchRules {
+    /// `resources` are folder names as they appear in the project directory;
+    /// `None` selects [DEFAULT_WATCHED_RESOURCES].
+    pub fn new(resources: Option<Vec<String>>) -> Self {
+        let resources = resources.unwrap_or_else(|| {
+            DEFAULT_WATCHED_RESOURCES
+                .iter()
+                .map(|r| r.to_string())
+                .collect()
+        });
+        Self { resources }
+    }
+
+    pub fn resources(&self) -> &[String] {
+        &self.resources
+    }
+
+    /// The resource type a change to `path` belongs to, or `None` if it should
+    /// not trigger a reload. The `.yyp` itself counts as `"project"`.
+    pub fn classify(&self, project_dir: &Path, path: &Path) -> Option<String> {
+        if is_scratch_file(path) {
+            return None;
+        }
+
+        let relative = path.strip_prefix(project_dir).ok()?;
+        let mut components = relative.components();
+        let Some(Component::Normal(first)) = components.next() else {
+            return None;
+        };
+        let first = first.to_string_lossy();
+
+        if components.next().is_none() {
+            // A file directly in the project folder: only the .yyp matters.
+            return (path.extension().and_then(|e| e.to_str()) == Some("yyp"))
+                .then(|| "project".to_string());
+        }
+
+        self.resources
+            .iter()
+            .find(|r| r.eq_ignore_ascii_case(&first))
+            .cloned()
+    }
+}
+
+/// Temp and backup files editors and gmhelper create next to real ones.
+fn is_scratch_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return true;
+    };
+    name.ends_with(".tmp")
+        || name.ends_with('~')
+        || name.ends_with(".old")
+        || name.ends_with(".swp")
+        || name.starts_with(".#")
+}
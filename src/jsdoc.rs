@@ -0,0 +1,639 @@
+//! `///` doc comments in the JSDoc style GameMaker's IDE reads for
+//! autocomplete: the `//: doc;` command that writes them, and the
+//! `gmhelper docs` API reference built from them.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::gml::ast::{self, Expr, ExprKind, Function, Stmt, StmtKind, Visitor};
+use crate::gml::{LineIndex, Program};
+use crate::snippets::Expansion;
+use crate::symbol_index::ProjectIndex;
+
+/// A parsed doc comment. Descriptions and types written by hand survive
+/// regeneration; only the signature and parameter list follow the code.
+#[derive(Debug, Default, Clone)]
+pub struct DocComment {
+    /// Lines of the `@description`, including untagged continuation lines.
+    pub description: Vec<String>,
+    pub params: Vec<DocParam>,
+    pub returns: Option<DocReturn>,
+    /// Tags gmhelper doesn't generate (`@deprecated`, `@pure`, ...), verbatim.
+    pub other_tags: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DocParam {
+    pub name: String,
+    pub ty: Option<String>,
+    /// Default value as written in the code, for optional parameters.
+    pub default: Option<String>,
+    pub optional: bool,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DocReturn {
+    pub ty: Option<String>,
+    pub description: String,
+}
+
+/// Which part of the comment an untagged line continues.
+enum Continues {
+    Description,
+    Param,
+    Returns,
+    Nothing,
+}
+
+impl DocComment {
+    /// Parse `///` lines, given with or without their `///` and indentation.
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut doc = DocComment::default();
+        let mut continues = Continues::Description;
+        for line in lines {
+            let text = doc_line_text(line).unwrap_or(line.trim());
+            let Some(tagged) = text.strip_prefix('@') else {
+                if text.is_empty() {
+                    continue;
+                }
+                match continues {
+                    Continues::Description => doc.description.push(text.to_string()),
+                    Continues::Param => {
+                        if let Some(param) = doc.params.last_mut() {
+                            append(&mut param.description, text);
+                        }
+                    }
+                    Continues::Returns => {
+                        if let Some(returns) = &mut doc.returns {
+                            append(&mut returns.description, text);
+                        }
+                    }
+                    Continues::Nothing => {}
+                }
+                continue;
+            };
+
+            let (tag, rest) = tagged
+                .split_once(char::is_whitespace)
+                .map_or((tagged, ""), |(tag, rest)| (tag, rest.trim()));
+            match tag {
+                "function" | "func" => continues = Continues::Nothing,
+                "description" | "desc" => {
+                    if !rest.is_empty() {
+                        doc.description.push(rest.to_string());
+                    }
+                    continues = Continues::Description;
+                }
+                "param" | "arg" | "argument" => {
+                    if let Some(param) = parse_param(rest) {
+                        doc.params.push(param);
+                        continues = Continues::Param;
+                    }
+                }
+                "returns" | "return" => {
+                    let (ty, description) = split_type(rest);
+                    doc.returns = Some(DocReturn {
+                        ty,
+                        description: description.to_string(),
+                    });
+                    continues = Continues::Returns;
+                }
+                _ => {
+                    doc.other_tags.push(text.to_string());
+                    continues = Continues::Nothing;
+                }
+            }
+        }
+        doc
+    }
+
+    fn param(&self, name: &str) -> Option<&DocParam> {
+        self.params.iter().find(|p| p.name == name)
+    }
+
+    /// `name(a, b, [c])`, optional parameters in brackets.
+    pub fn signature(&self, name: &str) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| {
+                if p.optional {
+                    format!("[{}]", p.name)
+                } else {
+                    p.name.clone()
+                }
+            })
+            .collect();
+        format!("{name}({})", params.join(", "))
+    }
+
+    /// The comment as `///` lines, without indentation.
+    pub fn render(&self, name: &str) -> Vec<String> {
+        let mut lines = vec![format!("/// @function {}", self.signature(name))];
+        match self.description.split_first() {
+            Some((first, rest)) => {
+                lines.push(format!("/// @description {first}"));
+                lines.extend(rest.iter().map(|line| format!("/// {line}")));
+            }
+            None => lines.push("/// @description".to_string()),
+        }
+        for param in &self.params {
+            let name = match (&param.default, param.optional) {
+                (Some(default), _) => format!("[{}={default}]", param.name),
+                (None, true) => format!("[{}]", param.name),
+                (None, false) => param.name.clone(),
+            };
+            lines.push(tagged_line(
+                "param",
+                &format!("{{{}}} {name}", type_or_any(&param.ty)),
+                &param.description,
+            ));
+        }
+        if let Some(returns) = &self.returns {
+            lines.push(tagged_line(
+                "returns",
+                &format!("{{{}}}", type_or_any(&returns.ty)),
+                &returns.description,
+            ));
+        }
+        lines.extend(self.other_tags.iter().map(|tag| format!("/// {tag}")));
+        lines
+    }
+}
+
+fn tagged_line(tag: &str, head: &str, description: &str) -> String {
+    if description.is_empty() {
+        format!("/// @{tag} {head}")
+    } else {
+        format!("/// @{tag} {head} {description}")
+    }
+}
+
+fn type_or_any(ty: &Option<String>) -> &str {
+    ty.as_deref().unwrap_or("Any")
+}
+
+fn append(text: &mut String, more: &str) {
+    if !text.is_empty() {
+        text.push(' ');
+    }
+    text.push_str(more);
+}
+
+/// The text after `///` if `line` is a doc comment line.
+fn doc_line_text(line: &str) -> Option<&str> {
+    line.trim_start_matches('\u{feff}')
+        .trim_start()
+        .strip_prefix("///")
+        .map(str::trim)
+}
+
+fn is_doc_line(line: &str) -> bool {
+    doc_line_text(line).is_some()
+}
+
+/// `{Type} rest` to `(Some("Type"), "rest")`.
+fn split_type(text: &str) -> (Option<String>, &str) {
+    if let Some(inner) = text.strip_prefix('{')
+        && let Some((ty, rest)) = inner.split_once('}')
+    {
+        return (Some(ty.trim().to_string()), rest.trim_start());
+    }
+    (None, text)
+}
+
+/// `{Type} name description`, `[name]` or `[name=default]` when optional.
+fn parse_param(text: &str) -> Option<DocParam> {
+    let (ty, rest) = split_type(text);
+    let (name_part, description) = match rest.strip_prefix('[') {
+        Some(inner) => {
+            let (inside, after) = inner.split_once(']')?;
+            (format!("[{inside}]"), after.trim())
+        }
+        None => {
+            let (name, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            (name.to_string(), after.trim())
+        }
+    };
+    let (optional, inside) = match name_part
+        .strip_prefix('[')
+        .and_then(|n| n.strip_suffix(']'))
+    {
+        Some(inside) => (true, inside),
+        None => (false, name_part.as_str()),
+    };
+    let (name, default) = match inside.split_once('=') {
+        Some((name, default)) => (name.trim(), Some(default.trim().to_string())),
+        None => (inside.trim(), None),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    Some(DocParam {
+        name: name.to_string(),
+        ty,
+        default,
+        optional,
+        description: description.to_string(),
+    })
+}
+
+/// The comment for `function`, keeping what `existing` says about it.
+fn document(function: &Function, source: &str, existing: &DocComment) -> DocComment {
+    let params = function
+        .params
+        .iter()
+        .map(|param| {
+            let name = &param.name.name;
+            let old = existing.param(name);
+            let default = param
+                .default
+                .as_ref()
+                .map(|d| d.span.text(source).to_string());
+            DocParam {
+                name: name.clone(),
+                ty: old
+                    .and_then(|p| p.ty.clone())
+                    .or_else(|| param.default.as_ref().and_then(infer_type)),
+                optional: default.is_some(),
+                default,
+                description: old.map(|p| p.description.clone()).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let returns = existing.returns.clone().or_else(|| {
+        (!function.is_constructor && returns_value(function)).then_some(DocReturn {
+            ty: None,
+            description: String::new(),
+        })
+    });
+
+    DocComment {
+        description: existing.description.clone(),
+        params,
+        returns,
+        other_tags: existing.other_tags.clone(),
+    }
+}
+
+/// A Feather type name for a default value, when it's obvious.
+fn infer_type(expr: &Expr) -> Option<String> {
+    let ty = match &expr.kind {
+        ExprKind::Number(_) => "Real",
+        ExprKind::Unary { op, operand } if op == "-" || op == "+" => {
+            return infer_type(operand);
+        }
+        ExprKind::String(_) | ExprKind::Template(_) => "String",
+        ExprKind::Ident(name) if name == "true" || name == "false" => "Bool",
+        ExprKind::Array(_) => "Array",
+        ExprKind::Struct(_) => "Struct",
+        ExprKind::Function(_) => "Function",
+        ExprKind::New { callee, .. } => match &callee.kind {
+            ExprKind::Ident(name) => return Some(format!("Struct.{name}")),
+            _ => "Struct",
+        },
+        _ => return None,
+    };
+    Some(ty.to_string())
+}
+
+/// Whether `function` itself (not a nested function) returns a value.
+fn returns_value(function: &Function) -> bool {
+    struct Finder {
+        found: bool,
+    }
+    impl Visitor for Finder {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            if matches!(stmt.kind, StmtKind::Return(Some(_))) {
+                self.found = true;
+            }
+            ast::walk_stmt(self, stmt);
+        }
+
+        fn visit_function(&mut self, _: &Function) {}
+    }
+
+    let mut finder = Finder { found: false };
+    ast::walk_stmts(&mut finder, &function.body);
+    finder.found
+}
+
+/// The function declared on a 1-based line, with the name it's called by:
+/// its own, or the variable a function expression is assigned to.
+fn function_on_line(
+    program: &Program,
+    lines: &LineIndex,
+    line: usize,
+) -> Option<(String, Function)> {
+    struct Finder<'a> {
+        lines: &'a LineIndex,
+        line: usize,
+        found: Option<(String, Function)>,
+    }
+    impl Finder<'_> {
+        fn check(&mut self, name: Option<&str>, function: &Function) {
+            if self.found.is_none()
+                && let Some(name) = name.or(function.name.as_ref().map(|n| n.name.as_str()))
+                && self.lines.line_col(function.span.start).0 == self.line
+            {
+                self.found = Some((name.to_string(), function.clone()));
+            }
+        }
+    }
+    impl Visitor for Finder<'_> {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            match &stmt.kind {
+                StmtKind::Expr(Expr {
+                    kind: ExprKind::Assign { target, value, .. },
+                    ..
+                }) => {
+                    if let ExprKind::Function(function) = &value.kind {
+                        let name = match &target.kind {
+                            ExprKind::Ident(name) => Some(name.as_str()),
+                            ExprKind::Member { field, .. } => Some(field.name.as_str()),
+                            _ => None,
+                        };
+                        self.check(name, function);
+                    }
+                }
+                StmtKind::Declare { vars, .. } => {
+                    for var in vars {
+                        if let Some(Expr {
+                            kind: ExprKind::Function(function),
+                            ..
+                        }) = &var.init
+                        {
+                            self.check(Some(&var.name.name), function);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            ast::walk_stmt(self, stmt);
+        }
+
+        fn visit_function(&mut self, function: &Function) {
+            self.check(None, function);
+            ast::walk_function(self, function);
+        }
+    }
+
+    let mut finder = Finder {
+        lines,
+        line,
+        found: None,
+    };
+    ast::walk_stmts(&mut finder, &program.statements);
+    finder.found
+}
+
+/// Expand `//: doc;` on the 0-based line `index` of `lines` into a doc comment
+/// for the function below it. `///` lines directly above or below the command
+/// are the comment being regenerated and are replaced along with it; returns
+/// the expansion and the 0-based, inclusive range of lines it replaces.
+pub fn expand_doc_command(
+    content: &str,
+    program: &Program,
+    lines: &[&str],
+    index: usize,
+) -> Result<(Expansion, usize, usize), String> {
+    let first = (0..index)
+        .rev()
+        .take_while(|&i| is_doc_line(lines[i]))
+        .last()
+        .unwrap_or(index);
+    let last = (index + 1..lines.len())
+        .take_while(|&i| is_doc_line(lines[i]))
+        .last()
+        .unwrap_or(index);
+
+    let line_index = LineIndex::new(content);
+    let (name, function) = function_on_line(program, &line_index, last + 2)
+        .ok_or("//: doc; goes directly above a function declaration")?;
+
+    let existing = DocComment::parse((first..=last).filter(|&i| i != index).map(|i| lines[i]));
+    let doc = document(&function, content, &existing);
+
+    let function_line = lines[last + 1];
+    let indent = &function_line[..function_line.len() - function_line.trim_start().len()];
+    let lines: Vec<String> = doc
+        .render(&name)
+        .into_iter()
+        .map(|line| format!("{indent}{line}"))
+        .collect();
+    // An empty description is the one thing left to write.
+    let cursor = doc
+        .description
+        .is_empty()
+        .then(|| (1, indent.len() + "/// @description".len()));
+    Ok((Expansion { lines, cursor }, first, last))
+}
+
+// ---------------------------------------------------------------------------
+// API reference
+// ---------------------------------------------------------------------------
+
+/// One documented function in the reference.
+struct Entry {
+    name: String,
+    is_constructor: bool,
+    doc: DocComment,
+}
+
+/// Each script with its top-level functions, in project order.
+fn collect_entries(index: &ProjectIndex) -> Vec<(String, Vec<Entry>)> {
+    let mut scripts = Vec::new();
+    for resource in index.resources.iter().filter(|r| r.kind == "scripts") {
+        let Some(dir) = resource.yy_path.parent() else {
+            continue;
+        };
+        let path = index
+            .project_dir
+            .join(dir)
+            .join(format!("{}.gml", resource.name));
+        let Some(file) = index.files.iter().find(|f| f.path == path) else {
+            continue;
+        };
+        let source_lines: Vec<&str> = file.source.lines().collect();
+
+        let mut entries = Vec::new();
+        for stmt in &file.program.statements {
+            let StmtKind::Function(function) = &stmt.kind else {
+                continue;
+            };
+            let Some(name) = &function.name else {
+                continue;
+            };
+            let line = file.lines.line_col(function.span.start).0 - 1;
+            let start = (0..line)
+                .rev()
+                .take_while(|&i| is_doc_line(source_lines[i]))
+                .last()
+                .unwrap_or(line);
+            let existing = DocComment::parse(source_lines[start..line].iter().copied());
+            entries.push(Entry {
+                name: name.name.clone(),
+                is_constructor: function.is_constructor,
+                doc: document(function, &file.source, &existing),
+            });
+        }
+        if !entries.is_empty() {
+            scripts.push((resource.name.clone(), entries));
+        }
+    }
+    scripts.sort_by_key(|(name, _)| name.to_lowercase());
+    scripts
+}
+
+/// Markdown API reference for every function the project's scripts declare.
+pub fn markdown_reference(index: &ProjectIndex, title: &str) -> String {
+    let scripts = collect_entries(index);
+    let mut out = format!("# {title} API reference\n\n");
+    for (script, _) in &scripts {
+        let _ = writeln!(out, "- [{script}](#{})", script.to_lowercase());
+    }
+    out.push('\n');
+
+    for (script, entries) in &scripts {
+        let _ = write!(out, "## {script}\n\n");
+        for entry in entries {
+            let _ = write!(out, "### `{}`\n\n", entry.doc.signature(&entry.name));
+            if entry.is_constructor {
+                out.push_str("*Constructor*\n\n");
+            }
+            if !entry.doc.description.is_empty() {
+                let _ = write!(out, "{}\n\n", entry.doc.description.join("\n"));
+            }
+            if !entry.doc.params.is_empty() {
+                out.push_str("| Parameter | Type | Default | Description |\n");
+                out.push_str("|---|---|---|---|\n");
+                for param in &entry.doc.params {
+                    let _ = writeln!(
+                        out,
+                        "| `{}` | {} | {} | {} |",
+                        param.name,
+                        type_or_any(&param.ty),
+                        param
+                            .default
+                            .as_deref()
+                            .map(|d| format!("`{}`", escape_cell(d)))
+                            .unwrap_or_default(),
+                        escape_cell(&param.description)
+                    );
+                }
+                out.push('\n');
+            }
+            if let Some(returns) = &entry.doc.returns {
+                let _ = write!(out, "**Returns** `{}`", type_or_any(&returns.ty));
+                if !returns.description.is_empty() {
+                    let _ = write!(out, ": {}", returns.description);
+                }
+                out.push_str("\n\n");
+            }
+            for tag in &entry.doc.other_tags {
+                let _ = write!(out, "`{tag}`\n\n");
+            }
+        }
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+/// HTML API reference, one self-contained page.
+pub fn html_reference(index: &ProjectIndex, title: &str) -> String {
+    let scripts = collect_entries(index);
+    let title = escape_html(title);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>{title} API reference</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }}\n\
+         code {{ background: #f3f3f3; padding: 0 .2em; }}\n\
+         table {{ border-collapse: collapse; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: .3em .6em; text-align: left; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title} API reference</h1>\n<ul>\n"
+    );
+    for (script, _) in &scripts {
+        let script = escape_html(script);
+        let _ = writeln!(out, "<li><a href=\"#{script}\">{script}</a></li>");
+    }
+    out.push_str("</ul>\n");
+
+    for (script, entries) in &scripts {
+        let script = escape_html(script);
+        let _ = writeln!(out, "<h2 id=\"{script}\">{script}</h2>");
+        for entry in entries {
+            let _ = writeln!(
+                out,
+                "<h3><code>{}</code></h3>",
+                escape_html(&entry.doc.signature(&entry.name))
+            );
+            if entry.is_constructor {
+                out.push_str("<p><em>Constructor</em></p>\n");
+            }
+            if !entry.doc.description.is_empty() {
+                let _ = writeln!(
+                    out,
+                    "<p>{}</p>",
+                    escape_html(&entry.doc.description.join("\n"))
+                );
+            }
+            if !entry.doc.params.is_empty() {
+                out.push_str(
+                    "<table>\n<tr><th>Parameter</th><th>Type</th><th>Default</th><th>Description</th></tr>\n",
+                );
+                for param in &entry.doc.params {
+                    let _ = writeln!(
+                        out,
+                        "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        escape_html(&param.name),
+                        escape_html(type_or_any(&param.ty)),
+                        param
+                            .default
+                            .as_deref()
+                            .map(|d| format!("<code>{}</code>", escape_html(d)))
+                            .unwrap_or_default(),
+                        escape_html(&param.description)
+                    );
+                }
+                out.push_str("</table>\n");
+            }
+            if let Some(returns) = &entry.doc.returns {
+                let _ = write!(
+                    out,
+                    "<p><strong>Returns</strong> <code>{}</code>",
+                    escape_html(type_or_any(&returns.ty))
+                );
+                if !returns.description.is_empty() {
+                    let _ = write!(out, ": {}", escape_html(&returns.description));
+                }
+                out.push_str("</p>\n");
+            }
+            for tag in &entry.doc.other_tags {
+                let _ = writeln!(out, "<p><code>{}</code></p>", escape_html(tag));
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// The project's name for the reference title: the `.yyp` file stem.
+pub fn project_title(yyp: &Path) -> String {
+    yyp.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Project".to_string())
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
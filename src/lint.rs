@@ -0,0 +1,679 @@
+//! `gmhelper lint`: project-aware checks for common GML mistakes, run over
+//! every code file in the symbol index.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::gml::ast::{self, DeclareKind, Expr, ExprKind, Function, Stmt, StmtKind, Visitor};
+use crate::gml::{self, Span, TokenKind};
+use crate::project_config::LintConfig;
+use crate::symbol_index::{ProjectIndex, SourceFile, SymbolKind};
+
+/// Rule names, as used in `lint.rules` in `gmhelper.json`.
+pub const RULES: &[&str] = &[
+    // Code the parser could not make sense of.
+    "syntax-error",
+    // A name used before its `var` line, where it still means an instance
+    // variable.
+    "use-before-var",
+    // A resource argument that names no resource, local or variable.
+    "unknown-resource",
+    // A project function called with too many or too few arguments.
+    "wrong-arity",
+    // A `var` that is never read.
+    "unused-local",
+    // `=` used as a comparison where `==` was probably meant.
+    "assign-as-compare",
+    // A non-empty `case` that runs on into the next one.
+    "switch-fallthrough",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub severity: &'static str,
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}[{}]: {}",
+            self.file.display(),
+            self.line,
+            self.column,
+            self.severity,
+            self.rule,
+            self.message
+        )
+    }
+}
+
+/// Built-in functions taking a resource, with the argument index and the
+/// resource folder type it expects.
+const RESOURCE_ARGUMENTS: &[(&str, usize, &str)] = &[
+    ("instance_create_layer", 3, "objects"),
+    ("instance_create_depth", 3, "objects"),
+    ("instance_exists", 0, "objects"),
+    ("instance_number", 0, "objects"),
+    ("instance_find", 0, "objects"),
+    ("instance_nearest", 2, "objects"),
+    ("instance_furthest", 2, "objects"),
+    ("instance_place", 2, "objects"),
+    ("place_meeting", 2, "objects"),
+    ("position_meeting", 2, "objects"),
+    ("collision_point", 2, "objects"),
+    ("collision_circle", 3, "objects"),
+    ("collision_line", 4, "objects"),
+    ("collision_rectangle", 4, "objects"),
+    ("object_get_name", 0, "objects"),
+    ("room_goto", 0, "rooms"),
+    ("audio_play_sound", 0, "sounds"),
+    ("audio_play_sound_at", 0, "sounds"),
+    ("draw_sprite", 0, "sprites"),
+    ("draw_sprite_ext", 0, "sprites"),
+    ("draw_sprite_part", 0, "sprites"),
+    ("draw_sprite_stretched", 0, "sprites"),
+    ("sprite_get_width", 0, "sprites"),
+    ("sprite_get_height", 0, "sprites"),
+    ("sprite_get_number", 0, "sprites"),
+    ("draw_set_font", 0, "fonts"),
+];
+
+/// Built-in variables assigned a sprite.
+const SPRITE_VARIABLES: &[&str] = &["sprite_index", "mask_index"];
+
+/// Built-in values and instance variables that stand in for a resource
+/// argument, as in `draw_sprite(sprite_index, ...)` or `instance_exists(id)`.
+const RESOURCE_KEYWORDS: &[&str] = &[
+    "all",
+    "noone",
+    "self",
+    "other",
+    "undefined",
+    "id",
+    "object_index",
+    "sprite_index",
+    "mask_index",
+    "image_index",
+    "room",
+    "room_first",
+    "room_last",
+];
+
+pub fn lint_project(index: &ProjectIndex, config: &LintConfig) -> Vec<Finding> {
+    let context = Context {
+        index,
+        config,
+        functions: project_functions(index),
+        assigned: assigned_names(index),
+        resource_prefixes: index
+            .resources
+            .iter()
+            .filter_map(|r| name_prefix(&r.name))
+            .collect(),
+    };
+
+    let mut findings = Vec::new();
+    for file in &index.files {
+        if config.is_enabled("syntax-error") {
+            for error in &file.program.errors {
+                findings.push(context.finding(
+                    file,
+                    error.span,
+                    "syntax-error",
+                    error.message.clone(),
+                ));
+            }
+        }
+        findings.extend(lint_scope(&context, file, &file.program.statements));
+    }
+    findings.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+    findings
+}
+
+struct Context<'a> {
+    index: &'a ProjectIndex,
+    config: &'a LintConfig,
+    /// Top-level named functions, by name; more than one entry means the
+    /// name is ambiguous.
+    functions: HashMap<String, Vec<Arity>>,
+    /// Every name assigned as an instance, struct or global variable.
+    assigned: HashSet<String>,
+    /// Naming prefixes of the project's resources (`spr_`, `o`, ...).
+    resource_prefixes: HashSet<&'a str>,
+}
+
+impl Context<'_> {
+    fn finding(
+        &self,
+        file: &SourceFile,
+        span: Span,
+        rule: &'static str,
+        message: String,
+    ) -> Finding {
+        let location = file.location(span.start);
+        Finding {
+            file: location.file,
+            line: location.line,
+            column: location.column,
+            severity: if rule == "syntax-error" {
+                "error"
+            } else {
+                "warning"
+            },
+            rule,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Arity {
+    min: usize,
+    max: usize,
+    /// Reads `argument[n]` / `argument_count`, so takes any number.
+    variadic: bool,
+    is_constructor: bool,
+}
+
+fn project_functions(index: &ProjectIndex) -> HashMap<String, Vec<Arity>> {
+    let mut functions: HashMap<String, Vec<Arity>> = HashMap::new();
+    for file in &index.files {
+        for stmt in &file.program.statements {
+            let StmtKind::Function(function) = &stmt.kind else {
+                continue;
+            };
+            let Some(name) = &function.name else {
+                continue;
+            };
+            let body = function.span.text(&file.source);
+            let variadic = gml::tokenize(body).tokens.iter().any(|t| {
+                let text = t.span.text(body);
+                t.kind == TokenKind::Ident
+                    && (text == "argument_count"
+                        || text
+                            .strip_prefix("argument")
+                            .is_some_and(|n| n.chars().all(|c| c.is_ascii_digit())))
+            });
+            functions.entry(name.name.clone()).or_default().push(Arity {
+                min: function
+                    .params
+                    .iter()
+                    .filter(|p| p.default.is_none())
+                    .count(),
+                max: function.params.len(),
+                variadic,
+                is_constructor: function.is_constructor,
+            });
+        }
+    }
+    functions
+}
+
+fn assigned_names(index: &ProjectIndex) -> HashSet<String> {
+    struct Assigned(HashSet<String>);
+
+    impl Visitor for Assigned {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            match &stmt.kind {
+                StmtKind::Expr(Expr {
+                    kind: ExprKind::Assign { target, .. },
+                    ..
+                }) => match &target.kind {
+                    ExprKind::Ident(name) => {
+                        self.0.insert(name.clone());
+                    }
+                    ExprKind::Member { field, .. } => {
+                        self.0.insert(field.name.clone());
+                    }
+                    _ => {}
+                },
+                StmtKind::Declare {
+                    kind: DeclareKind::Static | DeclareKind::GlobalVar,
+                    vars,
+                } => self.0.extend(vars.iter().map(|v| v.name.name.clone())),
+                _ => {}
+            }
+            ast::walk_stmt(self, stmt);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            if let ExprKind::Struct(fields) = &expr.kind {
+                self.0.extend(fields.iter().map(|f| f.name.name.clone()));
+            }
+            ast::walk_expr(self, expr);
+        }
+    }
+
+    let mut assigned = Assigned(HashSet::new());
+    for file in &index.files {
+        ast::walk_stmts(&mut assigned, &file.program.statements);
+    }
+    assigned.0
+}
+
+/// Check an event's (or script's) top level as one local scope.
+fn lint_scope(context: &Context, file: &SourceFile, body: &[Stmt]) -> Vec<Finding> {
+    let mut walker = ScopeWalker::new(context, file);
+    ast::walk_stmts(&mut walker, body);
+    walker.finish()
+}
+
+/// Functions are scopes of their own: they can't see the caller's locals.
+fn lint_function(context: &Context, file: &SourceFile, function: &Function) -> Vec<Finding> {
+    let mut walker = ScopeWalker::new(context, file);
+    walker
+        .bound
+        .extend(function.params.iter().map(|p| p.name.name.clone()));
+    ast::walk_function(&mut walker, function);
+    walker.finish()
+}
+
+struct Call {
+    name: String,
+    span: Span,
+    args: usize,
+    is_new: bool,
+}
+
+struct ScopeWalker<'a> {
+    context: &'a Context<'a>,
+    file: &'a SourceFile,
+    findings: Vec<Finding>,
+    /// `var` declarations, in source order.
+    declared: Vec<(String, Span)>,
+    /// Parameters and `catch` bindings: locals that are never "unused".
+    bound: HashSet<String>,
+    reads: Vec<(String, Span)>,
+    /// Plain `name = value` assignments.
+    writes: Vec<(String, Span)>,
+    calls: Vec<Call>,
+    /// Identifiers passed where a resource is expected, with its folder type.
+    resource_args: Vec<(String, Span, &'static str)>,
+}
+
+impl<'a> ScopeWalker<'a> {
+    fn new(context: &'a Context<'a>, file: &'a SourceFile) -> Self {
+        Self {
+            context,
+            file,
+            findings: Vec::new(),
+            declared: Vec::new(),
+            bound: HashSet::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            calls: Vec::new(),
+            resource_args: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, span: Span, rule: &'static str, message: String) {
+        if self.context.config.is_enabled(rule) {
+            let finding = self.context.finding(self.file, span, rule, message);
+            self.findings.push(finding);
+        }
+    }
+
+    fn finish(mut self) -> Vec<Finding> {
+        let mut first_declaration: HashMap<String, Span> = HashMap::new();
+        for (name, span) in &self.declared {
+            first_declaration.entry(name.clone()).or_insert(*span);
+        }
+
+        let mut late = Vec::new();
+        let mut unused = Vec::new();
+        for (name, declared_at) in &first_declaration {
+            if self.bound.contains(name) {
+                continue;
+            }
+            let early_use = self
+                .reads
+                .iter()
+                .chain(&self.writes)
+                .filter(|(used, span)| used == name && span.start < declared_at.start)
+                .map(|(_, span)| *span)
+                .min_by_key(|span| span.start);
+            if let Some(span) = early_use {
+                late.push((name.to_string(), span));
+            }
+            if !self.reads.iter().any(|(used, _)| used == name) {
+                unused.push((name.to_string(), *declared_at));
+            }
+        }
+        for (name, span) in late {
+            self.report(
+                span,
+                "use-before-var",
+                format!("'{name}' is used before `var {name}`, so here it is an instance variable"),
+            );
+        }
+        for (name, span) in unused {
+            self.report(
+                span,
+                "unused-local",
+                format!("local '{name}' is never read"),
+            );
+        }
+
+        let locals: HashSet<String> = first_declaration
+            .into_keys()
+            .chain(self.bound.iter().cloned())
+            .collect();
+
+        let calls = std::mem::take(&mut self.calls);
+        for call in calls {
+            if locals.contains(&call.name) {
+                continue;
+            }
+            if let Some(message) = self.arity_problem(&call) {
+                self.report(call.span, "wrong-arity", message);
+            }
+        }
+
+        let resource_args = std::mem::take(&mut self.resource_args);
+        for (name, span, expected) in resource_args {
+            if locals.contains(&name) {
+                continue;
+            }
+            if let Some(message) = self.resource_problem(&name, expected) {
+                self.report(span, "unknown-resource", message);
+            }
+        }
+
+        self.findings
+    }
+
+    fn arity_problem(&self, call: &Call) -> Option<String> {
+        let [arity] = self.context.functions.get(&call.name)?.as_slice() else {
+            return None;
+        };
+        if arity.variadic || (call.is_new && !arity.is_constructor) {
+            return None;
+        }
+        if (arity.min..=arity.max).contains(&call.args) {
+            return None;
+        }
+        let expected = if arity.min == arity.max {
+            format!("{}", arity.max)
+        } else {
+            format!("{} to {}", arity.min, arity.max)
+        };
+        Some(format!(
+            "'{}' takes {expected} argument{} but {} {} given",
+            call.name,
+            if arity.max == 1 { "" } else { "s" },
+            call.args,
+            if call.args == 1 { "was" } else { "were" }
+        ))
+    }
+
+    fn resource_problem(&self, name: &str, expected: &'static str) -> Option<String> {
+        if RESOURCE_KEYWORDS.contains(&name) || self.context.assigned.contains(name) {
+            return None;
+        }
+        // Any other built-in or variable: only names that follow the
+        // project's resource naming are taken to mean a resource.
+        if !name_prefix(name).is_some_and(|prefix| self.context.resource_prefixes.contains(prefix))
+        {
+            return None;
+        }
+        let kind_name = SymbolKind::Resource(expected).to_string();
+        let mut other_resource = None;
+        for symbol in self.context.index.definitions(name) {
+            match symbol.kind {
+                SymbolKind::Resource(kind) if kind == expected => return None,
+                SymbolKind::Resource(_) => other_resource = Some(symbol.kind),
+                // A macro, global or function may well hold the resource.
+                _ => return None,
+            }
+        }
+        Some(match other_resource {
+            Some(kind) => format!(
+                "'{name}' is {}, not {}",
+                with_article(&kind.to_string()),
+                with_article(&kind_name)
+            ),
+            None => format!("unknown {kind_name} '{name}'"),
+        })
+    }
+}
+
+/// The naming prefix of a resource name: up to and including the first `_`
+/// (`spr_player`), else the lowercase letters before the first capital
+/// (`sPlayer`).
+fn name_prefix(name: &str) -> Option<&str> {
+    if let Some(underscore) = name.find('_')
+        && underscore > 0
+    {
+        return Some(&name[..=underscore]);
+    }
+    let capital = name.find(|c: char| c.is_ascii_uppercase())?;
+    (capital > 0 && name[..capital].chars().all(|c| c.is_ascii_lowercase()))
+        .then(|| &name[..capital])
+}
+
+fn with_article(word: &str) -> String {
+    if word.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        format!("an {word}")
+    } else {
+        format!("a {word}")
+    }
+}
+
+/// Whether control never runs past the end of `stmt`.
+fn ends_flow(stmt: &Stmt) -> bool {
+    match &stmt.kind {
+        StmtKind::Break
+        | StmtKind::Continue
+        | StmtKind::Return(_)
+        | StmtKind::Exit
+        | StmtKind::Throw(_) => true,
+        StmtKind::Block(stmts) => stmts.last().is_some_and(ends_flow),
+        StmtKind::If {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => ends_flow(then_branch) && ends_flow(else_branch),
+        _ => false,
+    }
+}
+
+impl Visitor for ScopeWalker<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Declare {
+                kind: DeclareKind::Var,
+                vars,
+            } => {
+                for var in vars {
+                    self.declared.push((var.name.name.clone(), var.name.span));
+                }
+            }
+            StmtKind::Expr(Expr {
+                kind: ExprKind::Assign { op, target, value },
+                ..
+            }) if op == "=" => {
+                if let ExprKind::Ident(name) = &target.kind {
+                    self.writes.push((name.clone(), target.span));
+                    if SPRITE_VARIABLES.contains(&name.as_str())
+                        && let ExprKind::Ident(sprite) = &value.kind
+                    {
+                        self.resource_args
+                            .push((sprite.clone(), value.span, "sprites"));
+                    }
+                    self.visit_expr(value);
+                    return;
+                }
+            }
+            StmtKind::Switch { cases, .. } => {
+                let falls_through: Vec<Span> = cases
+                    .iter()
+                    .take(cases.len().saturating_sub(1))
+                    .filter(|case| case.body.last().is_some_and(|last| !ends_flow(last)))
+                    .map(|case| case.span)
+                    .collect();
+                for span in falls_through {
+                    self.report(
+                        span,
+                        "switch-fallthrough",
+                        "this case runs on into the next one; end it with `break`".into(),
+                    );
+                }
+            }
+            StmtKind::With { target, .. } => {
+                if let ExprKind::Ident(object) = &target.kind {
+                    self.resource_args
+                        .push((object.clone(), target.span, "objects"));
+                }
+            }
+            StmtKind::Try {
+                catch: Some(catch), ..
+            } => {
+                if let Some(binding) = &catch.binding {
+                    self.bound.insert(binding.name.clone());
+                }
+            }
+            _ => {}
+        }
+        ast::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Ident(name) => self.reads.push((name.clone(), expr.span)),
+            ExprKind::Binary { op, .. } if op == "=" => {
+                self.report(
+                    expr.span,
+                    "assign-as-compare",
+                    "`=` compares here; write `==`".into(),
+                );
+            }
+            ExprKind::Call { callee, args } | ExprKind::New { callee, args } => {
+                if let ExprKind::Ident(name) = &callee.kind {
+                    self.calls.push(Call {
+                        name: name.clone(),
+                        span: callee.span,
+                        args: args.len(),
+                        is_new: matches!(expr.kind, ExprKind::New { .. }),
+                    });
+                    for (function, position, kind) in RESOURCE_ARGUMENTS {
+                        if function == name
+                            && let Some(Expr {
+                                kind: ExprKind::Ident(resource),
+                                span,
+                            }) = args.get(*position)
+                        {
+                            self.resource_args.push((resource.clone(), *span, kind));
+                        }
+                    }
+                }
+            }
+            ExprKind::Struct(fields) => {
+                // `{ x }` reads the variable `x`.
+                for field in fields.iter().filter(|f| f.value.is_none()) {
+                    self.reads.push((field.name.name.clone(), field.name.span));
+                }
+            }
+            _ => {}
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        let nested = lint_function(self.context, self.file, function);
+        self.findings.extend(nested);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_index::{Location, ProjectResource, Symbol};
+
+    /// Unknown-resource findings for `source`, in a project with a sprite
+    /// `spr_player` and an object `obj_player`.
+    fn unknown_resources(source: &str) -> Vec<String> {
+        let mut index = ProjectIndex::from_sources(&[("a.gml", source)]);
+        for (name, kind) in [("spr_player", "sprites"), ("obj_player", "objects")] {
+            let yy_path = PathBuf::from(format!("{kind}/{name}/{name}.yy"));
+            index.symbols.push(Symbol {
+                name: name.to_string(),
+                kind: SymbolKind::Resource(kind),
+                location: Location {
+                    file: yy_path.clone(),
+                    line: 1,
+                    column: 1,
+                },
+                span: None,
+            });
+            index.resources.push(ProjectResource {
+                name: name.to_string(),
+                kind,
+                yy_path,
+            });
+        }
+        lint_project(&index, &LintConfig::default())
+            .into_iter()
+            .filter(|f| f.rule == "unknown-resource")
+            .map(|f| f.message)
+            .collect()
+    }
+
+    #[test]
+    fn built_in_instance_variables_are_valid_resources() {
+        let findings = unknown_resources(
+            "draw_sprite(sprite_index, image_index, x, y);\n\
+             draw_sprite_ext(mask_index, 0, x, y, 1, 1, 0, c_white, 1);\n\
+             if (instance_exists(id)) instance_destroy();\n\
+             if (place_meeting(x, y, object_index)) x += 1;\n\
+             room_goto(room);",
+        );
+        assert_eq!(findings, Vec::<String>::new());
+    }
+
+    #[test]
+    fn collision_and_draw_idioms_with_known_resources() {
+        let findings = unknown_resources(
+            "if (place_meeting(x, y + 1, obj_player)) vspeed = 0;\n\
+             var target = instance_nearest(x, y, obj_player);\n\
+             draw_sprite(spr_player, 0, x, y);\n\
+             sprite_index = spr_player;\n\
+             with (obj_player) hp -= 1;",
+        );
+        assert_eq!(findings, Vec::<String>::new());
+    }
+
+    #[test]
+    fn variables_that_do_not_look_like_resources_are_skipped() {
+        let findings = unknown_resources(
+            "function hit(target) {\n    return place_meeting(x, y, target);\n}\n\
+             draw_sprite(current_frame_sprite, 0, x, y);\n\
+             if (instance_exists(enemy)) enemy.hp -= 1;",
+        );
+        assert_eq!(findings, Vec::<String>::new());
+    }
+
+    #[test]
+    fn resource_shaped_names_are_still_checked() {
+        let findings = unknown_resources(
+            "draw_sprite(spr_enemy, 0, x, y);\n\
+             instance_create_layer(0, 0, \"Instances\", spr_player);\n\
+             if (place_meeting(x, y, obj_wall)) x -= 1;",
+        );
+        assert_eq!(
+            findings,
+            [
+                "unknown sprite 'spr_enemy'",
+                "'spr_player' is a sprite, not an object",
+                "unknown object 'obj_wall'",
+            ]
+        );
+    }
+}
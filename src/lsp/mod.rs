@@ -0,0 +1,647 @@
+//! `gmhelper lsp`: a language server over stdio backed by the project symbol
+//! index. Open buffers are indexed as the editor changes them; the disk index
+//! is rebuilt on save so new resources and files are picked up.
+
+mod transport;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+use crate::code_editor::{self, LineExpansion};
+use crate::gml::{self, Span, TokenKind};
+use crate::project_config;
+use crate::snippets::SnippetSet;
+use crate::symbol_index::{ProjectIndex, SymbolKind};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+pub fn run_lsp(project: Option<PathBuf>) {
+    let mut server = Server {
+        yyp: project.map(canonicalize),
+        index: None,
+        open: HashMap::new(),
+        shutdown_requested: false,
+    };
+
+    let mut reader = BufReader::new(io::stdin().lock());
+    let mut stdout = io::stdout().lock();
+    loop {
+        let message = match transport::read_message(&mut reader) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("gmhelper lsp: {e}");
+                break;
+            }
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            std::process::exit(if server.shutdown_requested { 0 } else { 1 });
+        }
+
+        let outgoing = match message.get("id") {
+            Some(id) if !method.is_empty() => {
+                let response = match server.request(method, &message["params"]) {
+                    Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                    Err((code, text)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {"code": code, "message": text},
+                    }),
+                };
+                vec![response]
+            }
+            // Responses to our own requests; we never send any.
+            Some(_) => Vec::new(),
+            None => server.notification(method, &message["params"]),
+        };
+        for message in outgoing {
+            if let Err(e) = transport::write_message(&mut stdout, &message) {
+                eprintln!("gmhelper lsp: {e}");
+                return;
+            }
+        }
+    }
+}
+
+struct Server {
+    yyp: Option<PathBuf>,
+    index: Option<ProjectIndex>,
+    /// Text of every open document, project file or not.
+    open: HashMap<PathBuf, String>,
+    shutdown_requested: bool,
+}
+
+type RequestResult = Result<Value, (i64, String)>;
+
+impl Server {
+    fn request(&mut self, method: &str, params: &Value) -> RequestResult {
+        match method {
+            "initialize" => Ok(self.initialize(params)),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => Ok(self.definition(params)),
+            "textDocument/references" => Ok(self.references(params)),
+            "textDocument/documentSymbol" => Ok(self.document_symbols(params)),
+            "textDocument/completion" => Ok(self.completion(params)),
+            "textDocument/codeAction" => Ok(self.code_actions(params)),
+            _ if self.shutdown_requested => {
+                Err((INVALID_REQUEST, "server is shutting down".into()))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method '{method}'"))),
+        }
+    }
+
+    /// Handle a notification, returning any notifications to send back.
+    fn notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let document = &params["textDocument"];
+        let Some(path) = document["uri"].as_str().and_then(uri_to_path) else {
+            return Vec::new();
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = document["text"].as_str().unwrap_or_default().to_string();
+                self.set_text(&path, text)
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change holds the whole document.
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                match text {
+                    Some(text) => self.set_text(&path, text.to_string()),
+                    None => Vec::new(),
+                }
+            }
+            "textDocument/didSave" => {
+                self.rebuild_index();
+                Vec::new()
+            }
+            "textDocument/didClose" => {
+                self.open.remove(&path);
+                if let (Some(index), Ok(text)) = (&mut self.index, fs::read_to_string(&path)) {
+                    index.update_source(&path, text);
+                }
+                vec![publish_diagnostics(&path, "")]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn initialize(&mut self, params: &Value) -> Value {
+        if self.yyp.is_none() {
+            let root = params["rootUri"]
+                .as_str()
+                .and_then(uri_to_path)
+                .or_else(|| params["rootPath"].as_str().map(PathBuf::from));
+            self.yyp = root.and_then(|root| find_yyp(&root));
+        }
+        self.rebuild_index();
+
+        json!({
+            "capabilities": {
+                "textDocumentSync": {"openClose": true, "change": 1, "save": true},
+                "definitionProvider": true,
+                "referencesProvider": true,
+                "documentSymbolProvider": true,
+                "completionProvider": {"triggerCharacters": ["."]},
+                "codeActionProvider": true,
+            },
+            "serverInfo": {"name": "gmhelper", "version": env!("CARGO_PKG_VERSION")},
+        })
+    }
+
+    fn rebuild_index(&mut self) {
+        let Some(yyp) = &self.yyp else {
+            eprintln!("gmhelper lsp: no .yyp project found; only //: code actions are available");
+            return;
+        };
+        match ProjectIndex::build(yyp) {
+            Ok(mut index) => {
+                for (path, text) in &self.open {
+                    index.update_source(path, text.clone());
+                }
+                self.index = Some(index);
+            }
+            Err(e) => eprintln!("gmhelper lsp: {e}"),
+        }
+    }
+
+    fn set_text(&mut self, path: &Path, text: String) -> Vec<Value> {
+        if let Some(index) = &mut self.index {
+            index.update_source(path, text.clone());
+        }
+        let diagnostics = publish_diagnostics(path, &text);
+        self.open.insert(path.to_path_buf(), text);
+        vec![diagnostics]
+    }
+
+    /// The document's current text: the open buffer, or the file on disk.
+    fn text(&self, path: &Path) -> Option<String> {
+        self.open
+            .get(path)
+            .cloned()
+            .or_else(|| fs::read_to_string(path).ok())
+    }
+
+    /// The project symbol under the cursor, trying `Enum.Member` before the
+    /// bare identifier.
+    fn symbol_at(&self, params: &Value) -> Option<String> {
+        let index = self.index.as_ref()?;
+        let path = params["textDocument"]["uri"]
+            .as_str()
+            .and_then(uri_to_path)?;
+        let text = self.text(&path)?;
+        let offset = position_to_offset(&text, &params["position"]);
+
+        let tokens: Vec<_> = gml::tokenize(&text)
+            .tokens
+            .into_iter()
+            .filter(|t| !t.is_comment())
+            .collect();
+        let at = tokens.iter().position(|t| {
+            t.kind == TokenKind::Ident && t.span.start <= offset && offset <= t.span.end
+        })?;
+        let name = tokens[at].span.text(&text);
+
+        if at >= 2
+            && tokens[at - 1].span.text(&text) == "."
+            && tokens[at - 2].kind == TokenKind::Ident
+        {
+            let qualified = format!("{}.{name}", tokens[at - 2].span.text(&text));
+            if index.definitions(&qualified).next().is_some() {
+                return Some(qualified);
+            }
+        }
+        Some(name.to_string())
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let (Some(index), Some(name)) = (&self.index, self.symbol_at(params)) else {
+            return Value::Null;
+        };
+        let locations: Vec<Value> = index
+            .definitions(&name)
+            .map(|symbol| match symbol.span {
+                Some(span) => span_location(index, &symbol.location.file, span),
+                None => file_start_location(&symbol.location.file),
+            })
+            .collect();
+        Value::Array(locations)
+    }
+
+    fn references(&self, params: &Value) -> Value {
+        let (Some(index), Some(name)) = (&self.index, self.symbol_at(params)) else {
+            return Value::Null;
+        };
+        let include_declaration = params["context"]["includeDeclaration"]
+            .as_bool()
+            .unwrap_or(true);
+
+        let definitions: HashSet<(&Path, usize)> = index
+            .definitions(&name)
+            .filter_map(|s| Some((s.location.file.as_path(), s.span?.start)))
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut locations = Vec::new();
+        let mut add = |path: &Path, span: Span| {
+            let is_definition = definitions.contains(&(path, span.start));
+            if (include_declaration || !is_definition)
+                && seen.insert((path.to_path_buf(), span.start))
+            {
+                locations.push(span_location(index, path, span));
+            }
+        };
+        for (file, span) in index.references(&name) {
+            add(&file.path, span);
+        }
+        for symbol in index.definitions(&name) {
+            if let Some(span) = symbol.span {
+                add(&symbol.location.file, span);
+            }
+        }
+        if include_declaration {
+            locations.extend(
+                index
+                    .definitions(&name)
+                    .filter(|s| s.span.is_none())
+                    .map(|s| file_start_location(&s.location.file)),
+            );
+        }
+        Value::Array(locations)
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let Some(index) = &self.index else {
+            return Value::Array(Vec::new());
+        };
+        let Some(path) = params["textDocument"]["uri"].as_str().and_then(uri_to_path) else {
+            return Value::Array(Vec::new());
+        };
+        let symbols = index
+            .symbols
+            .iter()
+            .filter(|s| s.location.file == path)
+            .filter_map(|symbol| {
+                let span = symbol.span?;
+                let (container, name) = match symbol.name.split_once('.') {
+                    Some((container, name)) => (Some(container), name),
+                    None => (None, symbol.name.as_str()),
+                };
+                Some(json!({
+                    "name": name,
+                    "kind": lsp_symbol_kind(symbol.kind),
+                    "location": span_location(index, &path, span),
+                    "containerName": container,
+                }))
+            })
+            .collect();
+        Value::Array(symbols)
+    }
+
+    /// Resource names and project definitions; after `Enum.`, that enum's
+    /// members.
+    fn completion(&self, params: &Value) -> Value {
+        let Some(index) = &self.index else {
+            return Value::Array(Vec::new());
+        };
+        let path = params["textDocument"]["uri"].as_str().and_then(uri_to_path);
+        let text = path.and_then(|p| self.text(&p)).unwrap_or_default();
+        let offset = position_to_offset(&text, &params["position"]);
+
+        let before = &text[..offset];
+        let word_start = before
+            .rfind(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+            .map_or(0, |i| i + 1);
+        let qualifier = before[..word_start].strip_suffix('.').map(|rest| {
+            let start = rest
+                .rfind(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+                .map_or(0, |i| i + 1);
+            &rest[start..]
+        });
+
+        let mut seen = HashSet::new();
+        let items: Vec<Value> = index
+            .symbols
+            .iter()
+            .filter_map(|symbol| {
+                let label = match (qualifier, symbol.name.split_once('.')) {
+                    (Some(q), Some((enum_name, member))) if q == enum_name => member,
+                    (None, None) => symbol.name.as_str(),
+                    _ => return None,
+                };
+                seen.insert(label.to_string()).then(|| {
+                    json!({
+                        "label": label,
+                        "kind": lsp_completion_kind(symbol.kind),
+                        "detail": symbol.kind.to_string(),
+                    })
+                })
+            })
+            .collect();
+        Value::Array(items)
+    }
+
+    /// A code action per `//:` command line in the requested range.
+    fn code_actions(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(path) = uri_to_path(uri) else {
+            return Value::Array(Vec::new());
+        };
+        let Some(text) = self.text(&path) else {
+            return Value::Array(Vec::new());
+        };
+        let start = params["range"]["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let end = params["range"]["end"]["line"].as_u64().unwrap_or(0) as usize;
+
+        let (style, text) = code_editor::SourceStyle::detect(&text);
+        let program = gml::parse(text);
+        let command_lines = code_editor::command_comment_lines(text, &program);
+        let snippets = SnippetSet::load_for_file(&path);
+
+        let lines: Vec<&str> = text.lines().collect();
+        let mut actions = Vec::new();
+        for number in start..=end.min(lines.len().saturating_sub(1)) {
+            if !command_lines.contains(&(number + 1)) {
+                continue;
+            }
+            let Some(result) =
+                code_editor::expand_command_line(text, &program, &lines, number, &path, &snippets)
+            else {
+                continue;
+            };
+            let title = format!("Expand {}", lines[number].trim());
+            let action = match result {
+                Ok(LineExpansion {
+                    expansion,
+                    first,
+                    last,
+                }) => {
+                    let range = json!({
+                        "start": {"line": first, "character": 0},
+                        "end": {"line": last, "character": utf16_len(lines[last])},
+                    });
+                    json!({
+                        "title": title,
+                        "kind": "refactor.rewrite",
+                        "edit": {"changes": {uri: [{
+                            "range": range,
+                            "newText": expansion.lines.join(style.newline()),
+                        }]}},
+                    })
+                }
+                Err(e) => json!({
+                    "title": title,
+                    "kind": "refactor.rewrite",
+                    "disabled": {"reason": e},
+                }),
+            };
+            actions.push(action);
+        }
+        Value::Array(actions)
+    }
+}
+
+/// A `.yyp` in `root`, or in the nearest folder above it.
+fn find_yyp(root: &Path) -> Option<PathBuf> {
+    let in_root = fs::read_dir(root).ok().and_then(|entries| {
+        entries
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.extension().and_then(|e| e.to_str()) == Some("yyp") && p.is_file())
+    });
+    in_root.or_else(|| project_config::find_project_for(root))
+}
+
+fn publish_diagnostics(path: &Path, text: &str) -> Value {
+    let program = gml::parse(text);
+    let diagnostics: Vec<Value> = program
+        .errors
+        .iter()
+        .map(|error| {
+            json!({
+                "range": span_range(text, error.span),
+                "severity": 1,
+                "source": "gmhelper",
+                "message": error.message,
+            })
+        })
+        .collect();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": path_to_uri(path), "diagnostics": diagnostics},
+    })
+}
+
+fn span_location(index: &ProjectIndex, path: &Path, span: Span) -> Value {
+    let text = index
+        .files
+        .iter()
+        .find(|f| f.path == path)
+        .map(|f| f.source.as_str())
+        .unwrap_or_default();
+    json!({"uri": path_to_uri(path), "range": span_range(text, span)})
+}
+
+/// Resources are defined by their `.yy` as a whole.
+fn file_start_location(path: &Path) -> Value {
+    let start = json!({"line": 0, "character": 0});
+    json!({"uri": path_to_uri(path), "range": {"start": start, "end": start}})
+}
+
+fn span_range(text: &str, span: Span) -> Value {
+    json!({
+        "start": offset_to_position(text, span.start),
+        "end": offset_to_position(text, span.end),
+    })
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.chars().map(char::len_utf16).sum()
+}
+
+/// LSP positions count UTF-16 code units from the start of the line.
+fn offset_to_position(text: &str, offset: usize) -> Value {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..line_start].matches('\n').count();
+    json!({"line": line, "character": utf16_len(&text[line_start..offset])})
+}
+
+fn position_to_offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+
+    let line_start = if line == 0 {
+        0
+    } else {
+        match text.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len(),
+        }
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+    let path = match decoded.as_bytes() {
+        // `file:///C:/...` on Windows.
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => decoded[1..].to_string(),
+        [b'/', ..] => decoded,
+        // `file://server/share/...`, a UNC path.
+        _ => format!("//{decoded}"),
+    };
+    Some(canonicalize(PathBuf::from(path)))
+}
+
+/// `fs::canonicalize`, or `path` itself if that fails. Windows gets plain
+/// `C:\...` paths rather than the `\\?\C:\...` form, as that's what editors
+/// send and expect back.
+fn canonicalize(path: PathBuf) -> PathBuf {
+    match fs::canonicalize(&path) {
+        Ok(canonical) => strip_verbatim(&canonical),
+        Err(_) => path,
+    }
+}
+
+/// `\\?\C:\dir` as `C:\dir` and `\\?\UNC\server\share` as `\\server\share`.
+fn strip_verbatim(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{rest}"))
+    } else if let Some(rest) = text.strip_prefix(r"\\?\") {
+        PathBuf::from(rest)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = strip_verbatim(path).to_string_lossy().replace('\\', "/");
+    // `//server/share` UNC paths keep the server as the URI's authority.
+    let mut uri = String::from(if path.starts_with("//") {
+        "file:"
+    } else if path.starts_with('/') {
+        "file://"
+    } else {
+        "file:///"
+    });
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+fn lsp_symbol_kind(kind: SymbolKind) -> u8 {
+    match kind {
+        SymbolKind::Function => 12,
+        SymbolKind::Constructor => 5,
+        SymbolKind::Macro => 14,
+        SymbolKind::Enum => 10,
+        SymbolKind::EnumMember => 22,
+        SymbolKind::GlobalVariable => 13,
+        SymbolKind::Resource(_) => 2,
+    }
+}
+
+fn lsp_completion_kind(kind: SymbolKind) -> u8 {
+    match kind {
+        SymbolKind::Function => 3,
+        SymbolKind::Constructor => 7,
+        SymbolKind::Macro => 21,
+        SymbolKind::Enum => 13,
+        SymbolKind::EnumMember => 20,
+        SymbolKind::GlobalVariable => 6,
+        SymbolKind::Resource(_) => 9,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_drive_uri_round_trips() {
+        let uri = "file:///C:/Games/My%20Game/scripts/scr_a/scr_a.gml";
+        let path = uri_to_path(uri).unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("C:/Games/My Game/scripts/scr_a/scr_a.gml")
+        );
+        assert_eq!(path_to_uri(&path), uri);
+    }
+
+    #[test]
+    fn verbatim_paths_become_plain_uris() {
+        assert_eq!(
+            path_to_uri(Path::new(r"\\?\C:\Games\game.yyp")),
+            "file:///C:/Games/game.yyp"
+        );
+        assert_eq!(
+            path_to_uri(Path::new(r"\\?\UNC\server\share\game.yyp")),
+            "file://server/share/game.yyp"
+        );
+    }
+
+    #[test]
+    fn unc_uri_round_trips() {
+        let uri = "file://server/share/game.yyp";
+        let path = uri_to_path(uri).unwrap();
+        assert_eq!(path, PathBuf::from("//server/share/game.yyp"));
+        assert_eq!(path_to_uri(&path), uri);
+    }
+
+    #[test]
+    fn existing_file_round_trips_through_its_canonical_path() {
+        let dir = canonicalize(std::env::temp_dir());
+        let file = dir.join(format!("gmhelper lsp {}.gml", std::process::id()));
+        fs::write(&file, "").unwrap();
+        let uri = path_to_uri(&file);
+        let round_trip = uri_to_path(&uri);
+        fs::remove_file(&file).unwrap();
+        assert_eq!(round_trip, Some(file));
+    }
+}
@@ -1,17 +1,35 @@
 mod history;
 mod sprites;
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use ost_export::Mp4ExportOptions;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 
 use crate::aseprite_exporter::{ensure_script_available, export_tags};
+use crate::undo_log::RewriteKind;
 
 mod aseprite_exporter;
 mod code_editor;
+mod code_generators;
+mod data_file;
+mod dev;
+mod gml;
+mod gml_project;
 mod hot_reloader;
+mod jsdoc;
+mod lint;
+mod lsp;
+mod project_config;
+mod rename;
+mod self_writes;
+mod snippets;
+mod sounds;
+mod symbol_index;
+mod undo_log;
 
 const EXPORT_TAGS_SCRIPT: &str = include_str!("../lua/export_tags.lua");
 
@@ -51,6 +69,52 @@ enum SubCmd {
 
         #[arg(short, long, value_name = "IMAGE_PATH")]
         image_path: Option<String>,
+
+        /// Trim leading and trailing silence quieter than DBFS instead of the
+        /// fixed FamiTracker offsets (which keep seamless loops intact)
+        #[arg(
+            long,
+            value_name = "DBFS",
+            num_args = 0..=1,
+            allow_negative_numbers = true
+        )]
+        auto_trim: Option<Option<f64>>,
+
+        /// Silence to keep next to the sound when auto-trimming
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value_t = ost_export::DEFAULT_SILENCE_MARGIN_SECS,
+            requires = "auto_trim"
+        )]
+        trim_margin: f64,
+
+        /// Normalize every OGG to the same EBU R128 integrated loudness (LUFS)
+        #[arg(
+            long,
+            value_name = "LUFS",
+            num_args = 0..=1,
+            allow_negative_numbers = true,
+            conflicts_with = "mp4"
+        )]
+        normalize: Option<Option<f64>>,
+
+        /// True-peak ceiling (dBTP) a normalized song may not exceed
+        #[arg(
+            long,
+            value_name = "DBTP",
+            default_value_t = ost_export::DEFAULT_TRUE_PEAK_CEILING_DBTP,
+            allow_negative_numbers = true,
+            requires = "normalize"
+        )]
+        true_peak: f64,
+
+        /// Path to a GameMaker .yyp project file. When set, the OGG files are
+        /// also imported into the project as sound resources, using the
+        /// `music` settings of its gmhelper.json, and their loop points as
+        /// the `music_loop_points` script.
+        #[arg(short, long, value_name = "YYP_FILE", conflicts_with = "mp4")]
+        project: Option<PathBuf>,
     },
 
     /// Hot-reload: watch .gml files and rebuild + relaunch the game on changes
@@ -58,6 +122,121 @@ enum SubCmd {
         /// Path to the GameMaker .yyp project file
         #[arg(value_name = "YYP_FILE")]
         project: PathBuf,
+
+        #[command(flatten)]
+        options: ReloadArgs,
+    },
+
+    /// Sprite import, code expansion and hot reload from one watcher and one log
+    Dev {
+        /// Path to the GameMaker .yyp project file
+        #[arg(value_name = "YYP_FILE")]
+        project: PathBuf,
+
+        /// Folder of .aseprite files to import. Defaults to `dev.spritesDir`
+        /// in gmhelper.json, then the project folder.
+        #[arg(short, long, value_name = "DIRECTORY")]
+        sprites_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        options: ReloadArgs,
+    },
+
+    /// Show where a function, macro, enum, global or resource is defined and used
+    Refs {
+        /// Name to look up; enum members as `Enum.Member`
+        #[arg(value_name = "SYMBOL")]
+        symbol: String,
+
+        /// Path to the GameMaker .yyp project file
+        #[arg(value_name = "YYP_FILE")]
+        project: PathBuf,
+    },
+
+    /// List the functions, macros, enums, globals and resources a project defines
+    Symbols {
+        /// Path to the GameMaker .yyp project file
+        #[arg(value_name = "YYP_FILE")]
+        project: PathBuf,
+
+        /// Only list symbols whose name contains this text (case-insensitive)
+        #[arg(value_name = "FILTER")]
+        filter: Option<String>,
+    },
+
+    /// Check a project's GML for common mistakes. Exits with status 1 when
+    /// anything is found; rules can be turned off under `lint.rules` in gmhelper.json.
+    Lint {
+        /// Path to the GameMaker .yyp project file
+        #[arg(value_name = "YYP_FILE")]
+        project: PathBuf,
+
+        /// Print findings as a JSON array instead of `file:line:col` lines
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Format .gml files: indentation, braces, operator spacing and blank lines.
+    /// Settings come from `fmt` in the project's gmhelper.json.
+    Fmt {
+        /// .gml files, folders of them, or a .yyp to format all of its code
+        #[arg(value_name = "PATH", required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Only report files that would change; exit with status 1 if any would
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Write an API reference for the project's scripts from their `///`
+    /// doc comments (`@description`, `@param`, `@returns`)
+    Docs {
+        /// Path to the GameMaker .yyp project file
+        #[arg(value_name = "YYP_FILE")]
+        project: PathBuf,
+
+        /// Write HTML instead of Markdown (implied by an .html output file)
+        #[arg(long)]
+        html: bool,
+
+        /// File to write the reference to. Defaults to stdout.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Run a GML language server over stdio for editors such as VS Code and Neovim
+    Lsp {
+        /// Path to the GameMaker .yyp project file. Defaults to the .yyp in
+        /// (or above) the editor's workspace folder.
+        #[arg(value_name = "YYP_FILE")]
+        project: Option<PathBuf>,
+    },
+
+    /// Rename a function, macro, enum, global or resource across the whole project
+    Rename {
+        /// Current name; enum members as `Enum.Member`
+        #[arg(value_name = "OLD")]
+        old: String,
+
+        /// New name
+        #[arg(value_name = "NEW")]
+        new: String,
+
+        /// Path to the GameMaker .yyp project file
+        #[arg(value_name = "YYP_FILE")]
+        project: PathBuf,
+    },
+
+    /// Revert gmhelper's last automatic rewrite of a file (command expansion,
+    /// formatting, rename), as long as the file hasn't been edited since
+    Undo {
+        /// File to revert. Defaults to the most recently rewritten file.
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+
+        /// List the logged rewrites, newest first, instead of undoing one
+        #[arg(long, conflicts_with = "file")]
+        list: bool,
     },
 
     /// List recent gmhelper invocations, or re-run one by number (#1 = most recent)
@@ -68,10 +247,52 @@ enum SubCmd {
     },
 }
 
+/// Build/run options shared by `reload` and `dev`.
+#[derive(Args)]
+struct ReloadArgs {
+    /// Target platform to build for (Windows, Linux/Ubuntu, Mac). Defaults to the host.
+    #[arg(short, long, value_name = "PLATFORM")]
+    target: Option<String>,
+
+    /// Runtime version to build with (e.g. 2024.1400.4.968). Defaults to the newest installed.
+    #[arg(short, long, value_name = "VERSION")]
+    runtime: Option<String>,
+
+    /// Custom shell command to build and run instead of Igor.
+    /// Placeholders: {project}, {project_dir}, {target}
+    #[arg(short, long, value_name = "COMMAND")]
+    build_command: Option<String>,
+
+    /// Only show game output lines containing this text (repeatable)
+    #[arg(short, long, value_name = "TEXT")]
+    include: Vec<String>,
+
+    /// Hide game output lines containing this text (repeatable)
+    #[arg(short, long, value_name = "TEXT")]
+    exclude: Vec<String>,
+
+    /// Push edits to `#macro NAME value //:live` lines to the running game without rebuilding
+    #[arg(long)]
+    live: bool,
+}
+
+impl From<ReloadArgs> for hot_reloader::ReloadOverrides {
+    fn from(args: ReloadArgs) -> Self {
+        Self {
+            target: args.target,
+            runtime: args.runtime,
+            build_command: args.build_command,
+            include: args.include,
+            exclude: args.exclude,
+            live: args.live,
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
-    if !matches!(&cli.command, SubCmd::Previous { .. })
+    if !matches!(&cli.command, SubCmd::Previous { .. } | SubCmd::Lsp { .. })
         && let Err(e) = history::record_current_invocation()
     {
         eprintln!("Warning: could not save command history: {e}");
@@ -87,8 +308,52 @@ fn main() {
             mp4,
             game_name,
             image_path,
-        } => run_music(mp4, game_name, image_path),
-        SubCmd::Reload { project } => hot_reloader::run_reload(project),
+            auto_trim,
+            trim_margin,
+            normalize,
+            true_peak,
+            project,
+        } => {
+            let trim = match auto_trim {
+                Some(threshold_dbfs) => ost_export::TrimMode::Silence {
+                    threshold_dbfs: threshold_dbfs
+                        .unwrap_or(ost_export::DEFAULT_SILENCE_THRESHOLD_DBFS),
+                    margin_secs: trim_margin,
+                },
+                None => ost_export::GameMusicExportOptions::famitracker_defaults().trim,
+            };
+            let loudness = normalize.map(|integrated_lufs| ost_export::LoudnessTarget {
+                integrated_lufs: integrated_lufs.unwrap_or(ost_export::DEFAULT_TARGET_LUFS),
+                true_peak_ceiling_dbtp: true_peak,
+            });
+            let options = ost_export::GameMusicExportOptions { trim, loudness };
+            run_music(mp4, game_name, image_path, options, project.as_deref())
+        }
+        SubCmd::Reload { project, options } => hot_reloader::run_reload(project, options.into()),
+        SubCmd::Dev {
+            project,
+            sprites_dir,
+            options,
+        } => dev::run_dev(project, sprites_dir, options.into()),
+        SubCmd::Refs { symbol, project } => run_refs(&symbol, &project),
+        SubCmd::Symbols { project, filter } => run_symbols(&project, filter.as_deref()),
+        SubCmd::Lint { project, json } => run_lint(&project, json),
+        SubCmd::Fmt { paths, check } => run_fmt(&paths, check),
+        SubCmd::Docs {
+            project,
+            html,
+            output,
+        } => run_docs(&project, html, output.as_deref()),
+        SubCmd::Lsp { project } => lsp::run_lsp(project),
+        SubCmd::Rename { old, new, project } => match rename::rename(&project, &old, &new) {
+            Ok(summary) => print!("{summary}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        },
+        SubCmd::Undo { list: true, .. } => print!("{}", undo_log::list_text(&undo_log::load())),
+        SubCmd::Undo { file, .. } => run_undo(file.as_deref()),
         SubCmd::Previous { index: None } => {
             let h = history::load();
             print!("{}", history::list_text(&h));
@@ -206,13 +471,45 @@ fn run_sprites(directory: Option<PathBuf>, start: bool, project: Option<PathBuf>
 // Music subcommand
 // ---------------------------------------------------------------------------
 
-fn run_music(mp4: bool, game_name: Option<String>, image_path: Option<String>) {
+fn run_music(
+    mp4: bool,
+    game_name: Option<String>,
+    image_path: Option<String>,
+    options: ost_export::GameMusicExportOptions,
+    project: Option<&Path>,
+) {
     let cwd = std::env::current_dir().unwrap_or_else(|e| {
         eprintln!("Error: Failed to get current directory: {e}");
         std::process::exit(1);
     });
+    // Read before exporting so a bad config doesn't waste an export.
+    let music_config = project.map(|project| {
+        project_config::load_for_project(project)
+            .unwrap_or_else(|e| {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            })
+            .music
+    });
 
-    let options = ost_export::GameMusicExportOptions::famitracker_defaults();
+    let mut control = ost_export::ExportControl {
+        on_progress: Some(Box::new(|progress: &ost_export::ExportProgress| {
+            if progress.stage != ost_export::ExportStage::Done {
+                println!(
+                    "  [{}/{}] {}: {}",
+                    progress.index + 1,
+                    progress.total,
+                    progress
+                        .file
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy(),
+                    progress.stage
+                );
+            }
+        })),
+        cancel: None,
+    };
     if mp4 {
         println!("Exporting game music from: {} as MP4 files", cwd.display());
 
@@ -220,11 +517,14 @@ fn run_music(mp4: bool, game_name: Option<String>, image_path: Option<String>) {
         let video_image_path = image_path.expect("You must provide a image_path if exporting mp4");
         let mp4_options = Mp4ExportOptions::defaults(&video_image_path, &game_title);
 
-        match ost_export::export_as_mp4_files(&cwd, &options, &mp4_options) {
-            Ok(result) => println!(
-                "MP4 export complete. Exported {} files.",
-                result.num_files_exported
-            ),
+        match ost_export::export_as_mp4_files(&cwd, &options, &mp4_options, &mut control) {
+            Ok(result) => {
+                print_track_report(&result);
+                println!(
+                    "MP4 export complete. Exported {} files.",
+                    result.num_files_exported
+                );
+            }
             Err(e) => {
                 eprintln!("Error: {e}");
                 std::process::exit(1);
@@ -233,15 +533,313 @@ fn run_music(mp4: bool, game_name: Option<String>, image_path: Option<String>) {
     } else {
         println!("Exporting game music from: {}", cwd.display());
 
-        match ost_export::export_as_game_music(&cwd, &options) {
-            Ok(result) => println!(
-                "Music export complete. Exported {} files.",
-                result.num_files_exported
-            ),
+        match ost_export::export_as_game_music(&cwd, &options, &mut control) {
+            Ok(result) => {
+                print_track_report(&result);
+                if let (Some(project), Some(config)) = (project, &music_config)
+                    && let Err(e) =
+                        sounds::gm_import::import_music_to_project(project, &result.tracks, config)
+                {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+                println!(
+                    "Music export complete. Exported {} files.",
+                    result.num_files_exported
+                );
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// How much was cut off each song, so a bad silence threshold is easy to
+/// spot, where it loops and how loud it was.
+fn print_track_report(result: &ost_export::MusicExportResult) {
+    for track in &result.tracks {
+        println!(
+            "  {}: trimmed {:.3}s from the start and {:.3}s from the end, {:.2}s left",
+            track
+                .source
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy(),
+            track.trimmed_start_secs,
+            track.trimmed_end_secs,
+            track.duration_secs
+        );
+        if let Some(points) = track.loop_points {
+            println!(
+                "    loops {:.3}s..{:.3}s",
+                points.start_secs(),
+                points.end_secs()
+            );
+        }
+        if let Some(loudness) = track.loudness {
+            let measured = loudness.measured;
+            println!(
+                "    measured {:.1} LUFS, {:.1} dBTP peak, {:.1} LU range; gain {:+.1} dB{}",
+                measured.integrated_lufs,
+                measured.true_peak_dbtp,
+                measured.loudness_range_lu,
+                loudness.gain_db,
+                if loudness.peak_limited {
+                    " (limited by the true-peak ceiling)"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Refs / Symbols subcommands
+// ---------------------------------------------------------------------------
+
+fn load_symbol_index(project: &Path) -> symbol_index::ProjectIndex {
+    symbol_index::ProjectIndex::build(project).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    })
+}
+
+fn run_refs(symbol: &str, project: &Path) {
+    let index = load_symbol_index(project);
+
+    let definitions: Vec<_> = index.definitions(symbol).collect();
+    let references: Vec<_> = index
+        .references(symbol)
+        .into_iter()
+        .map(|(file, span)| (file, file.location(span.start)))
+        .filter(|(_, location)| !definitions.iter().any(|d| &d.location == location))
+        .collect();
+
+    if definitions.is_empty() && references.is_empty() {
+        eprintln!("No definitions or references of '{symbol}' found");
+        std::process::exit(1);
+    }
+
+    if definitions.is_empty() {
+        println!("No definition of '{symbol}' in the project (built-in or undeclared)");
+    } else {
+        println!("Definitions ({}):", definitions.len());
+        for definition in &definitions {
+            println!("  {}: {}", definition.location, definition.kind);
+        }
+    }
+    println!("References ({}):", references.len());
+    for (file, location) in &references {
+        println!("  {location}: {}", file.line_text(location.line).trim());
+    }
+}
+
+fn run_symbols(project: &Path, filter: Option<&str>) {
+    let index = load_symbol_index(project);
+    let filter = filter.map(str::to_lowercase);
+
+    let mut symbols: Vec<_> = index
+        .symbols
+        .iter()
+        .filter(|s| {
+            filter
+                .as_ref()
+                .is_none_or(|f| s.name.to_lowercase().contains(f))
+        })
+        .collect();
+    symbols.sort_by_key(|s| s.name.to_lowercase());
+
+    for symbol in &symbols {
+        println!(
+            "{:<12} {:<32} {}",
+            symbol.kind.to_string(),
+            symbol.name,
+            symbol.location
+        );
+    }
+    println!(
+        "{} symbols in {} resources ({} code files)",
+        symbols.len(),
+        index.resources.len(),
+        index.files.len()
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Lint subcommand
+// ---------------------------------------------------------------------------
+
+fn run_lint(project: &Path, json: bool) {
+    let index = load_symbol_index(project);
+    let config = project_config::load_for_project(project).unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    });
+    for rule in config.lint.rules.keys() {
+        if !lint::RULES.contains(&rule.as_str()) {
+            eprintln!(
+                "Warning: unknown lint rule '{rule}' in gmhelper.json (known: {})",
+                lint::RULES.join(", ")
+            );
+        }
+    }
+
+    let findings = lint::lint_project(&index, &config.lint);
+    if json {
+        match serde_json::to_string_pretty(&findings) {
+            Ok(text) => println!("{text}"),
             Err(e) => {
                 eprintln!("Error: {e}");
                 std::process::exit(1);
             }
         }
+    } else {
+        for finding in &findings {
+            println!("{finding}");
+        }
+        println!(
+            "{} problem(s) in {} code file(s)",
+            findings.len(),
+            index.files.len()
+        );
+    }
+    if !findings.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Docs subcommand
+// ---------------------------------------------------------------------------
+
+fn run_docs(project: &Path, html: bool, output: Option<&Path>) {
+    let index = load_symbol_index(project);
+    let title = jsdoc::project_title(project);
+    let html = html
+        || output.is_some_and(|o| {
+            o.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"))
+        });
+    let reference = if html {
+        jsdoc::html_reference(&index, &title)
+    } else {
+        jsdoc::markdown_reference(&index, &title)
+    };
+
+    match output {
+        Some(output) => {
+            if let Err(e) = fs::write(output, reference) {
+                eprintln!("Error: Failed to write {}: {e}", output.display());
+                std::process::exit(1);
+            }
+            println!("Wrote {}", output.display());
+        }
+        None => print!("{reference}"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Undo subcommand
+// ---------------------------------------------------------------------------
+
+fn run_undo(file: Option<&Path>) {
+    match undo_log::undo(file) {
+        Ok(entry) => {
+            println!("Reverted the {} of {}", entry.kind, entry.file.display());
+            if entry.kind == RewriteKind::Rename {
+                println!(
+                    "  Other files changed by the same rename were left as they are; \
+                     rename back with `gmhelper rename` to undo it everywhere."
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Fmt subcommand
+// ---------------------------------------------------------------------------
+
+fn run_fmt(paths: &[PathBuf], check: bool) {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.extension().and_then(|e| e.to_str()) == Some("yyp") {
+            let index = load_symbol_index(path);
+            files.extend(index.files.into_iter().map(|f| f.path));
+        } else if path.is_dir() {
+            files.extend(gml_project::collect_gml_files(path));
+        } else if path.is_file() {
+            files.push(path.clone());
+        } else {
+            eprintln!("Error: '{}' does not exist", path.display());
+            std::process::exit(1);
+        }
+    }
+
+    let mut configs: HashMap<Option<PathBuf>, gml::format::FormatOptions> = HashMap::new();
+    let mut changed = 0;
+    let mut failed = 0;
+    for file in &files {
+        let yyp = std::path::absolute(file)
+            .ok()
+            .and_then(|file| project_config::find_project_for(&file));
+        let options = configs.entry(yyp.clone()).or_insert_with(|| {
+            yyp.as_deref()
+                .and_then(|yyp| project_config::load_for_project(yyp).ok())
+                .unwrap_or_default()
+                .fmt
+                .format_options()
+        });
+
+        let result = fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| {
+                let (style, source) = code_editor::SourceStyle::detect(&raw);
+                let formatted = gml::format::format(source, options)?;
+                let formatted = style.with_bom(&style.convert_newlines(&formatted));
+                Ok((raw, formatted))
+            });
+        let (source, formatted) = match result {
+            Ok(pair) => pair,
+            Err(e) => {
+                eprintln!("{}: skipped: {e}", file.display());
+                failed += 1;
+                continue;
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        changed += 1;
+        if check {
+            println!("Would reformat {}", file.display());
+        } else if let Err(e) =
+            code_editor::rewrite_source(file, formatted.as_bytes(), RewriteKind::Format)
+        {
+            eprintln!("{}: {e}", file.display());
+            failed += 1;
+        } else {
+            println!("Formatted {}", file.display());
+        }
+    }
+
+    println!(
+        "{changed} of {} file(s) {}",
+        files.len(),
+        if check {
+            "need formatting"
+        } else {
+            "reformatted"
+        }
+    );
+    if failed > 0 || (check && changed > 0) {
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,313 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::gml::format::FormatOptions;
+
+/// Name of the optional per-project config file, placed next to the `.yyp`.
+pub const CONFIG_FILE_NAME: &str = "gmhelper.json";
+
+/// Per-project settings read from `gmhelper.json` beside the `.yyp` file.
+/// Every field is optional so an empty (or missing) file means "use defaults".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProjectConfig {
+    pub reload: ReloadConfig,
+    pub dev: DevConfig,
+    pub lint: LintConfig,
+    pub fmt: FmtConfig,
+    pub music: MusicConfig,
+
+    /// `//: name args;` command-comment templates, keyed by command name.
+    /// These override the built-in and user-wide (data dir) snippets.
+    pub snippets: BTreeMap<String, SnippetTemplate>,
+}
+
+/// One `//:` command expansion.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetTemplate {
+    /// Parameter names in positional order; `name=value` gives a default.
+    #[serde(default)]
+    pub params: Vec<String>,
+
+    /// Text to insert, as one string or one entry per line. `${name}` and
+    /// `${1}` insert parameters (`${name:fallback}` with an inline default)
+    /// and `$0` marks where the cursor belongs.
+    pub body: SnippetBody,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SnippetBody {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+impl SnippetBody {
+    pub fn lines(&self) -> Vec<&str> {
+        match self {
+            SnippetBody::Text(text) => text.lines().collect(),
+            SnippetBody::Lines(lines) => lines.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DevConfig {
+    /// Folder of `.aseprite` sources `dev` exports into the project, relative
+    /// to the project folder. Defaults to the project folder itself.
+    pub sprites_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FmtConfig {
+    /// Indent with tabs, as the GameMaker IDE does; otherwise with
+    /// `indentWidth` spaces.
+    pub use_tabs: bool,
+    pub indent_width: usize,
+
+    pub brace_style: BraceStyle,
+
+    /// Longest run of blank lines kept between statements.
+    pub max_blank_lines: usize,
+
+    /// Format `.gml` files whenever `reload`/`dev` see them change.
+    pub format_on_save: bool,
+}
+
+impl Default for FmtConfig {
+    fn default() -> Self {
+        Self {
+            use_tabs: true,
+            indent_width: 4,
+            brace_style: BraceStyle::default(),
+            max_blank_lines: 1,
+            format_on_save: false,
+        }
+    }
+}
+
+impl FmtConfig {
+    pub fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            indent: if self.use_tabs {
+                "\t".to_string()
+            } else {
+                " ".repeat(self.indent_width)
+            },
+            allman: self.brace_style == BraceStyle::Allman,
+            max_blank_lines: self.max_blank_lines,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BraceStyle {
+    /// `{` on its own line, as in the built-in snippets.
+    #[default]
+    Allman,
+    /// `{` at the end of the header line.
+    Kr,
+}
+
+/// Sound resource settings for `music --project`. They apply to sounds the
+/// export creates; a sound that already exists keeps its own settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MusicConfig {
+    pub sound_type: SoundType,
+
+    /// Bit rate in kbps GameMaker recompresses the sound at.
+    pub bit_rate: u32,
+
+    /// Output quality: sample rate in Hz and bit depth (8 or 16).
+    pub sample_rate: u32,
+    pub bit_depth: u8,
+
+    pub audio_group: String,
+
+    /// GameMaker folder the sounds go in, e.g. "Sounds/Music".
+    pub folder: String,
+}
+
+impl Default for MusicConfig {
+    fn default() -> Self {
+        Self {
+            sound_type: SoundType::default(),
+            bit_rate: 128,
+            sample_rate: 44100,
+            bit_depth: 16,
+            audio_group: "audiogroup_default".to_string(),
+            folder: "Sounds/Music".to_string(),
+        }
+    }
+}
+
+/// How GameMaker stores and plays a compressed sound.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SoundType {
+    /// Decoded while playing; little memory, suits long music.
+    #[default]
+    Streamed,
+    /// Decoded once when loaded; no decoding cost while playing.
+    Decompressed,
+    /// Kept compressed in memory and decoded while playing.
+    Compressed,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LintConfig {
+    /// Rule name to on/off, e.g. `"unused-local": false`. Rules not listed
+    /// are on.
+    pub rules: BTreeMap<String, bool>,
+}
+
+impl LintConfig {
+    pub fn is_enabled(&self, rule: &str) -> bool {
+        self.rules.get(rule).copied().unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ReloadConfig {
+    /// Igor target platform, e.g. "Windows", "Linux" (or "Ubuntu"), "Mac".
+    /// Defaults to the host platform.
+    pub target: Option<String>,
+
+    /// Runtime version to build with, e.g. "2024.1400.4.968". Defaults to the
+    /// newest runtime found in the runtime cache.
+    pub runtime: Option<String>,
+
+    /// Extra directory to search for `runtime-*` folders, checked before the
+    /// standard GameMaker cache locations.
+    pub runtimes_dir: Option<PathBuf>,
+
+    /// GameMaker build configuration to compile. Defaults to "Default".
+    pub config: Option<String>,
+
+    /// Use this Igor options file as-is instead of generating one.
+    pub options_file: Option<PathBuf>,
+
+    /// Where Igor writes the compiled game. Defaults to `<tempDir>/output`.
+    pub output_dir: Option<PathBuf>,
+
+    /// Igor scratch directory, also where the generated options file goes.
+    /// Defaults to `build/<project>` in the gmhelper data directory.
+    pub temp_dir: Option<PathBuf>,
+
+    /// The IDE user folder (`<login>_<id>`) holding the licence. Found
+    /// automatically when unset.
+    pub user_dir: Option<PathBuf>,
+
+    /// Process name of the game runner to kill before relaunching.
+    /// Defaults to the target's runner (`Runner.exe` on Windows, `Runner` elsewhere).
+    pub runner_name: Option<String>,
+
+    /// Shell command that replaces Igor entirely. Supports the placeholders
+    /// `{project}`, `{project_dir}` and `{target}`.
+    pub build_command: Option<String>,
+
+    /// Resource folders whose changes trigger a reload, e.g.
+    /// `["scripts", "objects", "sprites"]`. Unset means every common resource
+    /// type plus `datafiles` (included files). The `.yyp` is always watched.
+    pub watch: Option<Vec<String>>,
+
+    /// What a change does while a build is still compiling.
+    pub on_change_during_build: BusyPolicy,
+
+    /// How the running game's output is shown.
+    pub output: OutputConfig,
+
+    /// Push edits to `//:live` macros to the running game without rebuilding.
+    pub live: LiveConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BusyPolicy {
+    /// Kill the in-flight build and start a new one right away.
+    #[default]
+    Cancel,
+    /// Let the in-flight build finish, then rebuild once.
+    Queue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OutputConfig {
+    /// Only show game output lines containing one of these substrings.
+    /// Empty means show everything.
+    pub include: Vec<String>,
+
+    /// Hide game output lines containing any of these substrings.
+    pub exclude: Vec<String>,
+
+    /// Prefix each line with the time since the build started.
+    pub timestamps: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            timestamps: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LiveConfig {
+    /// Install the companion script and serve `#macro NAME value //:live`
+    /// values live. Also enabled by `reload --live`.
+    pub enabled: bool,
+
+    /// The game's save area, where the companion script looks for the live
+    /// values file. Defaults to the per-user folder GameMaker uses for a game
+    /// named after the project.
+    pub save_dir: Option<PathBuf>,
+}
+
+/// Load the config for the project whose `.yyp` lives at `yyp_path`.
+/// A missing file yields the defaults; a malformed one is an error.
+pub fn load_for_project(yyp_path: &Path) -> Result<ProjectConfig, String> {
+    let Some(config_path) = config_path_for_project(yyp_path) else {
+        return Ok(ProjectConfig::default());
+    };
+    if !config_path.exists() {
+        return Ok(ProjectConfig::default());
+    }
+
+    let content = fs::read_to_string(&config_path)
+        .map_err(|e| format!("Failed to read {}: {e}", config_path.display()))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse {}: {e}", config_path.display()))
+}
+
+pub fn config_path_for_project(yyp_path: &Path) -> Option<PathBuf> {
+    yyp_path.parent().map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+/// The `.yyp` of the project containing `path`, found by walking up its
+/// ancestors.
+pub fn find_project_for(path: &Path) -> Option<PathBuf> {
+    path.ancestors().skip(1).find_map(|dir| {
+        fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .map(|e| e.path())
+            .find(|p| p.extension().and_then(|e| e.to_str()) == Some("yyp") && p.is_file())
+    })
+}
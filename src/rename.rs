@@ -0,0 +1,484 @@
+//! Project-wide rename of a GML symbol or resource, built on the symbol index.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::code_editor;
+use crate::gml::{self, Span};
+use crate::self_writes;
+use crate::sprites::gm_import;
+use crate::symbol_index::{ProjectIndex, ProjectResource, SymbolKind};
+use crate::undo_log::{self, RewriteKind};
+
+pub struct RenameSummary {
+    pub old: String,
+    pub new: String,
+    /// Code edits per file, in file order.
+    pub code_edits: Vec<(PathBuf, usize)>,
+    /// Resource folder moves, old path to new path.
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    /// `.yy` / `.yyp` files whose references were updated.
+    pub metadata_files: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for RenameSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let edits: usize = self.code_edits.iter().map(|(_, n)| n).sum();
+        writeln!(
+            f,
+            "Renamed '{}' to '{}': {} edit(s) in {} code file(s)",
+            self.old,
+            self.new,
+            edits,
+            self.code_edits.len()
+        )?;
+        for (file, count) in &self.code_edits {
+            writeln!(f, "  {}: {count}", file.display())?;
+        }
+        for (from, to) in &self.moved {
+            writeln!(f, "  moved {} -> {}", from.display(), to.display())?;
+        }
+        for file in &self.metadata_files {
+            writeln!(f, "  updated {}", file.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Rename `old` to `new` everywhere in the project: GML usages (never inside
+/// strings or comments), and when `old` is a resource, its folder, `.yy`
+/// names and every `.yy`/`.yyp` reference to it. Enum members are given as
+/// `Enum.Member`; `new` may be the bare member name or qualified the same way.
+pub fn rename(yyp_path: &Path, old: &str, new: &str) -> Result<RenameSummary, String> {
+    let index = ProjectIndex::build(yyp_path)?;
+
+    let definitions: Vec<_> = index.definitions(old).collect();
+    if definitions.is_empty() {
+        return Err(format!(
+            "'{old}' is not defined in this project; built-ins and undeclared names can't be renamed"
+        ));
+    }
+
+    let new_name = match old.rsplit_once('.') {
+        Some((enum_name, _)) => new
+            .strip_prefix(enum_name)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .unwrap_or(new),
+        None => new,
+    };
+    validate_identifier(new_name)?;
+    let new_full = match old.rsplit_once('.') {
+        Some((enum_name, _)) => format!("{enum_name}.{new_name}"),
+        None => new_name.to_string(),
+    };
+    if let Some(existing) = index.definitions(&new_full).next() {
+        return Err(format!(
+            "'{new_full}' is already defined as a {} at {}",
+            existing.kind, existing.location
+        ));
+    }
+
+    let resource = index.resources.iter().find(|r| r.name == old).filter(|_| {
+        definitions
+            .iter()
+            .any(|d| matches!(d.kind, SymbolKind::Resource(_)))
+    });
+    let moves = match resource {
+        Some(resource) => resource_moves(&index.project_dir, resource, new_name)?,
+        None => Vec::new(),
+    };
+
+    // Work out every edit before writing anything, so an error can't leave
+    // the project half renamed.
+    let code = code_edits(&index, old, new_name)?;
+    let metadata = match resource {
+        Some(resource) => metadata_edits(&index, yyp_path, resource, new_name)?,
+        None => Vec::new(),
+    };
+
+    let mut summary = RenameSummary {
+        old: old.to_string(),
+        new: new_full.clone(),
+        code_edits: Vec::new(),
+        moved: Vec::new(),
+        metadata_files: Vec::new(),
+    };
+
+    // Code first, while every file is still at its indexed path.
+    for (path, source, count) in code {
+        code_editor::rewrite_source(&path, source.as_bytes(), RewriteKind::Rename)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        summary.code_edits.push((path, count));
+    }
+    for (path, json) in metadata {
+        self_writes::record(&path, json.as_bytes());
+        code_editor::replace_via_temp(&path, json.as_bytes())
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        summary.metadata_files.push(path);
+    }
+
+    if let Some(resource) = resource {
+        for (from, to) in moves {
+            fs::rename(&from, &to).map_err(|e| {
+                format!("Failed to move {} to {}: {e}", from.display(), to.display())
+            })?;
+            if let Err(e) = undo_log::relocate(&from, &to) {
+                eprintln!("Warning: could not update the rewrite log: {e}");
+            }
+            summary.moved.push((from, to));
+        }
+        // Report files where they are now, not where they were edited.
+        let own_yy = index.project_dir.join(&resource.yy_path);
+        let new_yy_path = format!("{}/{new_name}/{new_name}.yy", resource.kind);
+        let old_dir = own_yy.parent().unwrap_or(&index.project_dir);
+        let new_dir = index
+            .project_dir
+            .join(format!("{}/{new_name}", resource.kind));
+        let files = summary
+            .code_edits
+            .iter_mut()
+            .map(|(file, _)| file)
+            .chain(summary.metadata_files.iter_mut());
+        for file in files {
+            if *file == own_yy {
+                *file = index.project_dir.join(&new_yy_path);
+            } else if let Ok(rest) = file.strip_prefix(old_dir) {
+                *file = new_dir.join(rest);
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// The renamed text of every code file that uses `old`, with its number of
+/// edits, in file order. Only uses that resolve to the global symbol change:
+/// locals of the same name, `.field` accesses and struct keys are left alone.
+/// Fails if a scope using `old` declares a local `new_name`, which would
+/// capture the renamed uses.
+fn code_edits(
+    index: &ProjectIndex,
+    old: &str,
+    new_name: &str,
+) -> Result<Vec<(PathBuf, String, usize)>, String> {
+    // An enum member is always qualified, so no local can capture it.
+    if !old.contains('.')
+        && let Some((file, span)) = index.local_conflicts(old, new_name).first()
+    {
+        return Err(format!(
+            "'{new_name}' is declared as a local at {} in a scope that uses '{old}'; the renamed uses would refer to that local",
+            file.location(span.start)
+        ));
+    }
+
+    let mut spans: BTreeMap<&Path, BTreeSet<(usize, usize)>> = BTreeMap::new();
+    for (file, span) in index.references(old) {
+        spans
+            .entry(file.path.as_path())
+            .or_default()
+            .insert((span.start, span.end));
+    }
+    for definition in index.definitions(old) {
+        if let Some(span) = definition.span {
+            spans
+                .entry(definition.location.file.as_path())
+                .or_default()
+                .insert((span.start, span.end));
+        }
+    }
+
+    let mut edits = Vec::new();
+    for (path, spans) in spans {
+        let Some(file) = index.files.iter().find(|f| f.path == path) else {
+            continue;
+        };
+        let mut source = file.source.clone();
+        for &(start, end) in spans.iter().rev() {
+            // `{ old }` keeps its key: `{ old: new }`.
+            let replacement = if file.is_struct_shorthand(Span::new(start, end)) {
+                format!("{}: {new_name}", &file.source[start..end])
+            } else {
+                new_name.to_string()
+            };
+            source.replace_range(start..end, &replacement);
+        }
+        edits.push((path.to_path_buf(), source, spans.len()));
+    }
+    Ok(edits)
+}
+
+/// The new contents of every `.yy`/`.yyp` that names `resource`.
+fn metadata_edits(
+    index: &ProjectIndex,
+    yyp_path: &Path,
+    resource: &ProjectResource,
+    new_name: &str,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    let new_yy_path = format!("{}/{new_name}/{new_name}.yy", resource.kind);
+    let old_yy_path = resource.yy_path.to_string_lossy().replace('\\', "/");
+
+    let mut metadata: Vec<PathBuf> = index
+        .resources
+        .iter()
+        .map(|r| index.project_dir.join(&r.yy_path))
+        .collect();
+    metadata.push(yyp_path.to_path_buf());
+    let own_yy = index.project_dir.join(&resource.yy_path);
+
+    let mut edits = Vec::new();
+    for path in metadata {
+        let Ok(mut value) = gm_import::read_project_value(&path) else {
+            continue;
+        };
+        let mut changed = replace_resource_refs(
+            &mut value,
+            &resource.name,
+            &old_yy_path,
+            new_name,
+            &new_yy_path,
+        );
+        if path == own_yy {
+            changed |= rename_own_yy(&mut value, &resource.name, new_name);
+        }
+        if changed {
+            let json = serde_json::to_string_pretty(&value)
+                .map_err(|e| format!("Failed to serialize {}: {e}", path.display()))?;
+            edits.push((path, json));
+        }
+    }
+    Ok(edits)
+}
+
+fn validate_identifier(name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+    if !valid {
+        return Err(format!("'{name}' is not a valid GML identifier"));
+    }
+    if gml::is_keyword(name) {
+        return Err(format!("'{name}' is a GML keyword"));
+    }
+    Ok(())
+}
+
+/// Moves that rename a resource: files named after it inside its folder,
+/// then the folder itself. Checked up front so nothing is written when the
+/// new name is taken.
+fn resource_moves(
+    project_dir: &Path,
+    resource: &ProjectResource,
+    new_name: &str,
+) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let Some(old_dir) = resource.yy_path.parent().map(|d| project_dir.join(d)) else {
+        return Ok(Vec::new());
+    };
+    let new_dir = old_dir.with_file_name(new_name);
+    if new_dir.exists() {
+        return Err(format!("'{}' already exists", new_dir.display()));
+    }
+
+    let mut moves = Vec::new();
+    for extension in ["yy", "gml"] {
+        let from = old_dir.join(format!("{}.{extension}", resource.name));
+        if from.is_file() {
+            moves.push((from, old_dir.join(format!("{new_name}.{extension}"))));
+        }
+    }
+    moves.push((old_dir, new_dir));
+    Ok(moves)
+}
+
+/// Point every `{"name": old, "path": old_path}` reference at the new
+/// resource. Returns whether anything changed.
+fn replace_resource_refs(
+    value: &mut serde_json::Value,
+    old: &str,
+    old_path: &str,
+    new: &str,
+    new_path: &str,
+) -> bool {
+    match value {
+        serde_json::Value::Object(map) => {
+            let is_ref = map.get("name").and_then(|v| v.as_str()) == Some(old)
+                && map.get("path").and_then(|v| v.as_str()) == Some(old_path);
+            if is_ref {
+                map.insert("name".into(), new.into());
+                map.insert("path".into(), new_path.into());
+                return true;
+            }
+            let mut changed = false;
+            for child in map.values_mut() {
+                changed |= replace_resource_refs(child, old, old_path, new, new_path);
+            }
+            changed
+        }
+        serde_json::Value::Array(items) => {
+            let mut changed = false;
+            for item in items {
+                changed |= replace_resource_refs(item, old, old_path, new, new_path);
+            }
+            changed
+        }
+        _ => false,
+    }
+}
+
+/// The resource's own `name` and `%Name` fields.
+fn rename_own_yy(value: &mut serde_json::Value, old: &str, new: &str) -> bool {
+    let Some(map) = value.as_object_mut() else {
+        return false;
+    };
+    let mut changed = false;
+    for key in ["name", "%Name"] {
+        if map.get(key).and_then(|v| v.as_str()) == Some(old) {
+            map.insert(key.into(), new.into());
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamed(files: &[(&str, &str)], old: &str, new: &str) -> Vec<(String, String)> {
+        let index = ProjectIndex::from_sources(files);
+        code_edits(&index, old, new)
+            .unwrap()
+            .into_iter()
+            .map(|(path, source, _)| (path.display().to_string(), source))
+            .collect()
+    }
+
+    #[test]
+    fn locals_of_the_same_name_are_left_alone() {
+        let edits = renamed(
+            &[
+                ("a.gml", "function hp_max() {\n    return 10;\n}"),
+                ("b.gml", "var hp_max = 5;\nx = hp_max;"),
+                (
+                    "c.gml",
+                    "function f(hp_max) {\n    return hp_max;\n}\ny = hp_max();",
+                ),
+            ],
+            "hp_max",
+            "max_health",
+        );
+        assert_eq!(
+            edits,
+            [
+                (
+                    "a.gml".into(),
+                    "function max_health() {\n    return 10;\n}".into()
+                ),
+                (
+                    "c.gml".into(),
+                    "function f(hp_max) {\n    return hp_max;\n}\ny = max_health();".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn field_accesses_are_left_alone() {
+        let edits = renamed(
+            &[(
+                "a.gml",
+                "globalvar speed_cap;\nspeed_cap = 4;\nother.speed_cap = 2;",
+            )],
+            "speed_cap",
+            "max_speed",
+        );
+        assert_eq!(
+            edits,
+            [(
+                "a.gml".into(),
+                "globalvar max_speed;\nmax_speed = 4;\nother.speed_cap = 2;".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn struct_keys_are_left_alone() {
+        let edits = renamed(
+            &[(
+                "a.gml",
+                "#macro SIZE 4\nvar a = { SIZE: SIZE };\nvar b = { SIZE };",
+            )],
+            "SIZE",
+            "CELL",
+        );
+        assert_eq!(
+            edits,
+            [(
+                "a.gml".into(),
+                "#macro CELL 4\nvar a = { SIZE: CELL };\nvar b = { SIZE: CELL };".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn enum_members_rename_only_the_qualified_access() {
+        let edits = renamed(
+            &[(
+                "a.gml",
+                "enum eState { Idle, Run }\nstate = eState.Idle;\nvar Idle = 1;\nother.Idle = Idle;",
+            )],
+            "eState.Idle",
+            "Rest",
+        );
+        assert_eq!(
+            edits,
+            [(
+                "a.gml".into(),
+                "enum eState { Rest, Run }\nstate = eState.Rest;\nvar Idle = 1;\nother.Idle = Idle;"
+                    .into()
+            )]
+        );
+    }
+
+    #[test]
+    fn refuses_when_a_local_would_capture_the_renamed_uses() {
+        let index = ProjectIndex::from_sources(&[
+            (
+                "scr_move.gml",
+                "function move(a, b, c) {\n    return a + b + c;\n}",
+            ),
+            ("player.gml", "x = 1;\nvar sp = move(1, 2, 3);"),
+        ]);
+        let error = code_edits(&index, "move", "sp").unwrap_err();
+        assert!(error.contains("player.gml:2:5"), "{error}");
+
+        // The same local in a scope that doesn't use `move` is fine.
+        let index = ProjectIndex::from_sources(&[
+            (
+                "scr_move.gml",
+                "function move(a, b, c) {\n    return a + b + c;\n}",
+            ),
+            (
+                "player.gml",
+                "var sp = 4;\nfunction f(sp) {\n    return sp;\n}",
+            ),
+            ("enemy.gml", "y = move(1, 2, 3);"),
+        ]);
+        assert!(code_edits(&index, "move", "sp").is_ok());
+    }
+
+    #[test]
+    fn parameters_and_catch_bindings_would_capture_too() {
+        let index = ProjectIndex::from_sources(&[
+            ("scr_move.gml", "function move(a) {\n    return a;\n}"),
+            ("a.gml", "function f(step) {\n    return move(step);\n}"),
+            ("b.gml", "try { move(1); } catch (err) { move(2); }"),
+        ]);
+        let error = code_edits(&index, "move", "step").unwrap_err();
+        assert!(error.contains("a.gml:1:12"), "{error}");
+        let error = code_edits(&index, "move", "err").unwrap_err();
+        assert!(error.contains("b.gml:1:25"), "{error}");
+    }
+}
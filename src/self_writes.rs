@@ -0,0 +1,104 @@
+//! Registry of files gmhelper itself just wrote, so file watchers can tell
+//! their own output apart from user edits and don't react to it in a loop.
+//!
+//! Writes are also recorded in `<data dir>/self_writes.json`, so a watcher
+//! recognises writes made by another gmhelper process too, such as a
+//! separate `gmhelper sprites` run or `gmhelper undo`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{data_file, history};
+
+const FILE_NAME: &str = "self_writes.json";
+/// Watchers only need an entry until they've seen the write's events, so
+/// the shared file keeps just the most recent writes.
+const MAX_SHARED_ENTRIES: usize = 500;
+
+/// Content hash of the last write this process made to each path.
+fn registry() -> &'static Mutex<HashMap<PathBuf, u64>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<PathBuf, u64>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SharedWrites {
+    /// Oldest first; at most one entry per path.
+    entries: Vec<SharedWrite>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SharedWrite {
+    path: PathBuf,
+    hash: u64,
+}
+
+fn shared_path() -> Option<PathBuf> {
+    history::data_dir().ok().map(|dir| dir.join(FILE_NAME))
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is the same in every build, so
+/// processes from different gmhelper versions agree on it.
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Remember that gmhelper wrote `contents` to `path`.
+pub fn record(path: &Path, contents: &[u8]) {
+    let key = key(path);
+    let hash = hash_bytes(contents);
+    if let Ok(mut map) = registry().lock() {
+        map.insert(key.clone(), hash);
+    }
+    // Best effort: without the shared file only this process recognises it.
+    if let Some(shared) = shared_path() {
+        let _ = data_file::update(&shared, |writes: &mut SharedWrites| {
+            writes.entries.retain(|w| w.path != key);
+            writes.entries.push(SharedWrite { path: key, hash });
+            let excess = writes.entries.len().saturating_sub(MAX_SHARED_ENTRIES);
+            writes.entries.drain(..excess);
+        });
+    }
+}
+
+/// Remember a file gmhelper just wrote through some other API (e.g. an image
+/// encoder), by reading back what is on disk.
+pub fn record_file(path: &Path) {
+    if let Ok(contents) = fs::read(path) {
+        record(path, &contents);
+    }
+}
+
+/// `true` if the file on disk is still exactly what gmhelper (this process
+/// or another) last wrote there. Content is compared rather than timestamps,
+/// so duplicate watcher events for one write are all recognised and a later
+/// user edit never is.
+pub fn is_own_write(path: &Path) -> bool {
+    let key = key(path);
+    let Ok(contents) = fs::read(path) else {
+        return false;
+    };
+    let hash = hash_bytes(&contents);
+    if registry()
+        .lock()
+        .is_ok_and(|map| map.get(&key) == Some(&hash))
+    {
+        return true;
+    }
+    shared_path().is_some_and(|shared| {
+        data_file::read::<SharedWrites>(&shared)
+            .entries
+            .iter()
+            .any(|w| w.path == key && w.hash == hash)
+    })
+}
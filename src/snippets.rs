@@ -0,0 +1,258 @@
+//! Templates behind `//: name args;` command comments: built-ins, then the
+//! user's `snippets.json` in the gmhelper data dir, then the project's
+//! `gmhelper.json`, each overriding the one before by name.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::history;
+use crate::project_config::{self, SnippetBody, SnippetTemplate};
+
+/// User-wide snippet file in the gmhelper data directory.
+pub const USER_SNIPPETS_FILE: &str = "snippets.json";
+
+#[derive(Debug, Clone, Default)]
+pub struct SnippetSet {
+    templates: BTreeMap<String, SnippetTemplate>,
+}
+
+/// A rendered snippet, not yet indented.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub lines: Vec<String>,
+    /// `(line, column)` of the `$0` marker within `lines`.
+    pub cursor: Option<(usize, usize)>,
+}
+
+impl SnippetSet {
+    /// Snippets that apply to `file`, re-read on every call so edits to the
+    /// snippet files take effect without restarting gmhelper.
+    pub fn load_for_file(file: &Path) -> Self {
+        let mut set = Self::builtin();
+
+        if let Some(path) = user_snippets_path()
+            && path.exists()
+        {
+            match read_snippet_file(&path) {
+                Ok(templates) => set.templates.extend(templates),
+                Err(e) => eprintln!("Warning: {e}"),
+            }
+        }
+
+        if let Some(yyp) = project_config::find_project_for(file) {
+            match project_config::load_for_project(&yyp) {
+                Ok(config) => set.templates.extend(config.snippets),
+                Err(e) => eprintln!("Warning: {e}"),
+            }
+        }
+
+        set
+    }
+
+    pub fn builtin() -> Self {
+        let mut templates = BTreeMap::new();
+        templates.insert(
+            "for".to_string(),
+            SnippetTemplate {
+                params: vec!["array".to_string(), "index=i".to_string()],
+                body: SnippetBody::Lines(
+                    [
+                        "for (var ${index} = 0; ${index} < array_length(${array}); ${index}++)",
+                        "{",
+                        "\t$0",
+                        "}",
+                    ]
+                    .map(String::from)
+                    .to_vec(),
+                ),
+                description: Some("Loop over an array by index".to_string()),
+            },
+        );
+        templates.insert(
+            "foreach".to_string(),
+            SnippetTemplate {
+                params: vec!["array".to_string(), "item=item".to_string()],
+                body: SnippetBody::Lines(
+                    [
+                        "for (var _${item}_index = 0; _${item}_index < array_length(${array}); _${item}_index++)",
+                        "{",
+                        "\tvar ${item} = ${array}[_${item}_index];",
+                        "\t$0",
+                        "}",
+                    ]
+                    .map(String::from)
+                    .to_vec(),
+                ),
+                description: Some("Loop over an array, binding each element".to_string()),
+            },
+        );
+        Self { templates }
+    }
+
+    /// Expand `command` (the text between `//:` and `;`). `None` if no snippet
+    /// has that name; an error if its arguments don't fit the template.
+    pub fn expand(&self, command: &str) -> Option<Result<Expansion, String>> {
+        let mut tokens = split_args(command).into_iter();
+        let name = tokens.next()?;
+        let template = self.templates.get(&name)?;
+        Some(render(&name, template, tokens.collect()))
+    }
+}
+
+fn user_snippets_path() -> Option<PathBuf> {
+    history::data_dir()
+        .ok()
+        .map(|dir| dir.join(USER_SNIPPETS_FILE))
+}
+
+fn read_snippet_file(path: &Path) -> Result<BTreeMap<String, SnippetTemplate>, String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse {}: {e}", path.display()))
+}
+
+fn render(name: &str, template: &SnippetTemplate, args: Vec<String>) -> Result<Expansion, String> {
+    let mut positional = Vec::new();
+    let mut named = HashMap::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some((key, value)) if is_identifier(key) => {
+                named.insert(key.to_string(), value.to_string());
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    if !template.params.is_empty() && positional.len() > template.params.len() {
+        return Err(format!(
+            "snippet '{name}' takes at most {} argument{}, got {}",
+            template.params.len(),
+            if template.params.len() == 1 { "" } else { "s" },
+            positional.len()
+        ));
+    }
+
+    let mut values = HashMap::new();
+    for (i, param) in template.params.iter().enumerate() {
+        let (param_name, default) = match param.split_once('=') {
+            Some((n, d)) => (n.trim(), Some(d.trim())),
+            None => (param.trim(), None),
+        };
+        let value = named
+            .remove(param_name)
+            .or_else(|| positional.get(i).cloned())
+            .or_else(|| default.map(str::to_string));
+        if let Some(value) = value {
+            values.insert((i + 1).to_string(), value.clone());
+            values.insert(param_name.to_string(), value);
+        }
+    }
+    if let Some(unknown) = named.keys().next()
+        && !template.params.is_empty()
+    {
+        return Err(format!("snippet '{name}' has no parameter '{unknown}'"));
+    }
+    // Templates without a params list address everything directly.
+    values.extend(named);
+    for (i, value) in positional.into_iter().enumerate() {
+        values.entry((i + 1).to_string()).or_insert(value);
+    }
+
+    let mut lines = Vec::new();
+    let mut cursor = None;
+    for (line_index, line) in template.body.lines().into_iter().enumerate() {
+        let (rendered, column) = render_line(name, line, &values)?;
+        if let Some(column) = column
+            && cursor.is_none()
+        {
+            cursor = Some((line_index, column));
+        }
+        lines.push(rendered);
+    }
+
+    Ok(Expansion { lines, cursor })
+}
+
+/// Substitute `${key}` / `${key:default}` and strip `$0`, returning the
+/// column the marker was at. Any other `$` (hex literals, template strings)
+/// is left alone.
+fn render_line(
+    name: &str,
+    line: &str,
+    values: &HashMap<String, String>,
+) -> Result<(String, Option<usize>), String> {
+    let mut out = String::with_capacity(line.len());
+    let mut cursor = None;
+    let mut rest = line;
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(inner) = after.strip_prefix('{')
+            && let Some(end) = inner.find('}')
+        {
+            let placeholder = &inner[..end];
+            let (key, default) = match placeholder.split_once(':') {
+                Some((k, d)) => (k.trim(), Some(d)),
+                None => (placeholder.trim(), None),
+            };
+            let value = values
+                .get(key)
+                .map(String::as_str)
+                .or(default)
+                .ok_or_else(|| format!("snippet '{name}' needs a value for '{key}'"))?;
+            out.push_str(value);
+            rest = &inner[end + 1..];
+        } else if let Some(tail) = after.strip_prefix('0')
+            && !tail.starts_with(|c: char| c.is_ascii_alphanumeric())
+        {
+            cursor.get_or_insert(out.len());
+            rest = tail;
+        } else {
+            out.push('$');
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+
+    Ok((out, cursor))
+}
+
+/// Whitespace-separated arguments; double quotes group words with spaces.
+pub fn split_args(command: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+
+    for c in command.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    args.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if has_token {
+        args.push(current);
+    }
+    args
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
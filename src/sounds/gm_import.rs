@@ -0,0 +1,233 @@
+use std::fs;
+use std::path::Path;
+
+use ost_export::{ExportedTrack, LOOP_HELPER_FILE_NAME, LoopPoints};
+
+use super::models::gm_sound_model::GMSoundModel;
+use crate::project_config::MusicConfig;
+use crate::self_writes;
+use crate::sprites::gm_import::{
+    ensure_gm_folders_value, read_project_value, strip_trailing_commas, upsert_resource,
+    write_project_value,
+};
+use crate::sprites::models::gm_sprite_model::ResourceReference;
+
+/// Import exported songs into a GameMaker project as sound resources,
+/// creating `sounds/{name}/{name}.yy` or updating the one already there, and
+/// their loop points as the `music_loop_points` script.
+///
+/// * `project_path` - path to the `.yyp` file
+/// * `tracks`       - the songs `export_as_game_music` wrote, named after their OGG
+/// * `config`       - settings for sounds that don't exist yet
+pub fn import_music_to_project(
+    project_path: &Path,
+    tracks: &[ExportedTrack],
+    config: &MusicConfig,
+) -> Result<(), String> {
+    let project_dir = project_path
+        .parent()
+        .ok_or_else(|| "Could not determine project directory from .yyp path".to_string())?;
+
+    let mut project = read_project_value(project_path)?;
+
+    for track in tracks {
+        let sound_name = track
+            .output
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| format!("Invalid sound file name: {}", track.output.display()))?;
+        let sound_file = format!("{sound_name}.ogg");
+
+        let sound_dir = project_dir.join("sounds").join(sound_name);
+        let overrides = read_sound_overrides(&sound_dir, sound_name);
+
+        fs::create_dir_all(&sound_dir)
+            .map_err(|e| format!("Failed to create sound directory: {e}"))?;
+        let audio_path = sound_dir.join(&sound_file);
+        fs::copy(&track.output, &audio_path)
+            .map_err(|e| format!("Failed to copy {}: {e}", track.output.display()))?;
+        self_writes::record_file(&audio_path);
+
+        let mut sound_model = GMSoundModel::new(
+            sound_name,
+            &sound_file,
+            track.duration_secs,
+            track.channels,
+            folder_reference(&config.folder),
+            config,
+        );
+
+        // An existing sound keeps whatever was set on it in the IDE.
+        let is_update = overrides.is_some();
+        if let Some(ov) = overrides {
+            sound_model.audio_group_id = ov.audio_group_id;
+            sound_model.bit_depth = ov.bit_depth;
+            sound_model.bit_rate = ov.bit_rate;
+            sound_model.compression = ov.compression;
+            sound_model.conversion_mode = ov.conversion_mode;
+            sound_model.parent = ov.parent;
+            sound_model.preload = ov.preload;
+            sound_model.sample_rate = ov.sample_rate;
+            sound_model.sound_type = ov.sound_type;
+            sound_model.volume = ov.volume;
+        } else {
+            ensure_gm_folders_value(&mut project, &config.folder)?;
+            ensure_audio_group_value(&mut project, &config.audio_group)?;
+        }
+
+        let yy_path = sound_dir.join(format!("{sound_name}.yy"));
+        let yy_json = serde_json::to_string_pretty(&sound_model)
+            .map_err(|e| format!("Failed to serialize sound .yy: {e}"))?;
+        self_writes::record(&yy_path, yy_json.as_bytes());
+        fs::write(&yy_path, &yy_json).map_err(|e| format!("Failed to write sound .yy: {e}"))?;
+
+        let resource_path = format!("sounds/{sound_name}/{sound_name}.yy");
+        upsert_resource(&mut project, sound_name, &resource_path)?;
+
+        if is_update {
+            println!("  Updated sound '{sound_name}' (kept its settings)");
+        } else {
+            println!("  Imported sound '{sound_name}'");
+        }
+    }
+
+    import_loop_helper(project_dir, &mut project, tracks, config)?;
+
+    write_project_value(project_path, &project)
+}
+
+/// Write the loop points of `tracks` as the `music_loop_points` script,
+/// rewritten on every import so songs that lost their loop drop out of it.
+/// A script that already exists keeps its `.yy`, and so its place in the
+/// IDE's folders.
+fn import_loop_helper(
+    project_dir: &Path,
+    project: &mut serde_json::Value,
+    tracks: &[ExportedTrack],
+    config: &MusicConfig,
+) -> Result<(), String> {
+    let script_name = LOOP_HELPER_FILE_NAME.trim_end_matches(".gml");
+    let loops: Vec<(String, LoopPoints)> = tracks
+        .iter()
+        .filter_map(|track| {
+            let sound_name = track.output.file_stem()?.to_string_lossy().into_owned();
+            Some((sound_name, track.loop_points?))
+        })
+        .collect();
+
+    let script_dir = project_dir.join("scripts").join(script_name);
+    fs::create_dir_all(&script_dir)
+        .map_err(|e| format!("Failed to create script directory: {e}"))?;
+
+    let gml_path = script_dir.join(LOOP_HELPER_FILE_NAME);
+    let gml = ost_export::gml_helper(&loops);
+    if fs::read_to_string(&gml_path).ok().as_deref() != Some(gml.as_str()) {
+        self_writes::record(&gml_path, gml.as_bytes());
+        fs::write(&gml_path, &gml)
+            .map_err(|e| format!("Failed to write {}: {e}", gml_path.display()))?;
+    }
+
+    let yy_path = script_dir.join(format!("{script_name}.yy"));
+    if !yy_path.is_file() {
+        ensure_gm_folders_value(project, &config.folder)?;
+        let parent = folder_reference(&config.folder);
+        let yy = serde_json::json!({
+            "$GMScript": "v1",
+            "%Name": script_name,
+            "isCompatibility": false,
+            "isDnD": false,
+            "name": script_name,
+            "parent": { "name": parent.name, "path": parent.path },
+            "resourceType": "GMScript",
+            "resourceVersion": "2.0",
+        });
+        let yy_json = serde_json::to_string_pretty(&yy)
+            .map_err(|e| format!("Failed to serialize script .yy: {e}"))?;
+        self_writes::record(&yy_path, yy_json.as_bytes());
+        fs::write(&yy_path, &yy_json).map_err(|e| format!("Failed to write script .yy: {e}"))?;
+    }
+
+    let resource_path = format!("scripts/{script_name}/{script_name}.yy");
+    upsert_resource(project, script_name, &resource_path)?;
+    println!(
+        "  Wrote script '{script_name}' ({} looping song{})",
+        loops.len(),
+        if loops.len() == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
+
+/// Reference to the GM folder `folder` (e.g. "Music" or "Audio/Music").
+fn folder_reference(folder: &str) -> ResourceReference {
+    ResourceReference {
+        name: folder.rsplit('/').next().unwrap_or(folder).to_string(),
+        path: format!("folders/{folder}.yy"),
+    }
+}
+
+/// Add `name` to the `.yyp` `AudioGroups` array unless it's already there.
+fn ensure_audio_group_value(project: &mut serde_json::Value, name: &str) -> Result<(), String> {
+    let groups = project
+        .get_mut("AudioGroups")
+        .and_then(|v| v.as_array_mut())
+        .ok_or_else(|| "Missing 'AudioGroups' array in .yyp".to_string())?;
+
+    let already_exists = groups
+        .iter()
+        .any(|g| g.get("name").and_then(|n| n.as_str()) == Some(name));
+    if !already_exists {
+        groups.push(serde_json::json!({
+            "$GMAudioGroup": "v1",
+            "%Name": name,
+            "name": name,
+            "resourceType": "GMAudioGroup",
+            "resourceVersion": "2.0",
+            "targets": -1,
+        }));
+    }
+    Ok(())
+}
+
+/// Fields preserved from an existing sound `.yy`.
+struct SoundOverrides {
+    audio_group_id: ResourceReference,
+    bit_depth: i32,
+    bit_rate: i32,
+    compression: i32,
+    conversion_mode: i32,
+    parent: ResourceReference,
+    preload: bool,
+    sample_rate: i32,
+    sound_type: i32,
+    volume: f64,
+}
+
+/// Try to read the settings of an existing sound's `.yy` file. Returns
+/// `None` if there is no such sound or its `.yy` can't be read.
+fn read_sound_overrides(sound_dir: &Path, sound_name: &str) -> Option<SoundOverrides> {
+    let yy_path = sound_dir.join(format!("{sound_name}.yy"));
+    let content = fs::read_to_string(&yy_path).ok()?;
+    let clean = strip_trailing_commas(&content);
+    let val: serde_json::Value = serde_json::from_str(&clean).ok()?;
+
+    let reference = |key: &str| -> Option<ResourceReference> {
+        let r = val.get(key)?;
+        Some(ResourceReference {
+            name: r.get("name")?.as_str()?.to_string(),
+            path: r.get("path")?.as_str()?.to_string(),
+        })
+    };
+
+    Some(SoundOverrides {
+        audio_group_id: reference("audioGroupId")?,
+        bit_depth: val.get("bitDepth")?.as_i64()? as i32,
+        bit_rate: val.get("bitRate")?.as_i64()? as i32,
+        compression: val.get("compression")?.as_i64()? as i32,
+        conversion_mode: val.get("conversionMode")?.as_i64()? as i32,
+        parent: reference("parent")?,
+        preload: val.get("preload")?.as_bool()?,
+        sample_rate: val.get("sampleRate")?.as_i64()? as i32,
+        sound_type: val.get("type")?.as_i64()? as i32,
+        volume: val.get("volume")?.as_f64()?,
+    })
+}
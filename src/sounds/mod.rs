@@ -0,0 +1,2 @@
+pub mod gm_import;
+pub mod models;
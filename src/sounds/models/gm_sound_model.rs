@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::project_config::{MusicConfig, SoundType};
+use crate::sprites::models::gm_sprite_model::ResourceReference;
+
+impl GMSoundModel {
+    /// Build a `GMSoundModel` for a new sound, ready to be serialized as a `.yy` file.
+    ///
+    /// * `name`       - sound resource name (e.g. "sndTitle")
+    /// * `sound_file` - the audio file beside the `.yy` (e.g. "sndTitle.ogg")
+    /// * `duration`   - length in seconds
+    /// * `channels`   - channel count of the audio; 2 or more means stereo
+    /// * `parent`     - the GM folder reference (name + folderPath)
+    /// * `config`     - compression, quality and audio group settings
+    pub fn new(
+        name: &str,
+        sound_file: &str,
+        duration: f64,
+        channels: u16,
+        parent: ResourceReference,
+        config: &MusicConfig,
+    ) -> Self {
+        Self {
+            gmsound: "v1".to_string(),
+            name_field: name.to_string(),
+            audio_group_id: ResourceReference {
+                name: config.audio_group.clone(),
+                path: format!("audiogroups/{}", config.audio_group),
+            },
+            // 0 is 8-bit, 1 is 16-bit.
+            bit_depth: if config.bit_depth == 8 { 0 } else { 1 },
+            bit_rate: config.bit_rate as i32,
+            compression: match config.sound_type {
+                SoundType::Compressed => 1,
+                SoundType::Decompressed => 2,
+                SoundType::Streamed => 3,
+            },
+            conversion_mode: 0,
+            duration,
+            name: name.to_string(),
+            parent,
+            preload: false,
+            resource_type: "GMSound".to_string(),
+            resource_version: "2.0".to_string(),
+            sample_rate: config.sample_rate as i32,
+            sound_file: sound_file.to_string(),
+            // 0 is mono, 1 is stereo.
+            sound_type: if channels >= 2 { 1 } else { 0 },
+            volume: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GMSoundModel {
+    #[serde(rename = "$GMSound")]
+    pub gmsound: String,
+
+    #[serde(rename = "%Name")]
+    pub name_field: String,
+
+    pub audio_group_id: ResourceReference,
+    pub bit_depth: i32,
+    pub bit_rate: i32,
+    pub compression: i32,
+    pub conversion_mode: i32,
+    pub duration: f64,
+    pub name: String,
+    pub parent: ResourceReference,
+    pub preload: bool,
+    pub resource_type: String,
+    pub resource_version: String,
+    pub sample_rate: i32,
+    pub sound_file: String,
+
+    #[serde(rename = "type")]
+    pub sound_type: i32,
+
+    pub volume: f64,
+}
@@ -0,0 +1 @@
+pub mod gm_sound_model;
@@ -5,6 +5,7 @@ use std::path::Path;
 use super::bbox::calculate_tight_bbox;
 use super::models::gm_project_model::GMFolder;
 use super::models::gm_sprite_model::{GMSpriteModel, ResourceReference};
+use crate::self_writes;
 
 /// Import a set of frames into a GameMaker project as a sprite resource.
 ///
@@ -26,11 +27,7 @@ pub fn import_sprite_to_project(
         .ok_or_else(|| "Could not determine project directory from .yyp path".to_string())?;
 
     // --- 1. Parse the .yyp as a generic Value to preserve exact field order ---
-    let yyp_content =
-        fs::read_to_string(project_path).map_err(|e| format!("Failed to read .yyp file: {e}"))?;
-    let yyp_clean = strip_trailing_commas(&yyp_content);
-    let mut project: serde_json::Value =
-        serde_json::from_str(&yyp_clean).map_err(|e| format!("Failed to parse .yyp JSON: {e}"))?;
+    let mut project = read_project_value(project_path)?;
 
     // --- 2. Read overrides from existing sprite if dimensions match ---
     let sprite_dir = project_dir.join("sprites").join(sprite_name);
@@ -75,6 +72,7 @@ pub fn import_sprite_to_project(
         let frame_path = sprite_dir.join(format!("{guid}.png"));
         rgba.save(&frame_path)
             .map_err(|e| format!("Failed to save frame {i} PNG: {e}"))?;
+        self_writes::record_file(&frame_path);
 
         // Layer copy: sprites/{sprite_name}/layers/{guid}/{layer_guid}.png
         let layer_frame_dir = layers_dir.join(guid);
@@ -84,6 +82,7 @@ pub fn import_sprite_to_project(
         let layer_frame_path = layer_frame_dir.join(format!("{layer_guid}.png"));
         rgba.save(&layer_frame_path)
             .map_err(|e| format!("Failed to save layer frame {i} PNG: {e}"))?;
+        self_writes::record_file(&layer_frame_path);
     }
 
     // --- 6. Calculate bounding box ---
@@ -126,6 +125,7 @@ pub fn import_sprite_to_project(
     let yy_path = sprite_dir.join(format!("{sprite_name}.yy"));
     let yy_json = serde_json::to_string_pretty(&sprite_model)
         .map_err(|e| format!("Failed to serialize sprite .yy: {e}"))?;
+    self_writes::record(&yy_path, yy_json.as_bytes());
     fs::write(&yy_path, &yy_json).map_err(|e| format!("Failed to write sprite .yy: {e}"))?;
 
     // --- 9. Ensure all folders exist in the .yyp ---
@@ -133,31 +133,10 @@ pub fn import_sprite_to_project(
 
     // --- 10. Add/replace the sprite resource in .yyp ---
     let resource_path = format!("sprites/{sprite_name}/{sprite_name}.yy");
-    {
-        let resources = project
-            .get_mut("resources")
-            .and_then(|v| v.as_array_mut())
-            .ok_or_else(|| "Missing 'resources' array in .yyp".to_string())?;
-
-        // Remove any existing entry with the same name
-        resources.retain(|entry| {
-            entry
-                .get("id")
-                .and_then(|id| id.get("name"))
-                .and_then(|n| n.as_str())
-                != Some(sprite_name)
-        });
-
-        // Push the new resource entry
-        resources.push(serde_json::json!({
-            "id": { "name": sprite_name, "path": resource_path }
-        }));
-    }
+    upsert_resource(&mut project, sprite_name, &resource_path)?;
 
     // --- 11. Write the .yyp back to disk ---
-    let yyp_json = serde_json::to_string_pretty(&project)
-        .map_err(|e| format!("Failed to serialize .yyp: {e}"))?;
-    fs::write(project_path, &yyp_json).map_err(|e| format!("Failed to write .yyp: {e}"))?;
+    write_project_value(project_path, &project)?;
 
     println!(
         "  Imported sprite '{sprite_name}' ({} frame{}) into {}",
@@ -169,11 +148,56 @@ pub fn import_sprite_to_project(
     Ok(())
 }
 
+/// Read a `.yyp` as a generic `Value`, which keeps the exact field order on
+/// write-back.
+pub fn read_project_value(project_path: &Path) -> Result<serde_json::Value, String> {
+    let yyp_content =
+        fs::read_to_string(project_path).map_err(|e| format!("Failed to read .yyp file: {e}"))?;
+    let yyp_clean = strip_trailing_commas(&yyp_content);
+    serde_json::from_str(&yyp_clean).map_err(|e| format!("Failed to parse .yyp JSON: {e}"))
+}
+
+/// Write a `.yyp` `Value` back to disk, recording it as gmhelper's own write.
+pub fn write_project_value(project_path: &Path, project: &serde_json::Value) -> Result<(), String> {
+    let yyp_json = serde_json::to_string_pretty(project)
+        .map_err(|e| format!("Failed to serialize .yyp: {e}"))?;
+    self_writes::record(project_path, yyp_json.as_bytes());
+    fs::write(project_path, &yyp_json).map_err(|e| format!("Failed to write .yyp: {e}"))
+}
+
+/// Add a resource entry to the `.yyp` `resources` array, replacing any
+/// existing entry with the same name.
+pub fn upsert_resource(
+    project: &mut serde_json::Value,
+    name: &str,
+    resource_path: &str,
+) -> Result<(), String> {
+    let resources = project
+        .get_mut("resources")
+        .and_then(|v| v.as_array_mut())
+        .ok_or_else(|| "Missing 'resources' array in .yyp".to_string())?;
+
+    // Remove any existing entry with the same name
+    resources.retain(|entry| {
+        entry
+            .get("id")
+            .and_then(|id| id.get("name"))
+            .and_then(|n| n.as_str())
+            != Some(name)
+    });
+
+    // Push the new resource entry
+    resources.push(serde_json::json!({
+        "id": { "name": name, "path": resource_path }
+    }));
+    Ok(())
+}
+
 /// Ensure that every intermediate folder in `gm_folder_path` exists in the
 /// `.yyp` `Folders` array. For example, `"Sprites/Enemies/Bosses"` will ensure
 /// entries for `"Sprites"`, `"Sprites/Enemies"`, and `"Sprites/Enemies/Bosses"`.
 /// Operates directly on the `serde_json::Value` to preserve field ordering.
-fn ensure_gm_folders_value(
+pub fn ensure_gm_folders_value(
     project: &mut serde_json::Value,
     gm_folder_path: &str,
 ) -> Result<(), String> {
@@ -314,7 +338,7 @@ fn read_sprite_overrides(
 /// Remove trailing commas from JSON text (commas before `]` or `}`).
 /// GameMaker's JSON files commonly include trailing commas which standard
 /// JSON parsers reject.
-fn strip_trailing_commas(json: &str) -> String {
+pub fn strip_trailing_commas(json: &str) -> String {
     let mut result = String::with_capacity(json.len());
     let mut in_string = false;
     let mut escape_next = false;
@@ -0,0 +1,736 @@
+//! Project-wide index of GML definitions and references, built from the code
+//! files of every resource listed in the `.yyp`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::gml::ast::{self, DeclareKind, Expr, ExprKind, Ident, Stmt, StmtKind, Visitor};
+use crate::gml::{self, LineIndex, Span, TokenKind};
+use crate::sprites::gm_import;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Function,
+    Constructor,
+    Macro,
+    Enum,
+    EnumMember,
+    GlobalVariable,
+    /// A `.yyp` resource; the string is its folder type (`objects`, ...).
+    Resource(&'static str),
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolKind::Function => write!(f, "function"),
+            SymbolKind::Constructor => write!(f, "constructor"),
+            SymbolKind::Macro => write!(f, "macro"),
+            SymbolKind::Enum => write!(f, "enum"),
+            SymbolKind::EnumMember => write!(f, "enum member"),
+            SymbolKind::GlobalVariable => write!(f, "global"),
+            SymbolKind::Resource(kind) => write!(f, "{}", kind.trim_end_matches('s')),
+        }
+    }
+}
+
+/// A position in a project file; `line` and `column` are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// Enum members are qualified: `eState.Idle`.
+    pub name: String,
+    pub kind: SymbolKind,
+    pub location: Location,
+    /// The name in the code file; `None` for resources, defined by their `.yy`.
+    pub span: Option<Span>,
+}
+
+/// A resource entry from the `.yyp`.
+#[derive(Debug, Clone)]
+pub struct ProjectResource {
+    pub name: String,
+    /// Folder type from the resource path: `scripts`, `objects`, `rooms`, ...
+    pub kind: &'static str,
+    /// The resource's `.yy`, relative to the project folder.
+    pub yy_path: PathBuf,
+}
+
+/// A code file belonging to a resource, kept parsed for lookups.
+pub struct SourceFile {
+    /// Path as shown to the user: the project folder joined with the
+    /// resource-relative path.
+    pub path: PathBuf,
+    pub source: String,
+    pub program: gml::Program,
+    pub lines: LineIndex,
+}
+
+impl SourceFile {
+    fn new(path: PathBuf, source: String) -> Self {
+        let program = gml::parse(&source);
+        let lines = LineIndex::new(&source);
+        Self {
+            path,
+            source,
+            program,
+            lines,
+        }
+    }
+
+    pub fn location(&self, offset: usize) -> Location {
+        let (line, column) = self.lines.line_col(offset);
+        Location {
+            file: self.path.clone(),
+            line,
+            column,
+        }
+    }
+
+    /// The text of the 1-based `line`, without its line break.
+    pub fn line_text(&self, line: usize) -> &str {
+        self.source.lines().nth(line - 1).unwrap_or_default()
+    }
+
+    /// Whether `span` is the name in a `{ name }` shorthand field, which is
+    /// both a struct key and a read of the variable `name`.
+    pub fn is_struct_shorthand(&self, span: Span) -> bool {
+        struct Finder {
+            span: Span,
+            found: bool,
+        }
+
+        impl Visitor for Finder {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if let ExprKind::Struct(fields) = &expr.kind {
+                    self.found |= fields
+                        .iter()
+                        .any(|f| f.value.is_none() && f.name.span == self.span);
+                }
+                ast::walk_expr(self, expr);
+            }
+        }
+
+        let mut finder = Finder { span, found: false };
+        ast::walk_stmts(&mut finder, &self.program.statements);
+        finder.found
+    }
+}
+
+pub struct ProjectIndex {
+    pub project_dir: PathBuf,
+    pub resources: Vec<ProjectResource>,
+    pub files: Vec<SourceFile>,
+    pub symbols: Vec<Symbol>,
+}
+
+/// Resource folder types, so kinds can be `&'static str`.
+const RESOURCE_KINDS: &[&str] = &[
+    "animcurves",
+    "extensions",
+    "fonts",
+    "notes",
+    "objects",
+    "particles",
+    "paths",
+    "rooms",
+    "scripts",
+    "sequences",
+    "shaders",
+    "sounds",
+    "sprites",
+    "tilesets",
+    "timelines",
+];
+
+impl ProjectIndex {
+    /// Read the `.yyp` and parse the code of every resource it lists.
+    pub fn build(yyp_path: &Path) -> Result<Self, String> {
+        if yyp_path.extension().and_then(|e| e.to_str()) != Some("yyp") {
+            return Err(format!(
+                "'{}' is not a .yyp file. Provide a valid GameMaker project file.",
+                yyp_path.display()
+            ));
+        }
+        let project_dir = yyp_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let project = gm_import::read_project_value(yyp_path)?;
+        let resources = read_resources(&project);
+
+        let mut index = Self {
+            project_dir,
+            resources: Vec::new(),
+            files: Vec::new(),
+            symbols: Vec::new(),
+        };
+
+        for resource in &resources {
+            index.symbols.push(Symbol {
+                name: resource.name.clone(),
+                kind: SymbolKind::Resource(resource.kind),
+                location: Location {
+                    file: index.project_dir.join(&resource.yy_path),
+                    line: 1,
+                    column: 1,
+                },
+                span: None,
+            });
+            for path in code_files(&index.project_dir, resource) {
+                if let Ok(source) = fs::read_to_string(&path) {
+                    index.files.push(SourceFile::new(path, source));
+                }
+            }
+        }
+
+        for file in &index.files {
+            index.symbols.extend(collect_definitions(file));
+        }
+        index.resources = resources;
+        Ok(index)
+    }
+
+    /// An index of in-memory code files, without any resources.
+    #[cfg(test)]
+    pub(crate) fn from_sources(files: &[(&str, &str)]) -> Self {
+        let files: Vec<SourceFile> = files
+            .iter()
+            .map(|(path, source)| SourceFile::new(PathBuf::from(path), source.to_string()))
+            .collect();
+        let symbols = files.iter().flat_map(collect_definitions).collect();
+        Self {
+            project_dir: PathBuf::new(),
+            resources: Vec::new(),
+            files,
+            symbols,
+        }
+    }
+
+    /// Swap in new text for one of the project's code files, e.g. an unsaved
+    /// editor buffer, and re-collect its definitions. Returns `false` for
+    /// files that don't belong to an indexed resource.
+    pub fn update_source(&mut self, path: &Path, source: String) -> bool {
+        let Some(slot) = self.files.iter().position(|f| f.path == path) else {
+            return false;
+        };
+        self.files[slot] = SourceFile::new(path.to_path_buf(), source);
+        self.symbols.retain(|s| s.location.file != path);
+        self.symbols.extend(collect_definitions(&self.files[slot]));
+        true
+    }
+
+    /// Definitions named exactly `name`.
+    pub fn definitions<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Symbol> {
+        self.symbols.iter().filter(move |s| s.name == name)
+    }
+
+    /// Every use of `name` in code, definitions included. A dotted name such
+    /// as `eState.Idle` matches that exact member access. As in lint, each
+    /// event, script and function body is one scope, and a name declared in
+    /// it with `var`, `static`, as a parameter or as a `catch` binding is a
+    /// local there, so none of that scope's uses count. Field accesses
+    /// (`other.name`) and struct keys aren't uses either, and strings and
+    /// comments never match.
+    pub fn references(&self, name: &str) -> Vec<(&SourceFile, Span)> {
+        let parts: Vec<&str> = name.split('.').collect();
+        let mut found = Vec::new();
+        for file in &self.files {
+            for span in collect_references(file, &parts, None).found {
+                found.push((file, span));
+            }
+        }
+        found
+    }
+
+    /// Declarations of `local` (as a `var`, `static`, parameter or `catch`
+    /// binding) in scopes where `name` refers to the global symbol. Renaming
+    /// `name` to `local` would turn those uses into uses of the local.
+    pub fn local_conflicts(&self, name: &str, local: &str) -> Vec<(&SourceFile, Span)> {
+        let parts: Vec<&str> = name.split('.').collect();
+        let mut conflicts = Vec::new();
+        for file in &self.files {
+            for span in collect_references(file, &parts, Some(local)).conflicts {
+                conflicts.push((file, span));
+            }
+        }
+        conflicts
+    }
+}
+
+/// Spans of `parts[0] . parts[1] . ...` in `source`, each covering the last
+/// identifier of the sequence.
+pub fn identifier_sequences(source: &str, parts: &[&str]) -> Vec<Span> {
+    let tokens: Vec<gml::Token> = gml::tokenize(source)
+        .tokens
+        .into_iter()
+        .filter(|t| !t.is_comment())
+        .collect();
+    let pattern_len = parts.len() * 2 - 1;
+
+    let mut spans = Vec::new();
+    for window in tokens.windows(pattern_len) {
+        let matches = window.iter().enumerate().all(|(i, token)| {
+            let text = token.span.text(source);
+            if i % 2 == 0 {
+                token.kind == TokenKind::Ident && text == parts[i / 2]
+            } else {
+                token.kind == TokenKind::Punct && text == "."
+            }
+        });
+        if matches {
+            spans.push(window[pattern_len - 1].span);
+        }
+    }
+    spans
+}
+
+/// The uses of `parts` in `file` that refer to the global symbol, in source
+/// order, and the declarations of the local `conflicting` in their scopes.
+fn collect_references<'a>(
+    file: &SourceFile,
+    parts: &'a [&'a str],
+    conflicting: Option<&'a str>,
+) -> ReferenceCollector<'a> {
+    let mut collector = ReferenceCollector {
+        parts,
+        conflicting,
+        scopes: vec![Scope::default()],
+        found: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    ast::walk_stmts(&mut collector, &file.program.statements);
+    collector.pop_scope();
+    collector.found.sort_by_key(|span| span.start);
+    collector.found.dedup();
+    collector.conflicts.sort_by_key(|span| span.start);
+    collector
+}
+
+/// Uses of a name in one event, script or function body.
+#[derive(Default)]
+struct Scope {
+    /// The name is declared as a local somewhere in the body.
+    shadowed: bool,
+    uses: Vec<Span>,
+    /// Where the body declares the conflicting local.
+    conflicting: Vec<Span>,
+}
+
+struct ReferenceCollector<'a> {
+    parts: &'a [&'a str],
+    /// A local name that would capture the uses found in its scope.
+    conflicting: Option<&'a str>,
+    /// Innermost last.
+    scopes: Vec<Scope>,
+    found: Vec<Span>,
+    conflicts: Vec<Span>,
+}
+
+impl ReferenceCollector<'_> {
+    fn is_name(&self, name: &str) -> bool {
+        self.parts == [name]
+    }
+
+    fn declare_local(&mut self, name: &Ident) {
+        let conflicting = self.conflicting == Some(name.name.as_str());
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if name.name == self.parts[0] {
+            scope.shadowed = true;
+        }
+        if conflicting {
+            scope.conflicting.push(name.span);
+        }
+    }
+
+    /// A use that a local of the same name would shadow.
+    fn add_use(&mut self, span: Span) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.uses.push(span);
+        }
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop()
+            && !scope.shadowed
+            && !scope.uses.is_empty()
+        {
+            self.found.extend(scope.uses);
+            self.conflicts.extend(scope.conflicting);
+        }
+    }
+
+    /// Whether `expr` is the member access `parts[0].parts[1]...`.
+    fn is_path(expr: &Expr, parts: &[&str]) -> bool {
+        match (&expr.kind, parts) {
+            (ExprKind::Ident(name), [only]) => name == only,
+            (ExprKind::Member { object, field }, [rest @ .., last]) if !rest.is_empty() => {
+                field.name == *last && Self::is_path(object, rest)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Visitor for ReferenceCollector<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Declare { kind, vars } => {
+                for var in vars {
+                    if *kind == DeclareKind::GlobalVar {
+                        if self.is_name(&var.name.name) {
+                            self.found.push(var.name.span);
+                        }
+                    } else {
+                        self.declare_local(&var.name);
+                    }
+                }
+            }
+            StmtKind::Try {
+                catch: Some(catch), ..
+            } => {
+                if let Some(binding) = &catch.binding {
+                    self.declare_local(binding);
+                }
+            }
+            StmtKind::Macro(decl) => {
+                if self.is_name(&decl.name.name) {
+                    self.found.push(decl.name.span);
+                }
+                // Macro values are expanded where they're used, but whatever
+                // they name is resolved there like any other global.
+                let value_start = decl.value_span.start;
+                for span in identifier_sequences(&decl.value, self.parts) {
+                    self.found
+                        .push(Span::new(span.start + value_start, span.end + value_start));
+                }
+            }
+            StmtKind::Enum(decl) => match self.parts {
+                [name] if *name == decl.name.name => self.found.push(decl.name.span),
+                [name, member] if *name == decl.name.name => {
+                    for m in decl.members.iter().filter(|m| m.name.name == *member) {
+                        self.found.push(m.name.span);
+                    }
+                }
+                _ => {}
+            },
+            StmtKind::Function(function) if self.scopes.len() == 1 => {
+                if let Some(name) = &function.name
+                    && self.is_name(&name.name)
+                {
+                    self.found.push(name.span);
+                }
+            }
+            _ => {}
+        }
+        ast::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Ident(name) if self.is_name(name) => self.add_use(expr.span),
+            ExprKind::Member { object, field } => {
+                if self.parts.len() > 1 && Self::is_path(expr, self.parts) {
+                    self.add_use(field.span);
+                } else if object.kind == ExprKind::Ident("global".into())
+                    && self.is_name(&field.name)
+                {
+                    self.found.push(field.span);
+                }
+            }
+            ExprKind::Struct(fields) => {
+                // `{ x }` reads the variable `x`; other keys are just keys.
+                for field in fields.iter().filter(|f| f.value.is_none()) {
+                    if self.is_name(&field.name.name) {
+                        self.add_use(field.name.span);
+                    }
+                }
+            }
+            _ => {}
+        }
+        ast::walk_expr(self, expr);
+    }
+
+    fn visit_function(&mut self, function: &ast::Function) {
+        if let Some(parent) = &function.parent
+            && self.is_name(&parent.name.name)
+        {
+            self.found.push(parent.name.span);
+        }
+        // Functions can't see the locals of the code around them.
+        self.scopes.push(Scope::default());
+        for param in &function.params {
+            self.declare_local(&param.name);
+        }
+        ast::walk_function(self, function);
+        self.pop_scope();
+    }
+}
+
+fn read_resources(project: &serde_json::Value) -> Vec<ProjectResource> {
+    let Some(entries) = project.get("resources").and_then(|r| r.as_array()) else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let id = entry.get("id")?;
+            let name = id.get("name")?.as_str()?;
+            let path = id.get("path")?.as_str()?;
+            let folder = path.split('/').next()?;
+            let kind = RESOURCE_KINDS
+                .iter()
+                .find(|k| k.eq_ignore_ascii_case(folder))
+                .copied()?;
+            Some(ProjectResource {
+                name: name.to_string(),
+                kind,
+                yy_path: PathBuf::from(path),
+            })
+        })
+        .collect()
+}
+
+/// GML files that belong to a resource: a script's `.gml`, every object
+/// event, and a room's creation and instance creation code.
+fn code_files(project_dir: &Path, resource: &ProjectResource) -> Vec<PathBuf> {
+    let Some(dir) = resource.yy_path.parent().map(|d| project_dir.join(d)) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = match resource.kind {
+        "scripts" => vec![dir.join(format!("{}.gml", resource.name))],
+        "objects" | "rooms" => dir
+            .read_dir()
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("gml"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    files.retain(|f| f.is_file());
+    files.sort();
+    files
+}
+
+fn collect_definitions(file: &SourceFile) -> Vec<Symbol> {
+    let mut collector = DefinitionCollector {
+        file,
+        symbols: Vec::new(),
+        depth: 0,
+    };
+    ast::walk_stmts(&mut collector, &file.program.statements);
+    collector.symbols
+}
+
+struct DefinitionCollector<'a> {
+    file: &'a SourceFile,
+    symbols: Vec<Symbol>,
+    /// Function nesting; only top-level functions are global.
+    depth: usize,
+}
+
+impl DefinitionCollector<'_> {
+    fn add(&mut self, name: String, kind: SymbolKind, span: Span) {
+        self.symbols.push(Symbol {
+            name,
+            kind,
+            location: self.file.location(span.start),
+            span: Some(span),
+        });
+    }
+}
+
+impl Visitor for DefinitionCollector<'_> {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Macro(decl) => {
+                self.add(decl.name.name.clone(), SymbolKind::Macro, decl.name.span);
+            }
+            StmtKind::Enum(decl) => {
+                self.add(decl.name.name.clone(), SymbolKind::Enum, decl.name.span);
+                for member in &decl.members {
+                    self.add(
+                        format!("{}.{}", decl.name.name, member.name.name),
+                        SymbolKind::EnumMember,
+                        member.name.span,
+                    );
+                }
+            }
+            StmtKind::Declare {
+                kind: DeclareKind::GlobalVar,
+                vars,
+            } => {
+                for var in vars {
+                    self.add(
+                        var.name.name.clone(),
+                        SymbolKind::GlobalVariable,
+                        var.name.span,
+                    );
+                }
+            }
+            StmtKind::Function(function) if self.depth == 0 => {
+                if let Some(name) = &function.name {
+                    let kind = if function.is_constructor {
+                        SymbolKind::Constructor
+                    } else {
+                        SymbolKind::Function
+                    };
+                    self.add(name.name.clone(), kind, name.span);
+                }
+            }
+            StmtKind::Expr(Expr {
+                kind: ExprKind::Assign { op, target, .. },
+                ..
+            }) => {
+                // `global.name = ...` defines (or redefines) a global.
+                if op == "="
+                    && let ExprKind::Member { object, field } = &target.kind
+                    && object.kind == ExprKind::Ident("global".into())
+                {
+                    self.add(field.name.clone(), SymbolKind::GlobalVariable, field.span);
+                }
+            }
+            _ => {}
+        }
+        ast::walk_stmt(self, stmt);
+    }
+
+    fn visit_function(&mut self, function: &ast::Function) {
+        self.depth += 1;
+        ast::walk_function(self, function);
+        self.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(file, line)` of every reference to `name`.
+    fn references(index: &ProjectIndex, name: &str) -> Vec<(String, usize)> {
+        index
+            .references(name)
+            .into_iter()
+            .map(|(file, span)| {
+                (
+                    file.path.display().to_string(),
+                    file.location(span.start).line,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn var_in_the_same_scope_shadows_the_global() {
+        let index = ProjectIndex::from_sources(&[
+            ("a.gml", "globalvar score;\nscore = 0;"),
+            ("b.gml", "var score = 10;\nshow_debug_message(score);"),
+            ("c.gml", "draw_text(0, 0, score);"),
+        ]);
+        assert_eq!(
+            references(&index, "score"),
+            [
+                ("a.gml".into(), 1),
+                ("a.gml".into(), 2),
+                ("c.gml".into(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn var_declared_later_still_shadows_the_whole_scope() {
+        let index = ProjectIndex::from_sources(&[("a.gml", "speed_up();\nhp = 1;\nvar hp = 2;")]);
+        assert!(references(&index, "hp").is_empty());
+    }
+
+    #[test]
+    fn parameters_and_catch_bindings_shadow() {
+        let index = ProjectIndex::from_sources(&[(
+            "a.gml",
+            "function f(player) {\n    return player;\n}\n\
+             try { x = 1; } catch (player) { show_debug_message(player); }\n\
+             function g() {\n    return player;\n}",
+        )]);
+        assert_eq!(references(&index, "player"), [("a.gml".into(), 6)]);
+    }
+
+    #[test]
+    fn functions_do_not_see_outer_locals() {
+        let index = ProjectIndex::from_sources(&[(
+            "a.gml",
+            "var hp = 1;\nfunction heal() {\n    return hp + 1;\n}",
+        )]);
+        assert_eq!(references(&index, "hp"), [("a.gml".into(), 3)]);
+    }
+
+    #[test]
+    fn member_accesses_and_struct_keys_are_not_uses() {
+        let index = ProjectIndex::from_sources(&[(
+            "a.gml",
+            "other.score = 1;\nvar s = { score: 2 };\nvar t = { score };\nglobal.score = 3;",
+        )]);
+        assert_eq!(
+            references(&index, "score"),
+            [("a.gml".into(), 3), ("a.gml".into(), 4)]
+        );
+    }
+
+    #[test]
+    fn enum_members_match_the_qualified_access() {
+        let index = ProjectIndex::from_sources(&[(
+            "a.gml",
+            "enum eState { Idle, Run }\nstate = eState.Idle;\nother_state = s.Idle;",
+        )]);
+        assert_eq!(
+            references(&index, "eState.Idle"),
+            [("a.gml".into(), 1), ("a.gml".into(), 2)]
+        );
+        assert_eq!(
+            references(&index, "eState"),
+            [("a.gml".into(), 1), ("a.gml".into(), 2)]
+        );
+    }
+
+    #[test]
+    fn macro_definitions_and_values() {
+        let index = ProjectIndex::from_sources(&[(
+            "a.gml",
+            "#macro SCALE 2\n#macro DOUBLE_SCALE (SCALE * 2)\nx = SCALE; // SCALE\ny = \"SCALE\";",
+        )]);
+        assert_eq!(
+            references(&index, "SCALE"),
+            [
+                ("a.gml".into(), 1),
+                ("a.gml".into(), 2),
+                ("a.gml".into(), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn constructor_parents_are_uses() {
+        let index = ProjectIndex::from_sources(&[(
+            "a.gml",
+            "function Shape() constructor {}\nfunction Circle() : Shape() constructor {}",
+        )]);
+        assert_eq!(
+            references(&index, "Shape"),
+            [("a.gml".into(), 1), ("a.gml".into(), 2)]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Log of gmhelper's automatic rewrites of user source (command expansion,
+//! formatting, rename), so the last one to a file can be undone.
+//!
+//! The index lives in `<data dir>/rewrites/index.json`; the contents before
+//! and after each rewrite are stored next to it as `<id>.before` / `<id>.after`.
+//! Several processes (a running `dev`, `fmt`, `undo`) share it, so every
+//! change to the index goes through [data_file::update].
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{code_editor, data_file, history};
+
+const MAX_ENTRIES: usize = 200;
+const FOLDER: &str = "rewrites";
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RewriteKind {
+    Expand,
+    Format,
+    Rename,
+    LiveMacros,
+}
+
+impl std::fmt::Display for RewriteKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RewriteKind::Expand => "command expansion",
+            RewriteKind::Format => "formatting",
+            RewriteKind::Rename => "rename",
+            RewriteKind::LiveMacros => "live macro rewrite",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rewrite {
+    /// Names the `.before` / `.after` content files.
+    pub id: String,
+    /// Absolute path of the rewritten file.
+    pub file: PathBuf,
+    pub kind: RewriteKind,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RewriteLog {
+    /// Newest first.
+    pub entries: Vec<Rewrite>,
+}
+
+fn log_dir() -> io::Result<PathBuf> {
+    Ok(history::data_dir()?.join(FOLDER))
+}
+
+pub fn load() -> RewriteLog {
+    match log_dir() {
+        Ok(dir) => data_file::read(&dir.join(INDEX_FILE)),
+        Err(_) => RewriteLog::default(),
+    }
+}
+
+/// Change the index under its lock.
+fn update<R>(change: impl FnOnce(&mut RewriteLog) -> R) -> io::Result<R> {
+    data_file::update(&log_dir()?.join(INDEX_FILE), change)
+}
+
+fn content_path(dir: &Path, id: &str, side: &str) -> PathBuf {
+    dir.join(format!("{id}.{side}"))
+}
+
+/// The same file always maps to the same key, however it was named.
+fn normalize(file: &Path) -> PathBuf {
+    fs::canonicalize(file)
+        .or_else(|_| std::path::absolute(file))
+        .unwrap_or_else(|_| file.to_path_buf())
+}
+
+/// Log that `file` went from `before` to `after`, dropping the oldest
+/// entries beyond [MAX_ENTRIES].
+pub fn record(file: &Path, kind: RewriteKind, before: &[u8], after: &[u8]) -> io::Result<()> {
+    if before == after {
+        return Ok(());
+    }
+    let dir = log_dir()?;
+    fs::create_dir_all(&dir)?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let id = format!("{}-{}", now.as_nanos(), std::process::id());
+    fs::write(content_path(&dir, &id, "before"), before)?;
+    fs::write(content_path(&dir, &id, "after"), after)?;
+
+    let entry = Rewrite {
+        id,
+        file: normalize(file),
+        kind,
+        time: now.as_secs(),
+    };
+    let dropped = update(|log| {
+        log.entries.insert(0, entry);
+        log.entries.split_off(MAX_ENTRIES.min(log.entries.len()))
+    })?;
+    for dropped in dropped {
+        remove_contents(&dir, &dropped.id);
+    }
+    Ok(())
+}
+
+fn remove_contents(dir: &Path, id: &str) {
+    let _ = fs::remove_file(content_path(dir, id, "before"));
+    let _ = fs::remove_file(content_path(dir, id, "after"));
+}
+
+/// Point entries for `from`, or files under it, at `to` after a move.
+pub fn relocate(from: &Path, to: &Path) -> io::Result<()> {
+    // `from` is gone by now, so only its parent can be resolved.
+    let from = match (from.parent(), from.file_name()) {
+        (Some(parent), Some(name)) => normalize(parent).join(name),
+        _ => std::path::absolute(from)?,
+    };
+    let to = normalize(to);
+    update(|log| {
+        for entry in &mut log.entries {
+            if let Ok(rest) = entry.file.strip_prefix(&from) {
+                entry.file = if rest.as_os_str().is_empty() {
+                    to.clone()
+                } else {
+                    to.join(rest)
+                };
+            }
+        }
+    })
+}
+
+/// Put back the contents from before the last rewrite of `file` (or of the
+/// most recently rewritten file). Refuses when the file changed since, so
+/// edits made after the rewrite are never lost.
+pub fn undo(file: Option<&Path>) -> Result<Rewrite, String> {
+    let log = load();
+    let key = file.map(normalize);
+    let position = log
+        .entries
+        .iter()
+        .position(|e| key.as_ref().is_none_or(|k| e.file == *k))
+        .ok_or_else(|| match file {
+            Some(file) => format!("No logged rewrites of {}", file.display()),
+            None => "No logged rewrites".to_string(),
+        })?;
+    let entry = log.entries[position].clone();
+
+    let dir = log_dir().map_err(|e| e.to_string())?;
+    let read = |side: &str| {
+        fs::read(content_path(&dir, &entry.id, side)).map_err(|e| {
+            format!(
+                "Logged contents of {} are missing: {e}",
+                entry.file.display()
+            )
+        })
+    };
+    let (before, after) = (read("before")?, read("after")?);
+    let current = fs::read(&entry.file)
+        .map_err(|e| format!("Failed to read {}: {e}", entry.file.display()))?;
+    if current != after {
+        return Err(format!(
+            "{} was edited after gmhelper's {} {}; not undoing",
+            entry.file.display(),
+            entry.kind,
+            ago(entry.time)
+        ));
+    }
+
+    // Recorded as gmhelper's own write, so a `dev`/`reload` running in
+    // another process doesn't expand or format the restored text again.
+    code_editor::write_source(&entry.file, &before)
+        .map_err(|e| format!("Failed to write {}: {e}", entry.file.display()))?;
+    update(|log| log.entries.retain(|e| e.id != entry.id))
+        .map_err(|e| format!("Failed to update the rewrite log: {e}"))?;
+    remove_contents(&dir, &entry.id);
+    Ok(entry)
+}
+
+pub fn list_text(log: &RewriteLog) -> String {
+    if log.entries.is_empty() {
+        return "No rewrites logged yet.\n".to_string();
+    }
+    let mut out = String::new();
+    for entry in &log.entries {
+        out.push_str(&format!(
+            "{:<10} {:<20} {}\n",
+            ago(entry.time),
+            entry.kind.to_string(),
+            entry.file.display()
+        ));
+    }
+    out
+}
+
+/// "just now", "5m ago", "3h ago", "2d ago".
+fn ago(time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(time);
+    match now.saturating_sub(time) {
+        0..60 => "just now".to_string(),
+        s @ 60..3600 => format!("{}m ago", s / 60),
+        s @ 3600..86400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86400),
+    }
+}